@@ -2,7 +2,7 @@ use super::{BlockDevice, BLOCK_SZ};
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use lazy_static::*;
-use spin::Mutex;
+use crate::lock::Mutex;
 /// Cached block inside memory
 pub struct BlockCache {
     /// cached block data
@@ -7,6 +7,7 @@ mod block_cache;
 mod block_dev;
 mod efs;
 mod layout;
+mod lock;
 mod vfs;
 /// Use a block size of 512 bytes
 pub const BLOCK_SZ: usize = 512;
@@ -14,5 +15,7 @@ use bitmap::Bitmap;
 use block_cache::{block_cache_sync_all, get_block_cache};
 pub use block_dev::BlockDevice;
 pub use efs::EasyFileSystem;
+#[cfg(feature = "lockdep")]
+pub use lock::{set_lock_hooks, LockHooks};
 use layout::*;
 pub use vfs::Inode;
@@ -0,0 +1,97 @@
+//! `spin::Mutex` that tells the kernel's lock validator about every lock
+//! taken, when built with the `lockdep` feature
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "lockdep")]
+use core::panic::Location;
+#[cfg(feature = "lockdep")]
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+/// Called with the address of a lock, so the kernel can check the order
+/// locks are taken in
+#[cfg(feature = "lockdep")]
+pub struct LockHooks {
+    /// before the lock is taken, with where it is taken
+    pub acquire: fn(usize, &'static Location<'static>),
+    /// once it is released
+    pub release: fn(usize),
+    /// when the lock goes away, its address may be reused
+    pub forget: fn(usize),
+}
+
+#[cfg(feature = "lockdep")]
+static HOOKS: AtomicPtr<LockHooks> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Report locks taken from now on to `hooks`
+#[cfg(feature = "lockdep")]
+pub fn set_lock_hooks(hooks: &'static LockHooks) {
+    HOOKS.store(hooks as *const _ as *mut _, Ordering::Release);
+}
+
+#[cfg(feature = "lockdep")]
+fn hooks() -> Option<&'static LockHooks> {
+    unsafe { HOOKS.load(Ordering::Acquire).as_ref() }
+}
+
+/// A spin lock
+pub struct Mutex<T> {
+    inner: spin::Mutex<T>,
+}
+
+impl<T> Mutex<T> {
+    /// Create a new unlocked mutex
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: spin::Mutex::new(value),
+        }
+    }
+    /// Spin until the lock is taken
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        #[cfg(feature = "lockdep")]
+        if let Some(hooks) = hooks() {
+            (hooks.acquire)(self as *const _ as usize, Location::caller());
+        }
+        MutexGuard {
+            mutex: self,
+            guard: self.inner.lock(),
+        }
+    }
+}
+
+#[cfg(feature = "lockdep")]
+impl<T> Drop for Mutex<T> {
+    fn drop(&mut self) {
+        if let Some(hooks) = hooks() {
+            (hooks.forget)(self as *const _ as usize);
+        }
+    }
+}
+
+/// Access to the data of a locked `Mutex`, released when dropped
+pub struct MutexGuard<'a, T> {
+    #[cfg_attr(not(feature = "lockdep"), allow(dead_code))]
+    mutex: &'a Mutex<T>,
+    guard: spin::MutexGuard<'a, T>,
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+#[cfg(feature = "lockdep")]
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(hooks) = hooks() {
+            (hooks.release)(self.mutex as *const _ as usize);
+        }
+    }
+}
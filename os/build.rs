@@ -1,10 +1,17 @@
-use std::fs::{File, read_dir};
+use rustc_demangle::demangle;
+use std::env;
+use std::fmt::Write as _;
+use std::fs::{self, File, read_dir};
 use std::io::{Result, Write};
+use xmas_elf::ElfFile;
+use xmas_elf::sections::SectionData;
+use xmas_elf::symbol_table::{Entry, Type};
 
 fn main() {
     println!("cargo:rerun-if-changed=../user/src/");
     println!("cargo:rerun-if-changed={}", TARGET_PATH);
     insert_app_data().unwrap();
+    insert_ksyms().unwrap();
 }
 
 static TARGET_PATH: &str = "../user/target/riscv64gc-unknown-none-elf/release/";
@@ -65,3 +72,82 @@ app_{0}_end:"#,
     }
     Ok(())
 }
+
+/// Function symbols of the kernel as last linked, demangled and sorted
+fn kernel_symbols(elf_path: &str) -> Vec<(u64, u64, String)> {
+    let mut symbols = Vec::new();
+    let Ok(data) = fs::read(elf_path) else {
+        return symbols;
+    };
+    let Ok(elf) = ElfFile::new(&data) else {
+        return symbols;
+    };
+    for section in elf.section_iter() {
+        let Ok(SectionData::SymbolTable64(entries)) = section.get_data(&elf) else {
+            continue;
+        };
+        for entry in entries {
+            if entry.get_type() != Ok(Type::Func) || entry.size() == 0 {
+                continue;
+            }
+            if let Ok(name) = entry.get_name(&elf) {
+                symbols.push((entry.value(), entry.size(), format!("{:#}", demangle(name))));
+            }
+        }
+    }
+    symbols.sort();
+    symbols.dedup_by_key(|symbol| symbol.0);
+    symbols
+}
+
+/// Write src/ksyms.S, the symbol table backtraces are printed with. It is
+/// taken from the kernel of the previous build, so a fresh tree gets an
+/// empty table and the Makefile links twice. Text comes before all data
+/// in the linker script, so the table itself moves no function.
+fn insert_ksyms() -> Result<()> {
+    let elf_path = format!(
+        "target/{}/{}/os",
+        env::var("TARGET").unwrap(),
+        env::var("PROFILE").unwrap()
+    );
+    println!("cargo:rerun-if-changed={}", elf_path);
+    let symbols = kernel_symbols(&elf_path);
+
+    let mut asm = String::new();
+    writeln!(
+        asm,
+        r#"
+    .section .rodata
+    .align 3
+    .global _ksyms
+_ksyms:
+    .quad {}"#,
+        symbols.len()
+    )
+    .unwrap();
+    let mut offset = 0;
+    for (addr, size, name) in symbols.iter() {
+        writeln!(asm, "    .quad {:#x}, {:#x}, {}", addr, size, offset).unwrap();
+        offset += name.len() + 1;
+    }
+    writeln!(
+        asm,
+        r#"
+    .global _ksyms_strs
+_ksyms_strs:"#
+    )
+    .unwrap();
+    for (_, _, name) in symbols.iter() {
+        writeln!(
+            asm,
+            r#"    .string "{}""#,
+            name.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+        .unwrap();
+    }
+    // rewriting an unchanged table would only trigger another build
+    if fs::read_to_string("src/ksyms.S").ok().as_deref() != Some(asm.as_str()) {
+        File::create("src/ksyms.S")?.write_all(asm.as_bytes())?;
+    }
+    Ok(())
+}
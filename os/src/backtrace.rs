@@ -0,0 +1,95 @@
+//! Stack unwinding and symbol lookup for kernel panic reports
+//!
+//! The kernel is built with frame pointers, so every frame keeps the
+//! return address at `fp - 8` and the caller's fp at `fp - 16`. Symbols come
+//! from `ksyms.S`, which build.rs generates from the previous link of the
+//! kernel; the Makefile links twice so the table matches the final image.
+use core::arch::{asm, global_asm};
+
+global_asm!(include_str!("ksyms.S"));
+
+/// Deepest chain of frames we follow
+const MAX_DEPTH: usize = 32;
+/// Frames above the first one we look at, the size of a boot stack
+const MAX_STACK_SPAN: usize = 4096 * 16;
+
+/// One kernel function, as laid out in `ksyms.S`
+#[repr(C)]
+struct KernelSymbol {
+    addr: usize,
+    size: usize,
+    /// offset of the NUL-terminated name in `_ksyms_strs`
+    name: usize,
+}
+
+fn symbols() -> &'static [KernelSymbol] {
+    unsafe extern "C" {
+        safe fn _ksyms();
+    }
+    unsafe {
+        let num = *(_ksyms as usize as *const usize);
+        let first = (_ksyms as usize + core::mem::size_of::<usize>()) as *const KernelSymbol;
+        core::slice::from_raw_parts(first, num)
+    }
+}
+
+fn symbol_name(symbol: &KernelSymbol) -> &'static str {
+    unsafe extern "C" {
+        safe fn _ksyms_strs();
+    }
+    unsafe {
+        let start = (_ksyms_strs as usize + symbol.name) as *const u8;
+        let len = (0..).take_while(|&i| *start.add(i) != 0).count();
+        core::str::from_utf8_unchecked(core::slice::from_raw_parts(start, len))
+    }
+}
+
+/// Function containing `pc` and the offset of `pc` into it
+pub fn lookup(pc: usize) -> Option<(&'static str, usize)> {
+    let symbols = symbols();
+    // symbols are sorted by address
+    let idx = symbols.partition_point(|symbol| symbol.addr <= pc).checked_sub(1)?;
+    let symbol = &symbols[idx];
+    (pc < symbol.addr + symbol.size).then(|| (symbol_name(symbol), pc - symbol.addr))
+}
+
+/// Print `pc` with the function it falls into
+pub fn print_pc(label: &str, pc: usize) {
+    match lookup(pc) {
+        Some((name, offset)) => println!("{} {:#x} <{}+{:#x}>", label, pc, name, offset),
+        None => println!("{} {:#x}", label, pc),
+    }
+}
+
+/// Walk the frame-pointer chain starting at `fp` and print each return
+/// address. Stops at a frame that does not lie above the previous one on
+/// the same stack.
+pub fn print_backtrace_from(mut fp: usize) {
+    println!("backtrace:");
+    let start = fp;
+    for depth in 0..MAX_DEPTH {
+        if fp == 0 || fp % 8 != 0 || fp < start || fp - start > MAX_STACK_SPAN {
+            break;
+        }
+        let (ra, prev_fp) = unsafe { (*((fp - 8) as *const usize), *((fp - 16) as *const usize)) };
+        // ra points after the call, look up the call itself
+        match lookup(ra.wrapping_sub(1)) {
+            Some((name, offset)) => println!("  #{} {:#x} <{}+{:#x}>", depth, ra, name, offset + 1),
+            None => println!("  #{} {:#x}", depth, ra),
+        }
+        if prev_fp <= fp {
+            break;
+        }
+        fp = prev_fp;
+    }
+}
+
+/// Print the call chain leading to our caller
+#[inline(never)]
+pub fn print_backtrace() {
+    let fp: usize;
+    unsafe {
+        asm!("mv {}, s0", out(reg) fp);
+    }
+    print_backtrace_from(fp);
+}
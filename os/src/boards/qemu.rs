@@ -1,8 +1,12 @@
 pub const CLOCK_FREQ: usize = 10000000;
 pub const MEMORY_END: usize = 0x8800_0000;
+/// Upper bound of harts brought up from `-smp N`; entry.s parks the rest
+/// and has this number written into it
+pub const MAX_HARTS: usize = 8;
 
 pub const MMIO: &[(usize, usize)] = &[
     (0x0010_0000, 0x00_2000), // VIRT_TEST/RTC  in virt machine
+    (0x0200_0000, 0x00_1000), // CLINT msip registers, used for IPIs
     (0x1000_1000, 0x00_1000), // Virtio Block in virt machine
 ];
  
@@ -1,13 +1,24 @@
 //! Constants used in rCore
 
 pub const USER_STACK_SIZE: usize = 4096 * 2;
+/// Largest user stack RLIMIT_STACK may ask for
+pub const USER_STACK_LIMIT: usize = 4096 * 256;
+/// mmap places mappings top down below this, the end of the lower half of
+/// an Sv39 address space
+pub const MMAP_TOP: usize = 0x40_0000_0000;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
+/// Bytes of kernel log kept for dmesg
+pub const LOG_BUF_SIZE: usize = 0x4000;
+/// Distinct pcs one task's profile counts hits for
+pub const PROFILE_MAX_PCS: usize = 4096;
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
+/// user-visible page holding the code signal handlers return into
+pub const SIGRETURN_TRAMPOLINE: usize = TRAP_CONTEXT - PAGE_SIZE;
 /// Return (bottom, top) of a kernel stack in kernel space.
 pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
     let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
@@ -15,4 +26,4 @@ pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
     (bottom, top)
 }
 
-pub use crate::board::{CLOCK_FREQ, MEMORY_END, MMIO};
+pub use crate::board::{CLOCK_FREQ, MAX_HARTS, MEMORY_END, MMIO};
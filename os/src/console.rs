@@ -1,7 +1,33 @@
+use crate::config::MAX_HARTS;
+use crate::smp::hart_id;
+use crate::sync::{SpinMutex, SpinMutexGuard};
 use crate::uart::Uart;
 use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
 
 pub static mut UART: Option<*mut Uart> = None;
+/// keeps lines printed by different harts from interleaving
+static PRINT_LOCK: SpinMutex<()> = SpinMutex::new(());
+/// 正在panic的hart，它可能已经持有PRINT_LOCK
+static PANICKING: [AtomicBool; MAX_HARTS] = [const { AtomicBool::new(false) }; MAX_HARTS];
+
+/// 当前hart之后的输出不再获取PRINT_LOCK，由panic处理函数调用
+pub fn set_panicking() {
+    if let Some(panicking) = PANICKING.get(hart_id()) {
+        panicking.store(true, Ordering::Relaxed);
+    }
+}
+
+fn lock_print() -> Option<SpinMutexGuard<'static, ()>> {
+    let panicking = PANICKING
+        .get(hart_id())
+        .is_some_and(|panicking| panicking.load(Ordering::Relaxed));
+    if panicking {
+        None
+    } else {
+        Some(PRINT_LOCK.lock())
+    }
+}
 
 pub unsafe fn set_uart() {
     if let Some(ref mut uart) = crate::UART_INSTANCE {
@@ -19,6 +45,7 @@ impl fmt::Write for Uart {
 }
 
 pub fn print(args: fmt::Arguments) {
+    let _guard = lock_print();
     unsafe {
         if let Some(uart_ptr) = UART {
             let uart = &mut *uart_ptr;
@@ -27,6 +54,19 @@ pub fn print(args: fmt::Arguments) {
     }
 }
 
+/// 原样输出字节，不要求是完整的UTF-8
+pub fn write_bytes(bytes: &[u8]) {
+    let _guard = lock_print();
+    unsafe {
+        if let Some(uart_ptr) = UART {
+            let uart = &mut *uart_ptr;
+            for byte in bytes {
+                uart.send(*byte);
+            }
+        }
+    }
+}
+
 pub fn getchar() -> u8 {
     unsafe {
         if let Some(uart_ptr) = UART {
@@ -39,6 +79,18 @@ pub fn getchar() -> u8 {
     }
 }
 
+/// 非阻塞读取一个字符，没有输入时返回None
+pub fn try_getchar() -> Option<u8> {
+    unsafe {
+        if let Some(uart_ptr) = UART {
+            let uart = &mut *uart_ptr;
+            uart.try_receive()
+        } else {
+            None
+        }
+    }
+}
+
 
 #[macro_export]
 macro_rules! print {
@@ -3,7 +3,7 @@ use crate::mm::{
     FrameTracker, PageTable, PhysAddr, PhysPageNum, StepByOne, VirtAddr, frame_alloc,
     frame_dealloc, kernel_token,
 };
-use crate::sync::UPSafeCell;
+use crate::sync::SpinMutex;
 use alloc::vec::Vec;
 use lazy_static::*;
 use virtio_drivers::{Hal, VirtIOBlk, VirtIOHeader};
@@ -11,13 +11,17 @@ use virtio_drivers::{Hal, VirtIOBlk, VirtIOHeader};
 #[allow(unused)]
 const VIRTIO0: usize = 0x10001000;
 
-pub struct VirtIOBlock(UPSafeCell<VirtIOBlk<'static, VirtioHal>>);
+pub struct VirtIOBlock(SpinMutex<VirtIOBlk<'static, VirtioHal>>);
+
+// the device is only ever touched with the lock held
+unsafe impl Send for VirtIOBlock {}
+unsafe impl Sync for VirtIOBlock {}
 
 impl VirtIOBlock {
     #[allow(unused)]
     pub fn new() -> Self {
         unsafe {
-            Self(UPSafeCell::new(
+            Self(SpinMutex::new(
                 VirtIOBlk::<VirtioHal>::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
             ))
         }
@@ -25,7 +29,7 @@ impl VirtIOBlock {
 }
 
 lazy_static! {
-    static ref QUEUE_FRAMES: UPSafeCell<Vec<FrameTracker>> = unsafe { UPSafeCell::new(Vec::new()) };
+    static ref QUEUE_FRAMES: SpinMutex<Vec<FrameTracker>> = SpinMutex::new(Vec::new());
 }
 
 pub struct VirtioHal;
@@ -39,7 +43,7 @@ impl Hal for VirtioHal {
                 ppn_base = frame.ppn;
             }
             assert_eq!(frame.ppn.0, ppn_base.0 + i);
-            QUEUE_FRAMES.exclusive_access().push(frame);
+            QUEUE_FRAMES.lock().push(frame);
         }
         let pa: PhysAddr = ppn_base.into();
         pa.0
@@ -70,13 +74,13 @@ impl Hal for VirtioHal {
 impl BlockDevice for VirtIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
         self.0
-            .exclusive_access()
+            .lock()
             .read_block(block_id, buf)
             .expect("Error when reading VirtIOBlk");
     }
     fn write_block(&self, block_id: usize, buf: &[u8]) {
         self.0
-            .exclusive_access()
+            .lock()
             .write_block(block_id, buf)
             .expect("Error when writing VirtIOBlk");
     }
@@ -1,19 +1,32 @@
     .section .text.entry
     .globl _start
 _start:
-    la sp, m_stack_top
+    # 每个 hart 使用独立的 M 模式栈: m_stack_lower_bound + (hartid + 1) * 64KiB
+    csrr t0, mhartid
+    # 超出 MAX_HARTS 的 hart 没有栈，也没有各 hart 的数据，直接停在这里
+    li t1, 8
+    bgeu t0, t1, park
+    addi t0, t0, 1
+    slli t0, t0, 16
+    la sp, m_stack_lower_bound
+    add sp, sp, t0
     call m_mode_init
 
+park:
+    wfi
+    j park
+
+    # 栈大小需与 config::MAX_HARTS (8) 保持一致
     .section .bss.mstack
     .globl m_stack_lower_bound
 m_stack_lower_bound:
-    .space 4096 * 16
+    .space 4096 * 16 * 8
     .globl m_stack_top
 m_stack_top:
 
     .section .bss.stack
     .globl boot_stack_lower_bound
 boot_stack_lower_bound:
-    .space 4096 * 16
+    .space 4096 * 16 * 8
     .globl boot_stack_top
-boot_stack_top:
\ No newline at end of file
+boot_stack_top:
@@ -0,0 +1,67 @@
+//! Error numbers syscalls fail with, numbered as on Linux
+//!
+//! A failing syscall returns the negated number, so user code sees -EBADF
+//! and the like in a0.
+
+#[repr(isize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Errno {
+    /// Operation not permitted
+    EPERM = 1,
+    /// No such file or directory
+    ENOENT = 2,
+    /// No such process
+    ESRCH = 3,
+    /// Interrupted system call
+    EINTR = 4,
+    /// Argument list too long
+    E2BIG = 7,
+    /// Exec format error
+    ENOEXEC = 8,
+    /// Bad file descriptor
+    EBADF = 9,
+    /// No child processes
+    ECHILD = 10,
+    /// Try again
+    EAGAIN = 11,
+    /// Out of memory
+    ENOMEM = 12,
+    /// Bad address
+    EFAULT = 14,
+    /// File exists
+    EEXIST = 17,
+    /// Not a directory
+    ENOTDIR = 20,
+    /// Is a directory
+    EISDIR = 21,
+    /// Invalid argument
+    EINVAL = 22,
+    /// Too many open files
+    EMFILE = 24,
+    /// Not a typewriter
+    ENOTTY = 25,
+    /// Illegal seek
+    ESPIPE = 29,
+    /// Function not implemented
+    ENOSYS = 38,
+    /// Directory not empty
+    ENOTEMPTY = 39,
+}
+
+impl From<Errno> for isize {
+    /// The value a syscall failing with `errno` returns
+    fn from(errno: Errno) -> Self {
+        -(errno as isize)
+    }
+}
+
+/// Result of a kernel operation that may fail the syscall doing it
+pub type SysResult<T = usize> = Result<T, Errno>;
+
+/// The value a syscall returns for `result`
+pub fn syscall_ret(result: SysResult) -> isize {
+    match result {
+        Ok(value) => value as isize,
+        Err(errno) => errno.into(),
+    }
+}
@@ -1,7 +1,9 @@
-use super::File;
+use super::{File, FileKind, Stat};
 use crate::drivers::BLOCK_DEVICE;
+use crate::errno::{Errno, SysResult};
 use crate::mm::UserBuffer;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinMutex;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::*;
@@ -12,9 +14,9 @@ use lazy_static::*;
 pub struct OSInode {
     readable: bool,
     writable: bool,
-    inner: UPSafeCell<OSInodeInner>,
+    inner: SpinMutex<OSInodeInner>,
 }
-/// The OS inode inner in 'UPSafeCell'
+/// The OS inode inner in 'SpinMutex'
 pub struct OSInodeInner {
     offset: usize,
     inode: Arc<Inode>,
@@ -26,12 +28,12 @@ impl OSInode {
         Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+            inner: SpinMutex::new(OSInodeInner { offset: 0, inode }),
         }
     }
     /// Read all data inside a inode into vector
     pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         let mut buffer = [0u8; 512];
         let mut v: Vec<u8> = Vec::new();
         loop {
@@ -44,6 +46,36 @@ impl OSInode {
         }
         v
     }
+    /// Write `data` from kernel memory at the current offset
+    pub fn write_all(&self, data: &[u8]) -> usize {
+        let mut inner = self.inner.lock();
+        let len = inner.inode.write_at(inner.offset, data);
+        inner.offset += len;
+        len
+    }
+    /// Read from `offset` into kernel memory, leaving the file offset alone
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.inner.lock().inode.read_at(offset, buf)
+    }
+    /// Hand the entries of a directory from the current offset on to
+    /// `fill` as (inode number, name, is a directory), until it returns
+    /// false. The offset counts the entries taken.
+    pub fn read_dir(&self, mut fill: impl FnMut(u32, &str, bool) -> bool) -> SysResult<()> {
+        let mut inner = self.inner.lock();
+        if !inner.inode.is_dir() {
+            return Err(Errno::ENOTDIR);
+        }
+        let names: Vec<String> = inner.inode.ls();
+        for name in names.iter().skip(inner.offset) {
+            if let Some(inode) = inner.inode.find(name) {
+                if !fill(inode.inode_id(), name, inode.is_dir()) {
+                    break;
+                }
+            }
+            inner.offset += 1;
+        }
+        Ok(())
+    }
 }
 
 lazy_static! {
@@ -52,13 +84,9 @@ lazy_static! {
         Arc::new(EasyFileSystem::root_inode(&efs))
     };
 }
-/// List all files in the filesystems
+/// Log the files in the root directory, at info level
 pub fn list_apps() {
-    println!("/**** APPS ****");
-    for app in ROOT_INODE.ls() {
-        println!("{}", app);
-    }
-    println!("**************/");
+    info!("apps: {}", ROOT_INODE.ls().join(" "));
 }
 
 bitflags! {
@@ -85,150 +113,119 @@ impl OpenFlags {
 }
 
 /// find inode by path
-fn find_inode(path: &str) -> Option<Arc<Inode>> {
+fn find_inode(path: &str) -> SysResult<Arc<Inode>> {
     let mut inode = ROOT_INODE.clone();
     for name in path.split('/') {
-        if name.is_empty() {
+        if name.is_empty() || name == "." {
             continue;
         }
-        if let Some(new_inode) = inode.find(name) {
-            inode = new_inode;
-        } else {
-            return None;
+        if !inode.is_dir() {
+            return Err(Errno::ENOTDIR);
         }
+        inode = inode.find(name).ok_or(Errno::ENOENT)?;
+    }
+    Ok(inode)
+}
+
+/// Directory holding the last component of `path`, and that component
+fn find_parent(path: &str) -> SysResult<(Arc<Inode>, &str)> {
+    let (parent_path, name) = if let Some(pos) = path.rfind('/') {
+        (&path[..pos], &path[pos + 1..])
+    } else {
+        ("", path)
+    };
+    if name.is_empty() {
+        return Err(Errno::ENOENT);
     }
-    Some(inode)
+    let parent_inode = find_inode(parent_path)?;
+    if !parent_inode.is_dir() {
+        return Err(Errno::ENOTDIR);
+    }
+    Ok((parent_inode, name))
 }
 
 ///Open file with flags
-pub fn open_file(path: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+pub fn open_file(path: &str, flags: OpenFlags) -> SysResult<Arc<OSInode>> {
     let (readable, writable) = flags.read_write();
     if flags.contains(OpenFlags::CREATE) {
         // 拆分路径：parent_path + name
-        let (parent_path, name) = if let Some(pos) = path.rfind('/') {
-            let parent = &path[..pos];
-            let name = &path[pos + 1..];
-            (parent, name)
-        } else {
-            ("", path)
+        let (parent_inode, name) = find_parent(path)?;
+        let inode = match parent_inode.find(name) {
+            Some(inode) if inode.is_dir() => return Err(Errno::EISDIR),
+            Some(inode) => {
+                inode.clear();
+                inode
+            }
+            None => parent_inode.create_file(name).ok_or(Errno::EEXIST)?,
         };
-        if name.is_empty() {
-            return None;
+        Ok(Arc::new(OSInode::new(readable, writable, inode)))
+    } else {
+        let inode = find_inode(path)?;
+        if writable && inode.is_dir() {
+            return Err(Errno::EISDIR);
         }
-        if let Some(inode) = find_inode(path) {
+        if flags.contains(OpenFlags::TRUNC) {
             inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
-        } else if let Some(parent_inode) = find_inode(parent_path) {
-            parent_inode
-                .create_file(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
-        } else {
-            None
         }
-    } else {
-        find_inode(path).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
-                inode.clear();
-            }
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
+        Ok(Arc::new(OSInode::new(readable, writable, inode)))
     }
 }
 
-pub fn delete_file(path: &str) -> bool {
-    let (parent_path, name) = if let Some(pos) = path.rfind('/') {
-        let parent = &path[..pos];
-        let name = &path[pos + 1..];
-        (parent, name)
-    } else {
-        ("", path)
-    };
-    if name.is_empty() {
-        return false;
-    }
-    if let Some(parent_inode) = find_inode(parent_path) {
-        parent_inode.delete_entry(name)
-    } else {
-        false
+pub fn delete_file(path: &str) -> SysResult<()> {
+    let (parent_inode, name) = find_parent(path)?;
+    match parent_inode.find(name) {
+        None => Err(Errno::ENOENT),
+        Some(inode) if inode.is_dir() => Err(Errno::EISDIR),
+        Some(_) if parent_inode.delete_entry(name) => Ok(()),
+        Some(_) => Err(Errno::ENOENT),
     }
 }
 
-pub fn make_dir(path: &str) -> bool {
-    let (parent_path, name) = if let Some(pos) = path.rfind('/') {
-        let parent = &path[..pos];
-        let name = &path[pos + 1..];
-        (parent, name)
-    } else {
-        ("", path)
-    };
-    if name.is_empty() {
-        return false;
-    }
-    if let Some(parent_inode) = find_inode(parent_path) {
-        parent_inode.create_dir(name).is_some()
-    } else {
-        false
-    }
+pub fn make_dir(path: &str) -> SysResult<()> {
+    let (parent_inode, name) = find_parent(path)?;
+    parent_inode.create_dir(name).map(|_| ()).ok_or(Errno::EEXIST)
 }
 
-pub fn remove_dir(path: &str) -> bool {
-    let (parent_path, name) = if let Some(pos) = path.rfind('/') {
-        (&path[..pos], &path[pos + 1..])
-    } else {
-        ("", path)
-    };
-    if name.is_empty() {
-        return false;
+pub fn remove_dir(path: &str) -> SysResult<()> {
+    let (parent_inode, name) = find_parent(path)?;
+    let dir_inode = parent_inode.find(name).ok_or(Errno::ENOENT)?;
+    // 1. 判断是不是目录
+    if !dir_inode.is_dir() {
+        return Err(Errno::ENOTDIR);
     }
-    if let Some(parent_inode) = find_inode(parent_path) {
-        if let Some(dir_inode) = parent_inode.find(name) {
-            // 1. 判断是不是目录
-            let is_dir = dir_inode.is_dir();
-            if !is_dir {
-                return false; // 不是目录
-            }
-            // 2. 判断目录是否为空
-            let file_list = dir_inode.ls();
-            if !file_list.is_empty() {
-                return false; // 目录非空
-            }
-            // 3. 执行
-            return parent_inode.delete_entry(name);
-        } else {
-            return false;
-        }
+    // 2. 判断目录是否为空
+    if !dir_inode.ls().is_empty() {
+        return Err(Errno::ENOTEMPTY);
+    }
+    // 3. 执行
+    if parent_inode.delete_entry(name) {
+        Ok(())
+    } else {
+        Err(Errno::ENOENT)
     }
-    false
 }
 
 /// 移动/重命名文件或目录（不覆盖，不跨文件系统）
-pub fn rename_file_or_dir(old_path: &str, new_path: &str) -> bool {
+pub fn rename_file_or_dir(old_path: &str, new_path: &str) -> SysResult<()> {
     // 路径分割
-    let (old_parent_path, old_name) = if let Some(pos) = old_path.rfind('/') {
-        (&old_path[..pos], &old_path[pos + 1..])
-    } else { ("", old_path) };
-    let (new_parent_path, new_name) = if let Some(pos) = new_path.rfind('/') {
-        (&new_path[..pos], &new_path[pos + 1..])
-    } else { ("", new_path) };
-    if old_name.is_empty() || new_name.is_empty() { return false; } 
-
-    let old_parent_inode = match find_inode(old_parent_path) { Some(x) => x, None => return false };
-    let moved_id = match old_parent_inode.find_id(old_name) { Some(x) => x, None => return false };
-    let new_parent_inode = match find_inode(new_parent_path) { Some(x) => x, None => return false };
+    let (old_parent_inode, old_name) = find_parent(old_path)?;
+    let moved_id = old_parent_inode.find_id(old_name).ok_or(Errno::ENOENT)?;
+    let (new_parent_inode, new_name) = find_parent(new_path)?;
     // 新名或同路径已存在
-    if new_parent_inode.find(new_name).is_some() { return false; }
+    if new_parent_inode.find(new_name).is_some() {
+        return Err(Errno::EEXIST);
+    }
 
     // 新目录加entry指向同 inode_id
-    if !new_parent_inode.link(new_name, moved_id) { return false; }
+    if !new_parent_inode.link(new_name, moved_id) {
+        return Err(Errno::EEXIST);
+    }
 
     // 原目录删原entry
-    if !old_parent_inode.unlink_entry(old_name) { return false; }
-    
-    // for name in new_parent_inode.ls() {
-    //     println!("{}", name);
-    // }
-
-    true
+    if !old_parent_inode.unlink_entry(old_name) {
+        return Err(Errno::ENOENT);
+    }
+    Ok(())
 }
 
 impl File for OSInode {
@@ -239,7 +236,7 @@ impl File for OSInode {
         self.writable
     }
     fn read(&self, mut buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         let mut total_read_size = 0usize;
         for slice in buf.buffers.iter_mut() {
             let read_size = inner.inode.read_at(inner.offset, *slice);
@@ -253,7 +250,7 @@ impl File for OSInode {
     }
 
     fn write(&self, buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         let mut total_write_size = 0usize;
         for slice in buf.buffers.iter() {
             let write_size = inner.inode.write_at(inner.offset, *slice);
@@ -263,36 +260,50 @@ impl File for OSInode {
         }
         total_write_size
     }
-    fn seek(&self, offset: isize, whence: usize) -> Option<usize> {
-        let mut inner = self.inner.exclusive_access();
+    fn seek(&self, offset: isize, whence: usize) -> SysResult {
+        let mut inner = self.inner.lock();
         // 获取文件长度
         let file_size = inner.inode.size();
 
         let new_offset = match whence {
             0 /* SEEK_SET */ => {
                 if offset < 0 {
-                    return None;
+                    return Err(Errno::EINVAL);
                 }
                 offset as usize
             },
             1 /* SEEK_CUR */ => {
                 let tmp = inner.offset as isize + offset;
                 if tmp < 0 {
-                    return None;
+                    return Err(Errno::EINVAL);
                 }
                 tmp as usize
             },
             2 /* SEEK_END */ => {
                 let tmp = file_size as isize + offset;
                 if tmp < 0 || tmp as usize > file_size {
-                    return None;
+                    return Err(Errno::EINVAL);
                 }
                 tmp as usize
             },
-            _ => return None,
+            _ => return Err(Errno::EINVAL),
         };
         inner.offset = new_offset;
-        Some(new_offset)
+        Ok(new_offset)
+    }
+
+    fn stat(&self) -> Stat {
+        let inner = self.inner.lock();
+        let kind = if inner.inode.is_dir() {
+            FileKind::Directory
+        } else {
+            FileKind::Regular
+        };
+        Stat {
+            ino: inner.inode.inode_id() as u64,
+            kind,
+            size: inner.inode.size(),
+        }
     }
 
     fn as_any(&self) -> &dyn Any {
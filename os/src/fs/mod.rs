@@ -3,6 +3,7 @@ mod inode;
 mod stdio;
 mod pipe;
 
+use crate::errno::SysResult;
 use crate::mm::UserBuffer;
 /// File trait
 use core::any::Any;
@@ -12,10 +13,38 @@ pub trait File: Send + Sync {
     fn writable(&self) -> bool;
     fn read(&self, buf: UserBuffer) -> usize;
     fn write(&self, buf: UserBuffer) -> usize;
-    fn seek(&self, offset: isize, whence: usize) -> Option<usize>;
+    /// Move the offset as lseek does; files without one fail with ESPIPE
+    fn seek(&self, offset: isize, whence: usize) -> SysResult;
+    fn stat(&self) -> Stat;
     fn as_any(&self) -> &dyn Any;
 }
 
-pub use inode::{OSInode, OpenFlags, list_apps, open_file, delete_file, make_dir, remove_dir, rename_file_or_dir};
-pub use stdio::{Stdin, Stdout};
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Regular,
+    Directory,
+    CharDevice,
+    Fifo,
+}
+
+/// What fstat reports about an open file
+#[derive(Clone, Copy)]
+pub struct Stat {
+    /// inode number, 0 for files not on disk
+    pub ino: u64,
+    pub kind: FileKind,
+    pub size: usize,
+}
+
+impl Stat {
+    /// An empty file of `kind` that lives outside the file system
+    pub fn new(kind: FileKind) -> Self {
+        Self { ino: 0, kind, size: 0 }
+    }
+}
+
+pub use inode::{
+    OSInode, OpenFlags, list_apps, open_file, delete_file, make_dir, remove_dir, rename_file_or_dir,
+};
+pub use stdio::{Stdin, Stdout, foreground_pgrp, poll_console, set_foreground_pgrp};
 pub use pipe::make_pipe;
@@ -1,6 +1,7 @@
-use super::File;
+use super::{File, FileKind, Stat};
+use crate::errno::{Errno, SysResult};
 use crate::mm::UserBuffer;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinMutex;
 use alloc::sync::{Arc, Weak};
 use core::any::Any;
 
@@ -9,18 +10,18 @@ use crate::task::suspend_current_and_run_next;
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+    buffer: Arc<SpinMutex<PipeRingBuffer>>,
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+    pub fn read_end_with_buffer(buffer: Arc<SpinMutex<PipeRingBuffer>>) -> Self {
         Self {
             readable: true,
             writable: false,
             buffer,
         }
     }
-    pub fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+    pub fn write_end_with_buffer(buffer: Arc<SpinMutex<PipeRingBuffer>>) -> Self {
         Self {
             readable: false,
             writable: true,
@@ -106,10 +107,10 @@ impl PipeRingBuffer {
 
 /// make a pipe
 pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
-    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new()) });
+    let buffer = Arc::new(SpinMutex::new(PipeRingBuffer::new()));
     let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
     let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-    buffer.exclusive_access().set_write_end(&write_end);
+    buffer.lock().set_write_end(&write_end);
     (read_end, write_end)
 }
 
@@ -128,7 +129,7 @@ impl File for Pipe {
         let mut buf_iter = buf.into_iter();
         let mut already_read = 0usize;
         loop{
-            let mut ring_buffer = self.buffer.exclusive_access();
+            let mut ring_buffer = self.buffer.lock();
             let loop_read = ring_buffer.available_read();
             if loop_read == 0 {
                 if ring_buffer.all_write_ends_closed() {
@@ -159,7 +160,7 @@ impl File for Pipe {
         let mut buf_iter = buf.into_iter();
         let mut already_written = 0usize;
         loop {
-            let mut ring_buffer = self.buffer.exclusive_access();
+            let mut ring_buffer = self.buffer.lock();
             let loop_write = ring_buffer.available_write();
             if loop_write == 0 {
                 drop(ring_buffer);
@@ -180,8 +181,11 @@ impl File for Pipe {
         }
     }
 
-    fn seek(&self, _offset: isize, _whence: usize) -> Option<usize> {
-        panic!("Pipe does not support seek!");
+    fn seek(&self, _offset: isize, _whence: usize) -> SysResult {
+        Err(Errno::ESPIPE)
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(FileKind::Fifo)
     }
     fn as_any(&self) -> &dyn Any {
         self
@@ -1,14 +1,56 @@
 //!Stdin & Stdout
-use super::File;
-use crate::console::getchar;
+use super::{File, FileKind, Stat};
+use crate::console::{try_getchar, write_bytes};
+use crate::errno::{Errno, SysResult};
 use crate::mm::UserBuffer;
-use crate::task::suspend_current_and_run_next;
+use crate::sync::SpinMutex;
+use crate::task::{SignalFlags, SignalInfo, signal_group, suspend_current_and_run_next};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
 ///Standard input
 pub struct Stdin;
 ///Standard output
 pub struct Stdout;
 use core::any::Any;
 
+const CTRL_C: u8 = 0x03;
+const CTRL_Z: u8 = 0x1a;
+
+/// Characters received from the console but not read yet
+static TTY_INPUT: SpinMutex<VecDeque<u8>> = SpinMutex::new(VecDeque::new());
+/// Process group that owns the console
+static FOREGROUND_PGRP: AtomicUsize = AtomicUsize::new(0);
+
+pub fn foreground_pgrp() -> usize {
+    FOREGROUND_PGRP.load(Ordering::Acquire)
+}
+
+pub fn set_foreground_pgrp(pgid: usize) {
+    FOREGROUND_PGRP.store(pgid, Ordering::Release);
+}
+
+/// Drain the UART, turning ^C and ^Z into signals for the foreground group.
+///
+/// Called from the read path and from the time-slice tick, so jobs that
+/// never read the console can still be interrupted.
+pub fn poll_console() {
+    let mut input = TTY_INPUT.lock();
+    let mut signals = Vec::new();
+    while let Some(ch) = try_getchar() {
+        match ch {
+            CTRL_C => signals.push(SignalFlags::SIGINT),
+            CTRL_Z => signals.push(SignalFlags::SIGTSTP),
+            _ => input.push_back(ch),
+        }
+    }
+    // readers take a task lock before TTY_INPUT, signalling takes them after
+    drop(input);
+    for signal in signals {
+        signal_group(foreground_pgrp(), SignalInfo::kernel(signal));
+    }
+}
+
 impl File for Stdin {
     fn readable(&self) -> bool {
         true
@@ -16,30 +58,41 @@ impl File for Stdin {
     fn writable(&self) -> bool {
         false
     }
-    fn read(&self, mut user_buf: UserBuffer) -> usize {
-        assert_eq!(user_buf.len(), 1);
+    /// Wait for input, then return what has arrived up to the buffer size
+    fn read(&self, user_buf: UserBuffer) -> usize {
+        if user_buf.len() == 0 {
+            return 0;
+        }
         // busy loop
-        let mut c: usize;
-        loop {
-            c = getchar() as usize;
-            if c == 0 {
-                suspend_current_and_run_next();
-                continue;
-            } else {
+        let mut input = loop {
+            poll_console();
+            let input = TTY_INPUT.lock();
+            if !input.is_empty() {
+                break input;
+            }
+            drop(input);
+            suspend_current_and_run_next();
+        };
+        let mut count = 0;
+        for byte in user_buf.into_iter() {
+            let Some(ch) = input.pop_front() else {
                 break;
+            };
+            unsafe {
+                byte.write_volatile(ch);
             }
+            count += 1;
         }
-        let ch = c as u8;
-        unsafe {
-            user_buf.buffers[0].as_mut_ptr().write_volatile(ch);
-        }
-        1
+        count
     }
     fn write(&self, _user_buf: UserBuffer) -> usize {
         panic!("Cannot write to stdin!");
     }
-    fn seek(&self, _offset: isize, _whence: usize) -> Option<usize> {
-        panic!("Stdin does not support seek!");
+    fn seek(&self, _offset: isize, _whence: usize) -> SysResult {
+        Err(Errno::ESPIPE)
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(FileKind::CharDevice)
     }
     fn as_any(&self) -> &dyn Any {
         self
@@ -58,12 +111,15 @@ impl File for Stdout {
     }
     fn write(&self, user_buf: UserBuffer) -> usize {
         for buffer in user_buf.buffers.iter() {
-            print!("{}", core::str::from_utf8(*buffer).unwrap());
+            write_bytes(buffer);
         }
         user_buf.len()
     }
-    fn seek(&self, _offset: isize, _whence: usize) -> Option<usize> {
-        panic!("Stdout does not support seek!");
+    fn seek(&self, _offset: isize, _whence: usize) -> SysResult {
+        Err(Errno::ESPIPE)
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(FileKind::CharDevice)
     }
     fn as_any(&self) -> &dyn Any {
         self
@@ -1,9 +1,19 @@
 use core::panic::PanicInfo;
+use crate::backtrace::print_backtrace;
 use crate::println;
+use crate::smp::hart_id;
+use crate::task::current_pid;
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("{}", info);
+    #[cfg(feature = "lockdep")]
+    crate::sync::lockdep::disable();
+    crate::console::set_panicking();
+    match current_pid() {
+        Some(pid) => println!("[kernel] panic on hart {} in pid {}: {}", hart_id(), pid, info),
+        None => println!("[kernel] panic on hart {}: {}", hart_id(), info),
+    }
+    print_backtrace();
     shutdown(true)
 }
 
@@ -16,10 +26,16 @@ pub fn shutdown(failure: bool) -> ! {
     
     // virt_test 设备的退出代码
     const VIRT_TEST_EXIT_SUCCESS: u32 = 0x5555; // 正常退出
+    const VIRT_TEST_EXIT_FAILURE: u32 = 0x3333; // 异常退出，高16位为QEMU的退出码
     
+    let code = if failure {
+        (1 << 16) | VIRT_TEST_EXIT_FAILURE
+    } else {
+        VIRT_TEST_EXIT_SUCCESS
+    };
     unsafe {
         // 写入退出代码到 test 设备地址，触发 QEMU 退出
-        VIRT_TEST_ADDR.write_volatile(VIRT_TEST_EXIT_SUCCESS);
+        VIRT_TEST_ADDR.write_volatile(code);
         
         // 无限循环，确保函数不返回
         loop {}
@@ -0,0 +1,178 @@
+//! Kernel log, a `log` backend that keeps recent records in a ring buffer
+//!
+//! Records up to the level chosen at build time (`make run LOG=debug`,
+//! info by default) are stored as `[sec.usec] LEVEL message` lines. Only
+//! those the console level lets through also go to the UART, so the kernel
+//! does not talk over user programs; `dmesg` reads the rest back with
+//! `sys_syslog`.
+use crate::config::LOG_BUF_SIZE;
+use crate::sync::SpinMutex;
+use crate::timer::{get_time, TimeVal};
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Console levels as Linux numbers them: a record reaches the console if
+/// its priority is below the level
+const CONSOLE_LEVEL_MIN: usize = 1;
+const CONSOLE_LEVEL_DEFAULT: usize = 5;
+const CONSOLE_LEVEL_MAX: usize = 8;
+
+static CONSOLE_LEVEL: AtomicUsize = AtomicUsize::new(CONSOLE_LEVEL_DEFAULT);
+/// the level to go back to once the console is turned on again
+static SAVED_CONSOLE_LEVEL: AtomicUsize = AtomicUsize::new(CONSOLE_LEVEL_DEFAULT);
+
+/// Linux priority of a record: err, warning, info, debug
+fn priority(level: Level) -> usize {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// The last `LOG_BUF_SIZE` bytes logged. Positions count every byte ever
+/// written, so they only grow.
+struct LogBuffer {
+    data: [u8; LOG_BUF_SIZE],
+    head: usize,
+    /// where the next destructive read starts
+    read: usize,
+    /// nothing before this is returned once the buffer has been cleared
+    clear: usize,
+}
+
+impl LogBuffer {
+    /// Oldest position still held
+    fn tail(&self) -> usize {
+        self.head.saturating_sub(LOG_BUF_SIZE)
+    }
+    fn bytes(&self, from: usize) -> Vec<u8> {
+        (from..self.head).map(|pos| self.data[pos % LOG_BUF_SIZE]).collect()
+    }
+}
+
+impl fmt::Write for LogBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.data[self.head % LOG_BUF_SIZE] = byte;
+            self.head += 1;
+        }
+        Ok(())
+    }
+}
+
+static LOG_BUFFER: SpinMutex<LogBuffer> = SpinMutex::new(LogBuffer {
+    data: [0; LOG_BUF_SIZE],
+    head: 0,
+    read: 0,
+    clear: 0,
+});
+
+struct KernelLog;
+
+impl Log for KernelLog {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let time = TimeVal::from_ticks(get_time());
+        {
+            let mut buffer = LOG_BUFFER.lock();
+            let _ = fmt::Write::write_fmt(
+                &mut *buffer,
+                format_args!(
+                    "[{:5}.{:06}] {:5} {}\n",
+                    time.sec,
+                    time.usec,
+                    record.level(),
+                    record.args()
+                ),
+            );
+        }
+        // the buffer lock is dropped first, printing takes the console's
+        if priority(record.level()) < CONSOLE_LEVEL.load(Ordering::Relaxed) {
+            println!(
+                "[{:5}.{:06}] {:5} {}",
+                time.sec,
+                time.usec,
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: KernelLog = KernelLog;
+
+/// Install the logger. Records logged before this are lost.
+pub fn init() {
+    let level = option_env!("LOG")
+        .and_then(|level| level.parse().ok())
+        .unwrap_or(LevelFilter::Info);
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(level);
+}
+
+/// Up to `len` bytes of the log that no destructive read has returned
+/// yet, which they then are
+pub fn read(len: usize) -> Vec<u8> {
+    let mut buffer = LOG_BUFFER.lock();
+    let from = buffer.read.max(buffer.tail());
+    let bytes = buffer.bytes(from);
+    let bytes = bytes[..len.min(bytes.len())].to_vec();
+    buffer.read = from + bytes.len();
+    bytes
+}
+
+/// The last `len` bytes held, clearing the buffer after if asked
+pub fn read_all(len: usize, clear: bool) -> Vec<u8> {
+    let mut buffer = LOG_BUFFER.lock();
+    let from = buffer.clear.max(buffer.tail()).max(buffer.head.saturating_sub(len));
+    let bytes = buffer.bytes(from);
+    if clear {
+        buffer.clear = buffer.head;
+    }
+    bytes
+}
+
+pub fn clear() {
+    let mut buffer = LOG_BUFFER.lock();
+    buffer.clear = buffer.head;
+}
+
+/// Bytes a destructive read would return
+pub fn unread() -> usize {
+    let buffer = LOG_BUFFER.lock();
+    buffer.head - buffer.read.max(buffer.tail())
+}
+
+/// Keep records off the console until `console_on`
+pub fn console_off() {
+    let level = CONSOLE_LEVEL.swap(CONSOLE_LEVEL_MIN, Ordering::Relaxed);
+    if level != CONSOLE_LEVEL_MIN {
+        SAVED_CONSOLE_LEVEL.store(level, Ordering::Relaxed);
+    }
+}
+
+pub fn console_on() {
+    CONSOLE_LEVEL.store(SAVED_CONSOLE_LEVEL.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+/// Set the console level, 1 to 8. False if `level` is out of range.
+pub fn set_console_level(level: usize) -> bool {
+    if !(CONSOLE_LEVEL_MIN..=CONSOLE_LEVEL_MAX).contains(&level) {
+        return false;
+    }
+    CONSOLE_LEVEL.store(level, Ordering::Relaxed);
+    SAVED_CONSOLE_LEVEL.store(level, Ordering::Relaxed);
+    true
+}
@@ -6,20 +6,27 @@ extern crate alloc;
 
 #[macro_use]
 extern crate bitflags;
+#[macro_use]
+extern crate log;
 
 #[path = "boards/qemu.rs"]
 mod board;
 
 #[macro_use]
 mod console;
+mod backtrace;
 mod lang_items;
+mod logging;
 mod uart;
 mod timer;
 mod mm;
 mod mmod;
 mod config;
+mod errno;
+mod perf;
 mod loader;
 mod drivers;
+mod smp;
 pub mod trap;
 pub mod sync;
 pub mod task;
@@ -80,15 +87,35 @@ pub fn dump_memory(addr: usize, size: usize) {
 
 #[unsafe(no_mangle)]
 pub fn rust_main() -> ! {
+    if smp::hart_id() != 0 {
+        secondary_main();
+    }
     clear_bss();
     uart_init();
+    logging::init();
+    #[cfg(feature = "lockdep")]
+    sync::lockdep::init();
     mm::init();
     mm::remap_test();
     trap::init();
     timer::set_next_trigger();
     fs::list_apps();
     task::add_initproc();
-    println!("after initproc!");
+    info!("initproc added");
+    smp::boot_finished();
+    smp::set_online();
+    task::run_tasks();
+    panic!("|program finished|");
+}
+
+/// Entry of every hart other than hart 0
+fn secondary_main() -> ! {
+    smp::wait_for_boot();
+    mm::init_secondary();
+    trap::init();
+    timer::set_next_trigger();
+    info!("hart {} online", smp::hart_id());
+    smp::set_online();
     task::run_tasks();
     panic!("|program finished|");
 }
\ No newline at end of file
@@ -171,6 +171,7 @@ impl VirtPageNum {
 
 impl PhysAddr {
     ///Get reference to `PhysAddr` value
+    #[allow(unused)]
     pub fn get_ref<T>(&self) -> &'static T {
         unsafe { (self.0 as *const T).as_ref().unwrap() }
     }
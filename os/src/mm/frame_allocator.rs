@@ -3,7 +3,7 @@
 
 use super::{PhysAddr, PhysPageNum};
 use crate::config::MEMORY_END;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinMutex;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use lazy_static::*;
@@ -11,12 +11,12 @@ use alloc::collections::BTreeMap;
 
 lazy_static! {
     // 全局引用计数表
-    static ref FRAME_REF_COUNT: UPSafeCell<BTreeMap<usize, usize>> = 
-        unsafe { UPSafeCell::new(BTreeMap::new()) };
+    static ref FRAME_REF_COUNT: SpinMutex<BTreeMap<usize, usize>> =
+        SpinMutex::new(BTreeMap::new());
 }
 
 pub fn only_one_frame(ppn: PhysPageNum) -> bool {
-    let ref_counts = FRAME_REF_COUNT.exclusive_access();
+    let ref_counts = FRAME_REF_COUNT.lock();
     if let Some(count) = ref_counts.get(&ppn.0) {
         *count == 1
     } else {
@@ -26,13 +26,13 @@ pub fn only_one_frame(ppn: PhysPageNum) -> bool {
 
 // 增加引用计数
 pub fn increase_frame_ref(ppn: PhysPageNum) {
-    let mut ref_counts = FRAME_REF_COUNT.exclusive_access();
+    let mut ref_counts = FRAME_REF_COUNT.lock();
     *ref_counts.entry(ppn.0).or_insert(0) += 1;
 }
 
 // 减少引用计数，返回是否应该释放
 pub fn decrease_frame_ref(ppn: PhysPageNum) -> bool {
-    let mut ref_counts = FRAME_REF_COUNT.exclusive_access();
+    let mut ref_counts = FRAME_REF_COUNT.lock();
     if let Some(count) = ref_counts.get_mut(&ppn.0) {
         *count -= 1;
         if *count == 0 {
@@ -85,6 +85,7 @@ trait FrameAllocator {
     fn new() -> Self;
     fn alloc(&mut self) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
+    fn free(&self) -> usize;
 }
 
 pub struct StackFrameAllocator {
@@ -126,34 +127,40 @@ impl FrameAllocator for StackFrameAllocator {
         // recycle
         self.recycled.push(ppn);
     }
+    fn free(&self) -> usize {
+        self.end - self.current + self.recycled.len()
+    }
 }
 
 type FrameAllocatorImpl = StackFrameAllocator;
 
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
-        unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
+    pub static ref FRAME_ALLOCATOR: SpinMutex<FrameAllocatorImpl> =
+        SpinMutex::new(FrameAllocatorImpl::new());
 }
 
 pub fn init_frame_allocator() {
     unsafe extern "C" {
         safe fn ekernel();
     }
-    FRAME_ALLOCATOR.exclusive_access().init(
+    FRAME_ALLOCATOR.lock().init(
         PhysAddr::from(ekernel as usize).ceil(),
         PhysAddr::from(MEMORY_END).floor(),
     );
 }
 
 pub fn frame_alloc() -> Option<FrameTracker> {
-    FRAME_ALLOCATOR
-        .exclusive_access()
-        .alloc()
-        .map(FrameTracker::new)
+    let ppn = FRAME_ALLOCATOR.lock().alloc();
+    ppn.map(FrameTracker::new)
 }
 
 pub fn frame_dealloc(ppn: PhysPageNum) {
-    FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
+    FRAME_ALLOCATOR.lock().dealloc(ppn);
+}
+
+/// 当前空闲的物理页帧数
+pub fn frames_free() -> usize {
+    FRAME_ALLOCATOR.lock().free()
 }
 
 #[allow(unused)]
@@ -5,9 +5,14 @@ use super::{FrameTracker, frame_alloc};
 use super::{PTEFlags, PageTable, PageTableEntry};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
-use crate::sync::UPSafeCell;
+use super::translated_byte_buffer;
+use crate::config::{
+    MEMORY_END, MMAP_TOP, MMIO, PAGE_SIZE, SIGRETURN_TRAMPOLINE, TRAMPOLINE, TRAP_CONTEXT,
+};
+use crate::errno::{Errno, SysResult};
+use crate::sync::SpinMutex;
 use alloc::collections::BTreeMap;
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::arch::asm;
@@ -27,15 +32,94 @@ unsafe extern "C" {
     safe fn ebss();
     safe fn ekernel();
     safe fn strampoline();
+    safe fn ssigreturn();
 }
 
 lazy_static! {
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinMutex<MemorySet>> =
+        Arc::new(SpinMutex::new(MemorySet::new_kernel()));
 }
 
 pub fn kernel_token() -> usize {
-    KERNEL_SPACE.exclusive_access().token()
+    KERNEL_SPACE.lock().token()
+}
+
+/// 加载ELF时得到的信息，用于设置入口和辅助向量
+pub struct ElfInfo {
+    pub entry: usize,
+    /// 程序头表在用户地址空间中的地址，不在任何段中时为0
+    pub phdr: usize,
+    pub phent: usize,
+    pub phnum: usize,
+    /// 堆（brk）的起点，紧接在用户栈之上
+    pub heap_bottom: usize,
+    /// 带有.note.acore段，即用本仓库的用户库链接的程序；其余按Linux程序对待
+    pub native: bool,
+}
+
+/// ELF头中的字段：64位、小端、RISC-V
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const EM_RISCV: usize = 0xf3;
+const ELF64_EHDR_SIZE: usize = 64;
+const ELF64_PHDR_SIZE: usize = 56;
+const ELF64_SHDR_SIZE: usize = 64;
+
+fn u16_at(data: &[u8], offset: usize) -> Option<usize> {
+    Some(u16::from_le_bytes(data.get(offset..offset.checked_add(2)?)?.try_into().ok()?) as usize)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<usize> {
+    Some(u32::from_le_bytes(data.get(offset..offset.checked_add(4)?)?.try_into().ok()?) as usize)
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Option<usize> {
+    Some(u64::from_le_bytes(data.get(offset..offset.checked_add(8)?)?.try_into().ok()?) as usize)
+}
+
+/// 检查ELF头和程序头表，通过后xmas_elf解析头和程序头时不会panic
+fn check_elf_headers(data: &[u8]) -> Option<()> {
+    if data.len() < ELF64_EHDR_SIZE
+        || data[..4] != [0x7f, b'E', b'L', b'F']
+        || data[4] != ELFCLASS64
+        || data[5] != ELFDATA2LSB
+        || u16_at(data, 0x12)? != EM_RISCV
+    {
+        return None;
+    }
+    let (ph_offset, ph_entsize, ph_count) =
+        (u64_at(data, 0x20)?, u16_at(data, 0x36)?, u16_at(data, 0x38)?);
+    let ph_end = ph_count.checked_mul(ELF64_PHDR_SIZE)?.checked_add(ph_offset)?;
+    if ph_count > 0 && (ph_entsize != ELF64_PHDR_SIZE || ph_offset % 8 != 0 || ph_end > data.len()) {
+        return None;
+    }
+    Some(())
+}
+
+/// 是否有名为`name`的节；节头表或节名损坏时当作没有
+fn has_section(data: &[u8], name: &str) -> bool {
+    let find = || -> Option<bool> {
+        let (sh_offset, sh_entsize, sh_count, shstrndx) = (
+            u64_at(data, 0x28)?,
+            u16_at(data, 0x3a)?,
+            u16_at(data, 0x3c)?,
+            u16_at(data, 0x3e)?,
+        );
+        if sh_entsize != ELF64_SHDR_SIZE {
+            return None;
+        }
+        let header = |i: usize| i.checked_mul(ELF64_SHDR_SIZE)?.checked_add(sh_offset);
+        let strtab = u64_at(data, header(shstrndx)?.checked_add(0x18)?)?;
+        for i in 0..sh_count {
+            let name_start = strtab.checked_add(u32_at(data, header(i)?)?)?;
+            let name_end = name_start.checked_add(name.len())?;
+            if data.get(name_start..name_end) == Some(name.as_bytes()) && data.get(name_end) == Some(&0) {
+                return Some(true);
+            }
+        }
+        Some(false)
+    };
+    find().unwrap_or(false)
 }
 
 pub struct MemorySet {
@@ -53,6 +137,21 @@ impl MemorySet {
     pub fn token(&self) -> usize {
         self.page_table.token()
     }
+    /// 所有区域的起止页号与权限，用于生成core文件
+    pub fn user_areas(&self) -> Vec<(VirtPageNum, VirtPageNum, MapPermission)> {
+        self.areas
+            .iter()
+            .map(|area| (area.vpn_range.get_start(), area.vpn_range.get_end(), area.map_perm))
+            .collect()
+    }
+    /// 用户可访问区域的总大小（字节），用于RLIMIT_AS
+    pub fn user_size(&self) -> usize {
+        self.areas
+            .iter()
+            .filter(|area| area.map_perm.contains(MapPermission::U))
+            .map(|area| (area.vpn_range.get_end().0 - area.vpn_range.get_start().0) * PAGE_SIZE)
+            .sum()
+    }
 
     pub fn insert_framed_area(
         &mut self,
@@ -66,21 +165,144 @@ impl MemorySet {
         );
     }
 
-    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
-        if let Some((idx, area)) = self
+    /// 同insert_framed_area，并从区域开头起填入data
+    pub fn insert_framed_area_with_data(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+        data: &[u8],
+    ) {
+        self.push(
+            MapArea::new(start_va, end_va, MapType::Framed, permission),
+            Some(data),
+        );
+    }
+
+    /// [start, end)与已有的区域都不重叠
+    pub fn range_free(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        self.areas
+            .iter()
+            .all(|area| area.vpn_range.get_end() <= start || area.vpn_range.get_start() >= end)
+    }
+
+    /// mmap用：MMAP_TOP以下、自上而下找到的第一段len字节的空闲区间
+    pub fn find_free_range(&self, len: usize) -> Option<VirtAddr> {
+        let pages = len.div_ceil(PAGE_SIZE);
+        let mut end = VirtAddr::from(MMAP_TOP).floor().0;
+        loop {
+            // 第0页不映射，空指针总会出错
+            let start = end.checked_sub(pages).filter(|start| *start > 0)?;
+            let blocker = self
+                .areas
+                .iter()
+                .filter(|area| {
+                    area.vpn_range.get_end().0 > start && area.vpn_range.get_start().0 < end
+                })
+                .map(|area| area.vpn_range.get_start().0)
+                .min();
+            match blocker {
+                Some(blocker) => end = blocker,
+                None => return Some(VirtPageNum(start).into()),
+            }
+        }
+    }
+
+    /// 若有区域跨过vpn，把它从vpn处一分为二
+    fn split_area_at(&mut self, vpn: VirtPageNum) {
+        if let Some(area) = self
             .areas
             .iter_mut()
-            .enumerate()
-            .find(|(_, area)| area.vpn_range.get_start() == start_vpn)
+            .find(|area| area.vpn_range.get_start() < vpn && vpn < area.vpn_range.get_end())
         {
-            area.unmap(&mut self.page_table);
-            self.areas.remove(idx);
+            let tail = area.split_off(vpn);
+            self.areas.push(tail);
+        }
+    }
+
+    /// munmap用：解除[start, end)内所有用户区域的映射，跨边界的区域只去掉重叠的部分
+    pub fn unmap_range(&mut self, start: VirtPageNum, end: VirtPageNum) {
+        self.split_area_at(start);
+        self.split_area_at(end);
+        let page_table = &mut self.page_table;
+        self.areas.retain_mut(|area| {
+            // 空的堆区域留着给brk用
+            let inside = area.vpn_range.get_start() >= start
+                && area.vpn_range.get_end() <= end
+                && area.vpn_range.get_start() < area.vpn_range.get_end()
+                && area.map_perm.contains(MapPermission::U);
+            if inside {
+                area.unmap(page_table);
+            }
+            !inside
+        });
+        unsafe {
+            asm!("sfence.vma");
         }
     }
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
+
+    /// mprotect用：把[start, end)的权限改为perm，其中每一页都须已映射给用户，
+    /// 否则什么也不改并返回false
+    pub fn protect_range(
+        &mut self,
+        start: VirtPageNum,
+        end: VirtPageNum,
+        perm: MapPermission,
+    ) -> bool {
+        let mut vpn = start;
+        while vpn < end {
+            match self.translate(vpn) {
+                Some(pte) if pte.is_valid() && pte.flags().contains(PTEFlags::U) => {}
+                _ => return false,
+            }
+            vpn.step();
+        }
+        self.split_area_at(start);
+        self.split_area_at(end);
+        let perm = perm | MapPermission::U;
+        for area in self.areas.iter_mut() {
+            if area.vpn_range.get_start() < start || area.vpn_range.get_end() > end {
+                continue;
+            }
+            area.map_perm = perm;
+            let mut flags = PTEFlags::from_bits(perm.bits()).unwrap();
+            // 共享的COW页仍只读，写时再复制
+            if area.map_type == MapType::Cow {
+                flags.remove(PTEFlags::W);
+            }
+            for vpn in area.vpn_range {
+                let ppn = self.page_table.translate(vpn).unwrap().ppn();
+                self.page_table.map_modify(vpn, ppn, flags);
+            }
+        }
+        unsafe {
+            asm!("sfence.vma");
+        }
+        true
+    }
+
+    /// 移除从start_vpn开始的区域并返回它。页表项已清除，但帧要等返回值
+    /// 被丢弃才回收，调用者可以先让其他hart刷新TLB
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) -> Option<MapArea> {
+        let idx = self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() == start_vpn)?;
+        let area = self.areas.remove(idx);
+        for vpn in area.vpn_range {
+            self.page_table.unmap(vpn);
+        }
+        Some(area)
+    }
+    fn push(&mut self, map_area: MapArea, data: Option<&[u8]>) {
+        self.push_at(map_area, data, 0);
+    }
+
+    /// 同push，data从区域第一页内的offset处开始填
+    fn push_at(&mut self, mut map_area: MapArea, data: Option<&[u8]>, offset: usize) {
         map_area.map(&mut self.page_table);
         if let Some(data) = data {
-            map_area.copy_data(&self.page_table, data);
+            map_area.copy_data(&self.page_table, data, offset);
         }
         self.areas.push(map_area);
     }
@@ -97,18 +319,27 @@ impl MemorySet {
         // }
     }
 
+    /// 用户态可执行的sigreturn跳板页
+    fn map_sigreturn_trampoline(&mut self) {
+        self.page_table.map(
+            VirtAddr::from(SIGRETURN_TRAMPOLINE).into(),
+            PhysAddr::from(ssigreturn as usize).into(),
+            PTEFlags::R | PTEFlags::X | PTEFlags::U,
+        );
+    }
+
     pub fn new_kernel() -> Self {
         let mut memory_set = Self::new_bare();
         memory_set.map_trampoline();
-        println!(".text [{:#x}, {:#x})", stext as usize, etext as usize);
-        println!(".rodata [{:#x}, {:#x})", srodata as usize, erodata as usize);
-        println!(".data [{:#x}, {:#x})", sdata as usize, edata as usize);
-        println!(
+        info!(".text [{:#x}, {:#x})", stext as usize, etext as usize);
+        info!(".rodata [{:#x}, {:#x})", srodata as usize, erodata as usize);
+        info!(".data [{:#x}, {:#x})", sdata as usize, edata as usize);
+        info!(
             ".bss [{:#x}, {:#x})",
             sbss_with_stack as usize, ebss as usize
         );
 
-        println!("mapping UART section");
+        debug!("mapping UART section");
         // 添加UART设备映射 - 将物理地址映射到相同的虚拟地址或特定虚拟地址
         memory_set.push(
             MapArea::new(
@@ -119,7 +350,7 @@ impl MemorySet {
             ),
             None,
         );
-        println!("mapping Timer section");
+        debug!("mapping Timer section");
         // 添加Timer设备映射 - 将物理地址映射到相同的虚拟地址或特定虚拟地址
         memory_set.push(
             MapArea::new(
@@ -139,7 +370,7 @@ impl MemorySet {
             ),
             None,
         );
-        println!("mapping .text section");
+        debug!("mapping .text section");
         memory_set.push(
             MapArea::new(
                 (stext as usize).into(),
@@ -149,7 +380,7 @@ impl MemorySet {
             ),
             None,
         );
-        println!("mapping .rodata section");
+        debug!("mapping .rodata section");
         memory_set.push(
             MapArea::new(
                 (srodata as usize).into(),
@@ -159,7 +390,7 @@ impl MemorySet {
             ),
             None,
         );
-        println!("mapping .data section");
+        debug!("mapping .data section");
         memory_set.push(
             MapArea::new(
                 (sdata as usize).into(),
@@ -169,7 +400,7 @@ impl MemorySet {
             ),
             None,
         );
-        println!("mapping .bss section");
+        debug!("mapping .bss section");
         memory_set.push(
             MapArea::new(
                 (sbss_with_stack as usize).into(),
@@ -179,7 +410,7 @@ impl MemorySet {
             ),
             None,
         );
-        println!("mapping physical memory");
+        debug!("mapping physical memory");
         memory_set.push(
             MapArea::new(
                 (ekernel as usize).into(),
@@ -189,7 +420,7 @@ impl MemorySet {
             ),
             None,
         );
-        println!("mapping memory-mapped registers");
+        debug!("mapping memory-mapped registers");
         for pair in MMIO {
             memory_set.push(
                 MapArea::new(
@@ -204,43 +435,76 @@ impl MemorySet {
         memory_set
     }
 
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
-        let mut memory_set = Self::new_bare();
-        memory_set.map_trampoline();
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+    /// 加载ELF并映射大小为`stack_size`的用户栈。不是64位RISC-V的ELF，
+    /// 或者文件被截断、段越界或互相重叠时返回ENOEXEC
+    pub fn from_elf(elf_data: &[u8], stack_size: usize) -> SysResult<(Self, usize, ElfInfo)> {
+        check_elf_headers(elf_data).ok_or(Errno::ENOEXEC)?;
+        let elf = xmas_elf::ElfFile::new(elf_data).map_err(|_| Errno::ENOEXEC)?;
         let elf_header = elf.header;
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
         let ph_count = elf_header.pt2.ph_count();
-        let mut max_end_vpn = VirtPageNum(0);
+        let ph_offset = elf_header.pt2.ph_offset() as usize;
+        // 先检查所有LOAD段，再开始映射
+        let mut segments: Vec<(VPNRange, xmas_elf::program::ProgramHeader)> = Vec::new();
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
-                let mut map_perm = MapPermission::U;
-                let ph_flags = ph.flags();
-                if ph_flags.is_read() {
-                    map_perm |= MapPermission::R;
-                }
-                if ph_flags.is_write() {
-                    map_perm |= MapPermission::W;
-                }
-                if ph_flags.is_execute() {
-                    map_perm |= MapPermission::X;
-                }
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-                max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+            let ph = elf.program_header(i).map_err(|_| Errno::ENOEXEC)?;
+            if ph.get_type() != Ok(xmas_elf::program::Type::Load) {
+                continue;
+            }
+            let (offset, file_size) = (ph.offset() as usize, ph.file_size() as usize);
+            let (start, mem_size) = (ph.virtual_addr() as usize, ph.mem_size() as usize);
+            let end = start.checked_add(mem_size).filter(|&end| end <= MMAP_TOP);
+            let file_end = offset.checked_add(file_size).filter(|&end| end <= elf_data.len());
+            let (Some(end), Some(_)) = (end, file_end) else {
+                return Err(Errno::ENOEXEC);
+            };
+            if file_size > mem_size {
+                return Err(Errno::ENOEXEC);
+            }
+            let range = VPNRange::new(VirtAddr::from(start).floor(), VirtAddr::from(end).ceil());
+            if segments.iter().any(|(other, _)| {
+                range.get_start() < other.get_end() && other.get_start() < range.get_end()
+            }) {
+                return Err(Errno::ENOEXEC);
+            }
+            segments.push((range, ph));
+        }
+        let mut memory_set = Self::new_bare();
+        memory_set.map_trampoline();
+        memory_set.map_sigreturn_trampoline();
+        let mut phdr = 0;
+        let mut max_end_vpn = VirtPageNum(0);
+        for (range, ph) in segments {
+            // 程序头表所在的段，用于计算AT_PHDR
+            let file_range = ph.offset() as usize..(ph.offset() + ph.file_size()) as usize;
+            if file_range.contains(&ph_offset) {
+                phdr = ph.virtual_addr() as usize + ph_offset - ph.offset() as usize;
+            }
+            let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
+            let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+            let mut map_perm = MapPermission::U;
+            let ph_flags = ph.flags();
+            if ph_flags.is_read() {
+                map_perm |= MapPermission::R;
+            }
+            if ph_flags.is_write() {
+                map_perm |= MapPermission::W;
+            }
+            if ph_flags.is_execute() {
+                map_perm |= MapPermission::X;
             }
+            let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
+            max_end_vpn = max_end_vpn.max(range.get_end());
+            // 段不一定从页首开始，如musl程序的数据段
+            memory_set.push_at(
+                map_area,
+                Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
+                start_va.page_offset(),
+            );
         }
         let max_end_va: VirtAddr = max_end_vpn.into();
         let mut user_stack_bottom: usize = max_end_va.into();
         user_stack_bottom += PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        let user_stack_top = user_stack_bottom + stack_size;
         memory_set.push(
             MapArea::new(
                 user_stack_bottom.into(),
@@ -250,6 +514,16 @@ impl MemorySet {
             ),
             None,
         );
+        // 堆一开始是空的，brk再扩展
+        memory_set.push(
+            MapArea::new(
+                user_stack_top.into(),
+                user_stack_top.into(),
+                MapType::Framed,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
         // TrapContext
         memory_set.push(
             MapArea::new(
@@ -260,11 +534,18 @@ impl MemorySet {
             ),
             None,
         );
-        (
+        Ok((
             memory_set,
             user_stack_top,
-            elf.header.pt2.entry_point() as usize,
-        )
+            ElfInfo {
+                entry: elf.header.pt2.entry_point() as usize,
+                phdr,
+                phent: elf_header.pt2.ph_entry_size() as usize,
+                phnum: ph_count as usize,
+                heap_bottom: user_stack_top,
+                native: has_section(elf_data, ".note.acore"),
+            },
+        ))
     }
 
     /// fork 时调用：新的 MemorySet
@@ -272,6 +553,7 @@ impl MemorySet {
         // 1. 新建一张空页表
         let mut child = Self::new_bare();
         child.map_trampoline();
+        child.map_sigreturn_trampoline();
         // println!("TRAP_CONTEXT: {:#x}", VirtAddr::from(TRAP_CONTEXT).0);
         // 2. 遍历父进程每一个 MapArea
         for area in user_space.areas.iter_mut() {
@@ -401,9 +683,148 @@ impl MemorySet {
         return false;
     }
 
+    /// 内核写用户内存[start, start + len)之前调用：检查每页都是用户可写的，
+    /// 遇到COW页先完成复制
+    pub fn prepare_user_write(&mut self, start: usize, len: usize) -> bool {
+        let end = start + len;
+        let mut vpn = VirtAddr::from(start).floor();
+        while VirtAddr::from(vpn).0 < end {
+            let va: VirtAddr = vpn.into();
+            match self.translate(vpn) {
+                Some(pte) if pte.is_valid() && pte.flags().contains(PTEFlags::U) => {
+                    if !pte.writable() {
+                        if !Self::cow_judge(self, va) {
+                            return false;
+                        }
+                        self.cow(va);
+                    }
+                }
+                _ => return false,
+            }
+            vpn.step();
+        }
+        true
+    }
+
+    /// 调试器（ptrace）写用户内存[start, start + len)之前调用：不要求页可写，
+    /// 与其他进程共享的页先复制一份，免得改到别人的代码
+    pub fn prepare_debug_write(&mut self, start: usize, len: usize) -> bool {
+        let end = start + len;
+        let mut vpn = VirtAddr::from(start).floor();
+        while VirtAddr::from(vpn).0 < end {
+            match self.translate(vpn) {
+                Some(pte) if pte.is_valid() && pte.flags().contains(PTEFlags::U) => {
+                    if !pte.writable() {
+                        self.cow(vpn.into());
+                    }
+                }
+                _ => return false,
+            }
+            vpn.step();
+        }
+        true
+    }
+
+    /// 检查用户内存[start, start + len)每页都是用户可读的
+    pub fn check_user_read(&self, start: usize, len: usize) -> bool {
+        let end = start + len;
+        let mut vpn = VirtAddr::from(start).floor();
+        while VirtAddr::from(vpn).0 < end {
+            match self.translate(vpn) {
+                Some(pte) if pte.is_valid() && pte.flags().contains(PTEFlags::U) && pte.readable() => {}
+                _ => return false,
+            }
+            vpn.step();
+        }
+        true
+    }
+
+    /// 用户内存[start, start + len)对应的各段物理内存，供内核读：
+    /// 各页须是用户可读的，否则返回EFAULT
+    pub fn user_slices(&self, start: usize, len: usize) -> SysResult<Vec<&'static mut [u8]>> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        if start.checked_add(len).is_none() || !self.check_user_read(start, len) {
+            return Err(Errno::EFAULT);
+        }
+        Ok(translated_byte_buffer(self.token(), start as *const u8, len))
+    }
+
+    /// 同user_slices，供内核写：各页须是用户可写的，COW页先复制
+    pub fn user_slices_mut(
+        &mut self,
+        start: usize,
+        len: usize,
+    ) -> SysResult<Vec<&'static mut [u8]>> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        if start.checked_add(len).is_none() || !self.prepare_user_write(start, len) {
+            return Err(Errno::EFAULT);
+        }
+        Ok(translated_byte_buffer(self.token(), start as *const u8, len))
+    }
+
+    /// 把用户内存src处的dst.len()字节读到dst
+    pub fn read_user(&self, src: usize, dst: &mut [u8]) -> SysResult<()> {
+        let mut offset = 0;
+        for slice in self.user_slices(src, dst.len())? {
+            dst[offset..offset + slice.len()].copy_from_slice(slice);
+            offset += slice.len();
+        }
+        Ok(())
+    }
+
+    /// 把src写到用户内存dst处
+    pub fn write_user(&mut self, dst: usize, src: &[u8]) -> SysResult<()> {
+        let mut offset = 0;
+        for slice in self.user_slices_mut(dst, src.len())? {
+            slice.copy_from_slice(&src[offset..offset + slice.len()]);
+            offset += slice.len();
+        }
+        Ok(())
+    }
+
+    /// 读出用户指针src指向的T
+    pub fn copy_from_user<T: Copy>(&self, src: *const T) -> SysResult<T> {
+        let mut value = core::mem::MaybeUninit::<T>::uninit();
+        let size = core::mem::size_of::<T>();
+        let bytes = unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size) };
+        self.read_user(src as usize, bytes)?;
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// 把value写到用户指针dst处
+    pub fn copy_to_user<T: Copy>(&mut self, dst: *mut T, value: &T) -> SysResult<()> {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+        };
+        self.write_user(dst as usize, bytes)
+    }
+
+    /// 读出用户内存ptr处以0结尾的字符串，逐页检查可读
+    pub fn read_user_str(&self, ptr: usize) -> SysResult<String> {
+        let mut string = String::new();
+        let mut va = ptr;
+        loop {
+            if (va == ptr || va % PAGE_SIZE == 0) && !self.check_user_read(va, 1) {
+                return Err(Errno::EFAULT);
+            }
+            let pte = self.translate(VirtAddr::from(va).floor()).unwrap();
+            let ch = pte.ppn().get_bytes_array()[VirtAddr::from(va).page_offset()];
+            if ch == 0 {
+                return Ok(string);
+            }
+            string.push(ch as char);
+            va += 1;
+        }
+    }
+
     pub fn from_existed_user(user_space: &Self) -> Self {
         let mut memory_set = Self::new_bare();
         memory_set.map_trampoline();
+        memory_set.map_sigreturn_trampoline();
         for area in user_space.areas.iter() {
             let new_area = MapArea::from_another(area);
             memory_set.push(new_area, None);
@@ -429,12 +850,19 @@ impl MemorySet {
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
         self.page_table.translate(vpn)
     }
+    /// 映射着`vpn`的物理页帧，返回的`FrameTracker`多占一份引用，
+    /// 在它被释放前这个页帧不会被回收
+    pub fn frame(&self, vpn: VirtPageNum) -> Option<FrameTracker> {
+        self.areas
+            .iter()
+            .find_map(|area| area.data_frames.get(&vpn))
+            .cloned()
+    }
 
     pub fn recycle_data_pages(&mut self) {
         //*self = Self::new_bare();
         self.areas.clear();
     }
-    #[allow(unused)]
     pub fn shrink_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
         if let Some(area) = self
             .areas
@@ -447,7 +875,6 @@ impl MemorySet {
             false
         }
     }
-    #[allow(unused)]
     pub fn append_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
         if let Some(area) = self
             .areas
@@ -552,14 +979,23 @@ impl MapArea {
             self.unmap_one(page_table, vpn);
         }
     }
-    #[allow(unused)]
+    /// 从at处截断，返回[at, end)的那一半
+    pub fn split_off(&mut self, at: VirtPageNum) -> Self {
+        let tail = Self {
+            vpn_range: VPNRange::new(at, self.vpn_range.get_end()),
+            data_frames: self.data_frames.split_off(&at),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+        };
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), at);
+        tail
+    }
     pub fn shrink_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
         for vpn in VPNRange::new(new_end, self.vpn_range.get_end()) {
             self.unmap_one(page_table, vpn)
         }
         self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
     }
-    #[allow(unused)]
     pub fn append_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
         for vpn in VPNRange::new(self.vpn_range.get_end(), new_end) {
             self.map_one(page_table, vpn, None);
@@ -567,23 +1003,23 @@ impl MapArea {
         self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
     }
 
-    pub fn copy_data(&mut self, page_table: &PageTable, data: &[u8]) {
+    /// 把data拷到区域中，从第一页内的offset处开始
+    pub fn copy_data(&mut self, page_table: &PageTable, data: &[u8], offset: usize) {
         assert_eq!(self.map_type, MapType::Framed);
         let mut start: usize = 0;
+        let mut page_offset = offset;
         let mut current_vpn = self.vpn_range.get_start();
         let len = data.len();
-        loop {
-            let src = &data[start..len.min(start + PAGE_SIZE)];
+        while start < len {
+            let copy_len = (PAGE_SIZE - page_offset).min(len - start);
             let dst = &mut page_table
                 .translate(current_vpn)
                 .unwrap()
                 .ppn()
-                .get_bytes_array()[..src.len()];
-            dst.copy_from_slice(src);
-            start += PAGE_SIZE;
-            if start >= len {
-                break;
-            }
+                .get_bytes_array()[page_offset..page_offset + copy_len];
+            dst.copy_from_slice(&data[start..start + copy_len]);
+            start += copy_len;
+            page_offset = 0;
             current_vpn.step();
         }
     }
@@ -607,7 +1043,7 @@ bitflags! {
 
 #[allow(unused)]
 pub fn remap_test() {
-    let mut kernel_space = KERNEL_SPACE.exclusive_access();
+    let mut kernel_space = KERNEL_SPACE.lock();
     let mid_text: VirtAddr = ((stext as usize + etext as usize) / 2).into();
     let mid_rodata: VirtAddr = ((srodata as usize + erodata as usize) / 2).into();
     let mid_data: VirtAddr = ((sdata as usize + edata as usize) / 2).into();
@@ -632,5 +1068,5 @@ pub fn remap_test() {
             .unwrap()
             .executable(),
     );
-    println!("remap_test passed!");
+    info!("remap_test passed!");
 }
@@ -7,16 +7,20 @@ mod memory_set;
 mod page_table;
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
 use address::VPNRange;
-pub use frame_allocator::{FrameTracker, frame_alloc, frame_dealloc};
+pub use frame_allocator::{FrameTracker, frame_alloc, frame_dealloc, frames_free};
 use heap_allocator::heap_test;
 pub use memory_set::remap_test;
-pub use memory_set::{KERNEL_SPACE, MapPermission, MemorySet, kernel_token};
+pub use memory_set::{ElfInfo, KERNEL_SPACE, MapPermission, MemorySet, kernel_token};
 use page_table::PTEFlags;
 pub use page_table::{PageTable, PageTableEntry, UserBuffer, UserBufferIterator, translated_byte_buffer,
-    translated_ref, translated_refmut, translated_str};
+    translated_refmut};
 /// initiate heap allocator, frame allocator and kernel space
 pub fn init() {
     heap_allocator::init_heap();
     frame_allocator::init_frame_allocator();
-    KERNEL_SPACE.exclusive_access().activate();
+    KERNEL_SPACE.lock().activate();
+}
+/// switch a secondary hart to the kernel space built by hart 0
+pub fn init_secondary() {
+    KERNEL_SPACE.lock().activate();
 }
@@ -3,7 +3,7 @@ use core::arch::global_asm;
 use riscv::register::mscratch;
 use riscv::register::mstatus::MPP;
 use riscv::register::sstatus;
-use riscv::register::{mcause, mepc, mie, mstatus, mtvec, pmpaddr0, pmpcfg0, satp, sie};
+use riscv::register::{mcause, mepc, mhartid, mie, mstatus, mtvec, pmpaddr0, pmpcfg0, satp, sie};
 
 use crate::config::TRAP_CONTEXT;
 use crate::config::TRAMPOLINE;
@@ -33,19 +33,26 @@ unsafe extern "C" {
     safe fn __alltraps_m(); // 中断处理
     safe fn smstack(); // M 模式栈
     safe fn emstack(); // M 模式栈
+    safe fn m_stack_lower_bound(); // 各 hart 的 M 模式栈
+    safe fn boot_stack_lower_bound(); // 各 hart 的 S 模式启动栈
 }
 
+/// 每个 hart 的 M 模式栈与 S 模式启动栈大小，需与 entry.s 一致
+const HART_STACK_SIZE: usize = 4096 * 16;
+
 mod mtrap;
 mod mtimer;
-/// 从 M 模式切换到 S 模式
+/// 从 M 模式切换到 S 模式，所有 hart 都会执行
 #[unsafe(no_mangle)]
 #[unsafe(link_section = ".text.minit")]
 pub unsafe fn m_mode_init() -> ! {
+    let hartid = mhartid::read();
     mstatus::set_mpp(MPP::Supervisor); // 设置 MPP=01 (S 模式)
     // 设置 S 模式入口点为 rust_main
     mepc::write(rust_main as usize);
 
-    mscratch::write(emstack as usize); // 设置 M 模式栈指针
+    // 设置 M 模式栈指针，每个 hart 独占一段
+    mscratch::write(m_stack_lower_bound as usize + (hartid + 1) * HART_STACK_SIZE);
 
     // 禁用分页
     satp::write(0);
@@ -76,13 +83,24 @@ pub unsafe fn m_mode_init() -> ! {
     }
 
     // 启用 S 模式中断
+    // 内核在 S 模式下保持 SIE=0 运行（空闲 hart 会在 run_tasks 中自旋），
+    // 处于 U 模式时 S 级中断总是可以被响应
     unsafe {
-        sstatus::set_sie(); // 设置 SIE (S-mode Interrupt Enable)
         sie::set_sext(); // 设置 SEIE (S-mode External Interrupt Enable)
         sie::set_stimer(); // 设置 STIE (S-mode Timer Interrupt Enable)
         sie::set_ssoft(); // 设置 SSIE (S-mode Software Interrupt Enable)
     }
 
+    // S 模式可读全部计数器，U 模式可读 cycle、time、instret 与 hpmcounter
+    unsafe {
+        asm!(
+            "li {0}, -1",
+            "csrw mcounteren, {0}",
+            "csrw scounteren, {0}",
+            out(reg) _
+        );
+    }
+
     pmpaddr0::write(0x3fffffffffffff);
     pmpcfg0::write(0xf);
 
@@ -108,13 +126,20 @@ pub unsafe fn m_mode_init() -> ! {
         mstatus::set_mie();
 
         mie::set_mtimer();
+        mie::set_msoft(); // 核间中断 (IPI)
     }
+    // tp 在 S 模式下保存 hartid，sp 指向本 hart 的启动栈，
+    // 然后执行 mret 指令切换到 S 模式
+    let boot_sp = boot_stack_lower_bound as usize + (hartid + 1) * HART_STACK_SIZE;
     unsafe {
-        asm!("la sp, boot_stack_top");
-    }
-    // 执行 mret 指令切换到 S 模式
-    unsafe {
-        asm!("mret", options(noreturn));
+        asm!(
+            "mv tp, {hartid}",
+            "mv sp, {sp}",
+            "mret",
+            hartid = in(reg) hartid,
+            sp = in(reg) boot_sp,
+            options(noreturn)
+        );
     }
 }
 
@@ -1,5 +1,6 @@
 //! RISC-V timer-related functionality
 use crate::board::CLOCK_FREQ;
+use riscv::register::mhartid;
 // use riscv::register::time;
 
 const MSEC_PER_SEC: usize = 1000;
@@ -21,11 +22,13 @@ pub fn read_time() -> u64 {
     unsafe { (MTIME_ADDR as *const u64).read_volatile() }
 }
 
-/// 配置时钟中断
+/// 配置当前 hart 的时钟中断
 pub fn set_timer() {
     const MTIME_ADDR: usize = 0x0200bff8;
     const MTIMECMP_ADDR: usize = 0x02004000;
     const INTERVAL: u64 = 1000000; // 大约1秒
+    // 每个 hart 有自己的 mtimecmp 寄存器
+    let mtimecmp = MTIMECMP_ADDR + 8 * mhartid::read();
 
     unsafe {
         // 读取当前时间
@@ -33,6 +36,6 @@ pub fn set_timer() {
 
         // 设置下一次中断时间
         let next_time = mtime + INTERVAL;
-        (MTIMECMP_ADDR as *mut u64).write_volatile(next_time);
+        (mtimecmp as *mut u64).write_volatile(next_time);
     }
 }
\ No newline at end of file
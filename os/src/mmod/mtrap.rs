@@ -5,7 +5,7 @@ use crate::task::{
     current_trap_cx, current_user_token
 };
 use riscv::register::{
-    mcause::{self,Interrupt},mtvec::TrapMode,stvec,mtval,mip,mepc,mstatus::{self,MPP}
+    mcause::{self,Interrupt},mtvec::TrapMode,stvec,mtval,mip,mepc,mhartid,mstatus::{self,MPP}
 };
 use crate::mmod::mtimer::set_next_trigger;
 #[unsafe(no_mangle)]
@@ -25,6 +25,12 @@ pub fn trap_handler_m(){
         mcause::Trap::Interrupt(Interrupt::MachineTimer) => {
                 // 从 U 模式跳转过来的情况下执行的操作
                 set_next_trigger();
+                // 给采样分析器记下被打断的内核pc，来自U模式则为None
+                let kernel_pc = match mstatus::read().mpp() {
+                    MPP::User => None,
+                    _ => Some(mepc::read()),
+                };
+                crate::task::note_tick(mhartid::read(), kernel_pc);
                 unsafe {
                     asm!(
                         "csrw sip, 2",
@@ -43,6 +49,14 @@ pub fn trap_handler_m(){
             // // println!("|m_timer_interrupt|");
             // cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
         }
+        mcause::Trap::Interrupt(Interrupt::MachineSoft) => {
+            // 核间中断：清除本 hart 的 msip，转发为 S 模式软件中断
+            const CLINT_MSIP: usize = 0x0200_0000;
+            unsafe {
+                ((CLINT_MSIP + 4 * mhartid::read()) as *mut u32).write_volatile(0);
+                asm!("csrsi sip, 2");
+            }
+        }
         _ => {
             panic!(
                 "Unsupported trap {:?}, mtval = {:#x}!",
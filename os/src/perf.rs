@@ -0,0 +1,251 @@
+//! Hardware performance counters
+//!
+//! `m_mode_init` lets user mode read `cycle`, `time` and `instret` (and the
+//! `hpmcounter`s) itself, but those count for the whole hart. A task also
+//! keeps its own totals, charged with what the counters advanced while it
+//! was on a hart each time it is switched out; `perf_event_open` hands
+//! out files reading those, so counts follow the task across harts.
+use crate::errno::{Errno, SysResult};
+use crate::fs::{File, FileKind, Stat};
+use crate::mm::UserBuffer;
+use crate::sync::SpinMutex;
+use crate::task::{current_task, TaskControlBlock};
+use alloc::sync::{Arc, Weak};
+use core::any::Any;
+use core::arch::asm;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub const PERF_TYPE_HARDWARE: u32 = 0;
+pub const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+pub const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+
+/// `PerfEventAttr::flags`: open the event disabled
+const ATTR_DISABLED: u64 = 1 << 0;
+const ATTR_EXCLUDE_USER: u64 = 1 << 4;
+const ATTR_EXCLUDE_KERNEL: u64 = 1 << 5;
+
+pub const PERF_EVENT_IOC_ENABLE: usize = 0x2400;
+pub const PERF_EVENT_IOC_DISABLE: usize = 0x2401;
+pub const PERF_EVENT_IOC_RESET: usize = 0x2403;
+
+/// The part of Linux's `struct perf_event_attr` we look at
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PerfEventAttr {
+    pub type_: u32,
+    pub size: u32,
+    pub config: u64,
+    pub sample_period: u64,
+    pub sample_type: u64,
+    pub read_format: u64,
+    pub flags: u64,
+}
+
+fn read_cycle() -> u64 {
+    let cycle: u64;
+    unsafe {
+        asm!("csrr {}, cycle", out(reg) cycle);
+    }
+    cycle
+}
+
+fn read_instret() -> u64 {
+    let instret: u64;
+    unsafe {
+        asm!("csrr {}, instret", out(reg) instret);
+    }
+    instret
+}
+
+#[derive(Clone, Copy, Default)]
+struct Counts {
+    cycles: u64,
+    instret: u64,
+}
+
+impl Counts {
+    /// The counters of this hart
+    fn now() -> Self {
+        Self {
+            cycles: read_cycle(),
+            instret: read_instret(),
+        }
+    }
+    /// `self` plus how far the counters moved from `from` to `to`
+    fn advanced(&self, from: Counts, to: Counts) -> Self {
+        Self {
+            cycles: self.cycles + to.cycles.wrapping_sub(from.cycles),
+            instret: self.instret + to.instret.wrapping_sub(from.instret),
+        }
+    }
+}
+
+/// What the counters advanced while a task was on a hart
+#[derive(Default)]
+pub struct TaskCounters {
+    total: Counts,
+    /// the counters when the task last went on its hart
+    stamp: Counts,
+}
+
+impl TaskCounters {
+    pub fn switch_in(&mut self) {
+        self.stamp = Counts::now();
+    }
+    pub fn switch_out(&mut self) {
+        self.total = self.total.advanced(self.stamp, Counts::now());
+    }
+    /// Totals up to now, for the task running on this hart
+    fn running(&self) -> Counts {
+        self.total.advanced(self.stamp, Counts::now())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PerfKind {
+    Cycles,
+    Instructions,
+}
+
+/// An open counter of one task, read as a u64. The task may be reaped
+/// while the file is still open elsewhere, so it is not kept alive.
+pub struct PerfEvent {
+    kind: PerfKind,
+    task: Weak<TaskControlBlock>,
+    /// the task's counter when it was last looked at, for once it is gone
+    last: AtomicU64,
+    inner: SpinMutex<PerfEventInner>,
+}
+
+struct PerfEventInner {
+    enabled: bool,
+    /// counted while enabled, up to `since`
+    count: u64,
+    /// the task's counter when the event was last enabled or reset
+    since: u64,
+}
+
+impl PerfEvent {
+    /// The task's counter now: up to the moment if it is the one asking,
+    /// up to when it was last switched out otherwise, and the last one
+    /// seen once the task is gone. Never less than a count returned before,
+    /// even to another hart reading behind a switch-out.
+    fn task_count(&self) -> u64 {
+        let Some(task) = self.task.upgrade() else {
+            return self.last.load(Ordering::Relaxed);
+        };
+        let running = current_task().is_some_and(|current| Arc::ptr_eq(&current, &task));
+        let inner = task.inner_exclusive_access();
+        let counts = if running {
+            inner.perf.running()
+        } else {
+            inner.perf.total
+        };
+        let count = match self.kind {
+            PerfKind::Cycles => counts.cycles,
+            PerfKind::Instructions => counts.instret,
+        };
+        self.last.fetch_max(count, Ordering::Relaxed).max(count)
+    }
+    fn value(&self) -> u64 {
+        let now = self.task_count();
+        let inner = self.inner.lock();
+        if inner.enabled {
+            inner.count + now.saturating_sub(inner.since)
+        } else {
+            inner.count
+        }
+    }
+    /// PERF_EVENT_IOC_ENABLE, DISABLE and RESET
+    pub fn ioctl(&self, request: usize) -> SysResult<()> {
+        let now = self.task_count();
+        let mut inner = self.inner.lock();
+        match request {
+            PERF_EVENT_IOC_ENABLE if !inner.enabled => {
+                inner.enabled = true;
+                inner.since = now;
+            }
+            PERF_EVENT_IOC_DISABLE if inner.enabled => {
+                inner.enabled = false;
+                inner.count += now.saturating_sub(inner.since);
+            }
+            PERF_EVENT_IOC_ENABLE | PERF_EVENT_IOC_DISABLE => {}
+            PERF_EVENT_IOC_RESET => {
+                inner.count = 0;
+                inner.since = now;
+            }
+            _ => return Err(Errno::ENOTTY),
+        }
+        Ok(())
+    }
+}
+
+/// An event counting `attr` for the current task. Only hardware cycles and
+/// instructions are known, counted in both user and kernel mode, and
+/// without sampling or read formats.
+pub fn open_event(attr: &PerfEventAttr) -> SysResult<Arc<PerfEvent>> {
+    if attr.type_ != PERF_TYPE_HARDWARE {
+        return Err(Errno::ENOENT);
+    }
+    let kind = match attr.config {
+        PERF_COUNT_HW_CPU_CYCLES => PerfKind::Cycles,
+        PERF_COUNT_HW_INSTRUCTIONS => PerfKind::Instructions,
+        _ => return Err(Errno::ENOENT),
+    };
+    if attr.sample_period != 0
+        || attr.sample_type != 0
+        || attr.read_format != 0
+        || attr.flags & (ATTR_EXCLUDE_USER | ATTR_EXCLUDE_KERNEL) != 0
+    {
+        return Err(Errno::EINVAL);
+    }
+    let event = Arc::new(PerfEvent {
+        kind,
+        task: Arc::downgrade(&current_task().unwrap()),
+        last: AtomicU64::new(0),
+        inner: SpinMutex::new(PerfEventInner {
+            enabled: false,
+            count: 0,
+            since: 0,
+        }),
+    });
+    if attr.flags & ATTR_DISABLED == 0 {
+        event.ioctl(PERF_EVENT_IOC_ENABLE)?;
+    }
+    Ok(event)
+}
+
+impl File for PerfEvent {
+    fn readable(&self) -> bool {
+        true
+    }
+    fn writable(&self) -> bool {
+        false
+    }
+    /// The count as 8 native-endian bytes, nothing if they do not fit
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        if buf.len() < 8 {
+            return 0;
+        }
+        let bytes = self.value().to_ne_bytes();
+        let mut copied = 0;
+        for slice in buf.buffers.iter_mut() {
+            let n = slice.len().min(bytes.len() - copied);
+            slice[..n].copy_from_slice(&bytes[copied..copied + n]);
+            copied += n;
+        }
+        copied
+    }
+    fn write(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+    fn seek(&self, _offset: isize, _whence: usize) -> SysResult {
+        Err(Errno::ESPIPE)
+    }
+    fn stat(&self) -> Stat {
+        Stat::new(FileKind::Regular)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
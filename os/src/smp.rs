@@ -0,0 +1,115 @@
+//! Multi-hart bring-up and inter-processor interrupts
+//!
+//! Every hart enters `rust_main` after `mmod::m_mode_init`. Hart 0 initializes
+//! the kernel while the others wait in [`wait_for_boot`], then all of them run
+//! their own scheduler loop. IPIs are raised through the CLINT `msip`
+//! registers; the M-mode handler forwards them as S-mode software interrupts.
+
+use crate::config::MAX_HARTS;
+use core::arch::asm;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+const CLINT_MSIP: usize = 0x0200_0000;
+
+/// Flush the local TLB
+pub const IPI_TLB_FLUSH: usize = 1 << 0;
+
+static BOOT_FINISHED: AtomicBool = AtomicBool::new(false);
+/// Bitmask of harts that entered their scheduler loop
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+/// Pending IPI reasons of each hart
+static IPI_PENDING: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(0) }; MAX_HARTS];
+/// Number of the last `tlb_shootdown`
+static TLB_GENERATION: AtomicUsize = AtomicUsize::new(0);
+/// Latest shootdown each hart has flushed its TLB for
+static TLB_FLUSHED: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(0) }; MAX_HARTS];
+
+/// Id of the current hart, kept in `tp` while running in the kernel
+pub fn hart_id() -> usize {
+    let id: usize;
+    unsafe {
+        asm!("mv {0}, tp", out(reg) id);
+    }
+    id
+}
+
+/// Called by the boot hart once global kernel state is ready
+pub fn boot_finished() {
+    BOOT_FINISHED.store(true, Ordering::Release);
+}
+
+/// Spin until the boot hart has finished initialization
+pub fn wait_for_boot() {
+    while !BOOT_FINISHED.load(Ordering::Acquire) {
+        core::hint::spin_loop();
+    }
+}
+
+/// Mark the current hart as able to receive IPIs
+pub fn set_online() {
+    ONLINE_HARTS.fetch_or(1 << hart_id(), Ordering::AcqRel);
+}
+
+/// Raise an IPI on `hart` with the given reason bits
+pub fn send_ipi(hart: usize, reason: usize) {
+    IPI_PENDING[hart].fetch_or(reason, Ordering::AcqRel);
+    unsafe {
+        ((CLINT_MSIP + 4 * hart) as *mut u32).write_volatile(1);
+    }
+}
+
+/// Handle IPIs pending on the current hart
+pub fn handle_ipi() {
+    let reason = IPI_PENDING[hart_id()].swap(0, Ordering::AcqRel);
+    if reason & IPI_TLB_FLUSH != 0 {
+        flush_tlb();
+    }
+}
+
+/// Handle a pending TLB flush only, which is safe anywhere, even while
+/// spinning on a lock the hart waiting for the flush holds
+pub fn handle_tlb_flush() {
+    let pending = &IPI_PENDING[hart_id()];
+    if pending.load(Ordering::Relaxed) & IPI_TLB_FLUSH != 0
+        && pending.fetch_and(!IPI_TLB_FLUSH, Ordering::AcqRel) & IPI_TLB_FLUSH != 0
+    {
+        flush_tlb();
+    }
+}
+
+/// Flush the local TLB for every shootdown started so far. The generation
+/// is read after the request was taken, so it covers that request.
+fn flush_tlb() {
+    let generation = TLB_GENERATION.load(Ordering::Acquire);
+    unsafe {
+        asm!("sfence.vma");
+    }
+    TLB_FLUSHED[hart_id()].fetch_max(generation, Ordering::Release);
+}
+
+/// Flush the TLB of every online hart after the kernel page table changed,
+/// and wait until all of them have, so that what was unmapped can be
+/// reused.
+///
+/// The kernel runs with S-mode interrupts off, so remote harts pick the
+/// flush up on their next trap or scheduler iteration, or while they spin
+/// on a `SpinMutex`.
+pub fn tlb_shootdown() {
+    unsafe {
+        asm!("sfence.vma");
+    }
+    let generation = TLB_GENERATION.fetch_add(1, Ordering::AcqRel) + 1;
+    let me = hart_id();
+    let online = ONLINE_HARTS.load(Ordering::Acquire);
+    let others = || (0..MAX_HARTS).filter(move |&hart| hart != me && online & (1 << hart) != 0);
+    for hart in others() {
+        send_ipi(hart, IPI_TLB_FLUSH);
+    }
+    for hart in others() {
+        // another hart may be waiting for us in the same way
+        while TLB_FLUSHED[hart].load(Ordering::Acquire) < generation {
+            handle_tlb_flush();
+            core::hint::spin_loop();
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! Lock validator for debug builds (`make run LOCKDEP=1`)
+//!
+//! Every `SpinMutex`, `UPSafeCell` and easy-fs `Mutex` taken goes on a
+//! stack of the locks its hart holds, with the place it was taken at.
+//! Tasks never keep a lock across a switch, which `schedule` checks, so
+//! that stack is what the running task holds. Taking a lock records that
+//! it comes after each lock held. Taking a lock that is already held, or
+//! one that was once held while a lock now held was taken, is reported
+//! with the places both were taken at. A `UPSafeCell` belongs to one hart
+//! and is never waited for by another, so it is only checked for being
+//! taken twice and has no place in the order.
+//!
+//! Locks are told apart by address, and only direct inversions (A then B
+//! somewhere, B then A elsewhere) are found. Nothing here takes a tracked
+//! lock or allocates, as the heap lock is tracked too. The first report
+//! turns the validator off.
+use crate::backtrace::print_backtrace;
+use crate::config::MAX_HARTS;
+use crate::smp::hart_id;
+use core::cell::UnsafeCell;
+use core::panic::Location;
+use core::sync::atomic::{AtomicBool, Ordering};
+use easy_fs::LockHooks;
+
+/// Locks one hart can hold at once
+const MAX_HELD: usize = 32;
+/// Pairs of locks whose order is remembered
+const MAX_ORDERS: usize = 1024;
+
+type Site = &'static Location<'static>;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[derive(Clone, Copy)]
+struct Held {
+    lock: usize,
+    kind: &'static str,
+    site: Site,
+    /// other harts can take it as well
+    shared: bool,
+}
+
+struct HeldLocks {
+    locks: [Option<Held>; MAX_HELD],
+    len: usize,
+}
+
+impl HeldLocks {
+    fn iter(&self) -> impl Iterator<Item = &Held> {
+        self.locks[..self.len].iter().flatten()
+    }
+    fn shared(&self) -> impl Iterator<Item = &Held> {
+        self.iter().filter(|held| held.shared)
+    }
+    fn find(&self, lock: usize) -> Option<Held> {
+        self.iter().find(|held| held.lock == lock).copied()
+    }
+}
+
+/// The locks of one hart, only ever touched by that hart
+struct HartLocks(UnsafeCell<HeldLocks>);
+
+unsafe impl Sync for HartLocks {}
+
+static HELD: [HartLocks; MAX_HARTS] = [const {
+    HartLocks(UnsafeCell::new(HeldLocks {
+        locks: [None; MAX_HELD],
+        len: 0,
+    }))
+}; MAX_HARTS];
+
+fn held() -> &'static mut HeldLocks {
+    unsafe { &mut *HELD[hart_id()].0.get() }
+}
+
+/// `then` was taken while `first` was held
+#[derive(Clone, Copy)]
+struct Order {
+    first: Held,
+    then: Held,
+}
+
+struct OrderList {
+    orders: [Option<Order>; MAX_ORDERS],
+    len: usize,
+}
+
+impl OrderList {
+    fn find(&self, first: usize, then: usize) -> Option<Order> {
+        self.orders[..self.len]
+            .iter()
+            .flatten()
+            .find(|order| order.first.lock == first && order.then.lock == then)
+            .copied()
+    }
+    /// False if there is no room left
+    fn add(&mut self, first: Held, then: Held) -> bool {
+        if self.find(first.lock, then.lock).is_some() {
+            return true;
+        }
+        if self.len == MAX_ORDERS {
+            return false;
+        }
+        self.orders[self.len] = Some(Order { first, then });
+        self.len += 1;
+        true
+    }
+    fn remove(&mut self, lock: usize) {
+        let mut i = 0;
+        while i < self.len {
+            let order = self.orders[i].unwrap();
+            if order.first.lock == lock || order.then.lock == lock {
+                self.len -= 1;
+                self.orders[i] = self.orders[self.len].take();
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// The orders seen on all harts, behind a bare spin lock of its own
+struct Orders {
+    locked: AtomicBool,
+    list: UnsafeCell<OrderList>,
+}
+
+unsafe impl Sync for Orders {}
+
+static ORDERS: Orders = Orders {
+    locked: AtomicBool::new(false),
+    list: UnsafeCell::new(OrderList {
+        orders: [None; MAX_ORDERS],
+        len: 0,
+    }),
+};
+
+fn with_orders<R>(f: impl FnOnce(&mut OrderList) -> R) -> R {
+    while ORDERS
+        .locked
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+    let result = f(unsafe { &mut *ORDERS.list.get() });
+    ORDERS.locked.store(false, Ordering::Release);
+    result
+}
+
+/// Stop validating, so that reporting, which takes locks itself, is not
+/// validated too. True for the caller that turned it off.
+fn turn_off() -> bool {
+    ENABLED.swap(false, Ordering::Relaxed)
+}
+
+/// The panic handler turns the validator off before it prints
+pub fn disable() {
+    turn_off();
+}
+
+fn give_up(reason: &str) {
+    if turn_off() {
+        println!(
+            "[lockdep] hart {}: {}, turning off the lock validator",
+            hart_id(),
+            reason
+        );
+    }
+}
+
+/// Called before `lock`, of kind `kind` and shared between harts, is
+/// taken at `site`
+pub fn acquire(lock: usize, kind: &'static str, site: Site) {
+    take(Held {
+        lock,
+        kind,
+        site,
+        shared: true,
+    });
+}
+
+/// Called before `lock`, which belongs to this hart, is taken at `site`
+pub fn acquire_local(lock: usize, kind: &'static str, site: Site) {
+    take(Held {
+        lock,
+        kind,
+        site,
+        shared: false,
+    });
+}
+
+fn take(new: Held) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let held = held();
+    let Held {
+        lock, kind, site, ..
+    } = new;
+    if let Some(holder) = held.find(lock) {
+        if turn_off() {
+            println!("[lockdep] hart {}: recursive locking", hart_id());
+            println!("[lockdep]   {} {:#x} taken at {}", kind, lock, site);
+            println!("[lockdep]   is already held, taken at {}", holder.site);
+        }
+        panic!("{} {:#x} taken again at {}", kind, lock, site);
+    }
+    if new.shared && !record_order(held, new) {
+        return;
+    }
+    push(held, new);
+}
+
+/// Record that `new` comes after the shared locks held. False, after
+/// reporting it, if one of them was taken while `new` was held before.
+fn record_order(held: &HeldLocks, new: Held) -> bool {
+    let Held {
+        lock, kind, site, ..
+    } = new;
+    let recorded = with_orders(|orders| {
+        for holder in held.shared() {
+            if let Some(order) = orders.find(lock, holder.lock) {
+                return Err((*holder, order));
+            }
+        }
+        Ok(held.shared().all(|holder| orders.add(*holder, new)))
+    });
+    match recorded {
+        Err((holder, order)) => {
+            if turn_off() {
+                println!("[lockdep] hart {}: lock order inversion", hart_id());
+                println!("[lockdep]   {} {:#x} taken at {}", kind, lock, site);
+                println!(
+                    "[lockdep]   while holding {} {:#x}, taken at {}",
+                    holder.kind, holder.lock, holder.site
+                );
+                println!("[lockdep] but the other way round before:");
+                println!(
+                    "[lockdep]   {} {:#x} taken at {}",
+                    holder.kind, holder.lock, order.then.site
+                );
+                println!(
+                    "[lockdep]   while holding {} {:#x}, taken at {}",
+                    kind, lock, order.first.site
+                );
+                println!("[lockdep] turning off the lock validator");
+                print_backtrace();
+            }
+            false
+        }
+        Ok(false) => {
+            give_up("too many lock orders");
+            true
+        }
+        Ok(true) => true,
+    }
+}
+
+/// Called once `lock`, which belongs to this hart, was taken at `site`
+/// without waiting
+pub fn acquired_local(lock: usize, kind: &'static str, site: Site) {
+    if ENABLED.load(Ordering::Relaxed) {
+        push(
+            held(),
+            Held {
+                lock,
+                kind,
+                site,
+                shared: false,
+            },
+        );
+    }
+}
+
+fn push(held: &mut HeldLocks, new: Held) {
+    if held.len == MAX_HELD {
+        give_up("too many locks held");
+        return;
+    }
+    held.locks[held.len] = Some(new);
+    held.len += 1;
+}
+
+/// Called once `lock` is released, in whatever order the locks held are
+pub fn release(lock: usize) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let held = held();
+    if let Some(i) = held.locks[..held.len]
+        .iter()
+        .rposition(|held| held.is_some_and(|held| held.lock == lock))
+    {
+        held.locks.copy_within(i + 1..held.len, i);
+        held.len -= 1;
+        held.locks[held.len] = None;
+    }
+}
+
+/// Called when `lock` goes away, as another lock may get its address
+pub fn forget(lock: usize) {
+    if ENABLED.load(Ordering::Relaxed) {
+        with_orders(|orders| orders.remove(lock));
+    }
+}
+
+/// Called by the current task before it switches away, which it must not
+/// do holding a lock
+pub fn check_switch() {
+    if !ENABLED.load(Ordering::Relaxed) || held().len == 0 {
+        return;
+    }
+    if turn_off() {
+        println!("[lockdep] hart {}: switching away holding locks", hart_id());
+        for holder in held().iter() {
+            println!(
+                "[lockdep]   {} {:#x} taken at {}",
+                holder.kind, holder.lock, holder.site
+            );
+        }
+        println!("[lockdep] turning off the lock validator");
+        print_backtrace();
+    }
+}
+
+fn fs_acquire(lock: usize, site: Site) {
+    acquire(lock, "easy-fs Mutex", site);
+}
+
+static FS_HOOKS: LockHooks = LockHooks {
+    acquire: fs_acquire,
+    release,
+    forget,
+};
+
+/// Validate the locks of easy-fs too
+pub fn init() {
+    easy_fs::set_lock_hooks(&FS_HOOKS);
+}
@@ -1,4 +1,7 @@
 mod up;
 pub mod mutex;
+#[cfg(feature = "lockdep")]
+pub mod lockdep;
 
 pub use up::UPSafeCell;
+pub use mutex::{SpinMutex, SpinMutexGuard};
@@ -5,6 +5,10 @@ use core::cmp::{max, min};
 use core::mem::size_of;
 use core::sync::atomic::{AtomicBool, Ordering};
 use core::cell::UnsafeCell;
+#[cfg(feature = "lockdep")]
+use core::panic::Location;
+#[cfg(feature = "lockdep")]
+use super::lockdep;
 
 const BUDDY_ALLOCATOR_LEVEL: usize = 32;
 
@@ -24,8 +28,13 @@ impl<T> SpinMutex<T> {
         }
     }
 
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub fn lock(&self) -> SpinMutexGuard<T> {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(self as *const _ as usize, "SpinMutex", Location::caller());
         while self.locked.compare_and_swap(false, true, Ordering::Acquire) != false {
+            // the holder may be waiting for us to flush the TLB
+            crate::smp::handle_tlb_flush();
             core::hint::spin_loop();
         }
         
@@ -33,6 +42,13 @@ impl<T> SpinMutex<T> {
     }
 }
 
+#[cfg(feature = "lockdep")]
+impl<T> Drop for SpinMutex<T> {
+    fn drop(&mut self) {
+        lockdep::forget(self as *const _ as usize);
+    }
+}
+
 pub struct SpinMutexGuard<'a, T> {
     mutex: &'a SpinMutex<T>,
 }
@@ -53,6 +69,8 @@ impl<'a, T> core::ops::DerefMut for SpinMutexGuard<'a, T> {
 
 impl<'a, T> Drop for SpinMutexGuard<'a, T> {
     fn drop(&mut self) {
+        #[cfg(feature = "lockdep")]
+        lockdep::release(self.mutex as *const _ as usize);
         self.mutex.locked.store(false, Ordering::Release);
     }
 }
@@ -1,5 +1,12 @@
+#[cfg(feature = "lockdep")]
+use super::lockdep;
 use core::cell::{RefCell, RefMut};
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "lockdep")]
+use core::panic::Location;
 
+/// Interior mutability for data that is only ever touched by one hart,
+/// such as the per-hart `Processor`. Shared state uses `SpinMutex` instead.
 pub struct UPSafeCell<T> {
     inner: RefCell<T>,
 }
@@ -12,7 +19,56 @@ impl<T> UPSafeCell<T> {
             inner: RefCell::new(value),
         }
     }
-    pub fn exclusive_access(&self) -> RefMut<'_, T> {
-        self.inner.borrow_mut()
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn exclusive_access(&self) -> UPSafeCellGuard<'_, T> {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire_local(self as *const _ as usize, "UPSafeCell", Location::caller());
+        UPSafeCellGuard {
+            cell: self,
+            inner: self.inner.borrow_mut(),
+        }
+    }
+    /// Like `exclusive_access`, but None instead of a panic if the value
+    /// is already borrowed
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn try_exclusive_access(&self) -> Option<UPSafeCellGuard<'_, T>> {
+        let inner = self.inner.try_borrow_mut().ok()?;
+        #[cfg(feature = "lockdep")]
+        lockdep::acquired_local(self as *const _ as usize, "UPSafeCell", Location::caller());
+        Some(UPSafeCellGuard { cell: self, inner })
+    }
+}
+
+#[cfg(feature = "lockdep")]
+impl<T> Drop for UPSafeCell<T> {
+    fn drop(&mut self) {
+        lockdep::forget(self as *const _ as usize);
+    }
+}
+
+/// The value of a `UPSafeCell`, given back when dropped
+pub struct UPSafeCellGuard<'a, T> {
+    #[cfg_attr(not(feature = "lockdep"), allow(dead_code))]
+    cell: &'a UPSafeCell<T>,
+    inner: RefMut<'a, T>,
+}
+
+impl<T> Deref for UPSafeCellGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for UPSafeCellGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+#[cfg(feature = "lockdep")]
+impl<T> Drop for UPSafeCellGuard<'_, T> {
+    fn drop(&mut self) {
+        lockdep::release(self.cell as *const _ as usize);
     }
 }
@@ -1,79 +1,93 @@
 //! File and filesystem-related syscalls
+use crate::errno::{syscall_ret, Errno};
 use crate::fs::{OpenFlags, make_pipe, open_file, delete_file, make_dir, remove_dir, rename_file_or_dir};
-use crate::mm::{translated_byte_buffer, translated_refmut, translated_str, UserBuffer};
-use crate::task::{current_task, current_user_token};
+use crate::fs::{Stdin, Stdout, foreground_pgrp, set_foreground_pgrp};
+use crate::perf::{open_event, PerfEvent, PerfEventAttr};
+use crate::task::{
+    copy_from_user, copy_str_from_user, copy_to_user, current_task, pgid2tasks, user_buffer,
+};
 use alloc::sync::Arc;
 
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    let token = current_user_token();
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Errno::EBADF.into();
     }
     if let Some(file) = &inner.fd_table[fd] {
         if !file.writable() {
-            return -1;
+            return Errno::EBADF.into();
         }
         let file = file.clone();
         drop(inner);
-        file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        match user_buffer(buf, len, false) {
+            Ok(buffer) => file.write(buffer) as isize,
+            Err(errno) => errno.into(),
+        }
     } else {
-        -1
+        Errno::EBADF.into()
     }
 }
 
 pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
-    let token = current_user_token();
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Errno::EBADF.into();
     }
     if let Some(file) = &inner.fd_table[fd] {
         let file = file.clone();
         if !file.readable() {
-            return -1;
+            return Errno::EBADF.into();
         }
         drop(inner);
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        match user_buffer(buf, len, true) {
+            Ok(buffer) => file.read(buffer) as isize,
+            Err(errno) => errno.into(),
+        }
     } else {
-        -1
+        Errno::EBADF.into()
     }
 }
 
 pub fn sys_open(path: *const u8, flags: u32) -> isize {
     let task = current_task().unwrap();
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
-        let mut inner = task.inner_exclusive_access();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
-    } else {
-        -1
+    let path = match copy_str_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno.into(),
+    };
+    let Some(flags) = OpenFlags::from_bits(flags) else {
+        return Errno::EINVAL.into();
+    };
+    match open_file(path.as_str(), flags) {
+        Ok(inode) => {
+            let mut inner = task.inner_exclusive_access();
+            let Some(fd) = inner.alloc_fd() else {
+                return Errno::EMFILE.into();
+            };
+            inner.fd_table[fd] = Some(inode);
+            fd as isize
+        }
+        Err(errno) => errno.into(),
     }
 }
 
 pub fn sys_delete(path: *const u8) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if delete_file(path.as_str()) {
-        0
-    } else {
-        -1
-    }
+    let path = match copy_str_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno.into(),
+    };
+    syscall_ret(delete_file(path.as_str()).map(|_| 0))
 }
 
 pub fn sys_close(fd: usize) -> isize {
     let task = current_task().unwrap();
     let mut inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Errno::EBADF.into();
     }
     if inner.fd_table[fd].is_none() {
-        return -1;
+        return Errno::EBADF.into();
     }
     inner.fd_table[fd].take();
     0
@@ -82,27 +96,82 @@ pub fn sys_dup(fd: usize) -> isize {
     let task = current_task().unwrap();
     let mut inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Errno::EBADF.into();
     }
     if inner.fd_table[fd].is_none() {
-        return -1;
+        return Errno::EBADF.into();
     }
-    let new_fd = inner.alloc_fd();
+    let Some(new_fd) = inner.alloc_fd() else {
+        return Errno::EMFILE.into();
+    };
     inner.fd_table[new_fd] = Some(Arc::clone(inner.fd_table[fd].as_ref().unwrap()));
     new_fd as isize
 }
 
+/// Get the foreground process group of the console
+const TIOCGPGRP: usize = 0x540f;
+/// Set the foreground process group of the console
+const TIOCSPGRP: usize = 0x5410;
+
+/// The console understands ioctl for its foreground process group, perf
+/// events to enable, disable and reset them
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let sid = inner.sid;
+    let file = match inner.fd_table.get(fd) {
+        Some(Some(file)) => file.clone(),
+        _ => return Errno::EBADF.into(),
+    };
+    drop(inner);
+    if let Some(event) = file.as_any().downcast_ref::<PerfEvent>() {
+        return syscall_ret(event.ioctl(request).map(|_| 0));
+    }
+    if !file.as_any().is::<Stdin>() && !file.as_any().is::<Stdout>() {
+        return Errno::ENOTTY.into();
+    }
+    match request {
+        TIOCGPGRP => match copy_to_user(arg as *mut u32, &(foreground_pgrp() as u32)) {
+            Ok(()) => 0,
+            Err(errno) => errno.into(),
+        },
+        TIOCSPGRP => {
+            let pgid = match copy_from_user(arg as *const u32) {
+                Ok(pgid) => pgid as usize,
+                Err(errno) => return errno.into(),
+            };
+            if !pgid2tasks(pgid)
+                .iter()
+                .any(|member| member.inner_exclusive_access().sid == sid)
+            {
+                return Errno::EPERM.into();
+            }
+            set_foreground_pgrp(pgid);
+            0
+        }
+        _ => Errno::EINVAL.into(),
+    }
+}
+
 pub fn sys_pipe(pipe: *mut usize) -> isize {
     let task = current_task().unwrap();
-    let token = current_user_token();
     let mut inner = task.inner_exclusive_access();
     let (pipe_read, pipe_write) = make_pipe();
-    let read_fd = inner.alloc_fd();
+    let Some(read_fd) = inner.alloc_fd() else {
+        return Errno::EMFILE.into();
+    };
     inner.fd_table[read_fd] = Some(pipe_read);
-    let write_fd = inner.alloc_fd();
+    let Some(write_fd) = inner.alloc_fd() else {
+        inner.fd_table[read_fd] = None;
+        return Errno::EMFILE.into();
+    };
     inner.fd_table[write_fd] = Some(pipe_write);
-    *translated_refmut(token, pipe) = read_fd;
-    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
+    let fds = [read_fd, write_fd];
+    if let Err(errno) = inner.memory_set.copy_to_user(pipe as *mut [usize; 2], &fds) {
+        inner.fd_table[read_fd] = None;
+        inner.fd_table[write_fd] = None;
+        return errno.into();
+    }
     0
 }
 
@@ -111,44 +180,76 @@ pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
     let inner = task.inner_exclusive_access();
 
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Errno::EBADF.into();
     }
     let file = match &inner.fd_table[fd] {
         Some(file) => file.clone(),
-        None => return -1,
+        None => return Errno::EBADF.into(),
     };
-    file.seek(offset, whence)
-        .map(|new_offset| new_offset as isize)
-        .unwrap_or(-1)
+    syscall_ret(file.seek(offset, whence))
 }
 
 pub fn sys_mkdir(path: *const u8) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if make_dir(path.as_str()) {
-        0
-    } else {
-        -1
-    }
+    let path = match copy_str_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno.into(),
+    };
+    syscall_ret(make_dir(path.as_str()).map(|_| 0))
 }
 
 pub fn sys_rmdir(path: *const u8) -> isize {
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if remove_dir(path.as_str()) {
-        0
-    } else {
-        -1
-    }
+    let path = match copy_str_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno.into(),
+    };
+    syscall_ret(remove_dir(path.as_str()).map(|_| 0))
 }
 
 pub fn sys_rename(old_path: *const u8, new_path: *const u8) -> isize {
-    let token = current_user_token();
-    let old_path = translated_str(token, old_path);
-    let new_path = translated_str(token, new_path);
-    if rename_file_or_dir(old_path.as_str(), new_path.as_str()) {
-        0
-    } else {
-        -1
+    let old_path = match copy_str_from_user(old_path) {
+        Ok(old_path) => old_path,
+        Err(errno) => return errno.into(),
+    };
+    let new_path = match copy_str_from_user(new_path) {
+        Ok(new_path) => new_path,
+        Err(errno) => return errno.into(),
+    };
+    syscall_ret(rename_file_or_dir(old_path.as_str(), new_path.as_str()).map(|_| 0))
+}
+/// Let the fd of a perf event be closed on exec; we keep fds across exec
+/// anyway, so it changes nothing
+const PERF_FLAG_FD_CLOEXEC: usize = 8;
+
+/// Open a counter of hardware cycles or instructions as Linux
+/// perf_event_open does, for ourselves only: `pid` is 0 or our pid,
+/// `cpu` and `group_fd` are -1. Reading the fd gives the count as a u64.
+pub fn sys_perf_event_open(
+    attr: *const PerfEventAttr,
+    pid: isize,
+    cpu: isize,
+    group_fd: isize,
+    flags: usize,
+) -> isize {
+    let attr = match copy_from_user(attr) {
+        Ok(attr) => attr,
+        Err(errno) => return errno.into(),
+    };
+    let task = current_task().unwrap();
+    if (pid != 0 && pid as usize != task.getpid())
+        || cpu != -1
+        || group_fd != -1
+        || flags & !PERF_FLAG_FD_CLOEXEC != 0
+    {
+        return Errno::EINVAL.into();
     }
-}
\ No newline at end of file
+    let event = match open_event(&attr) {
+        Ok(event) => event,
+        Err(errno) => return errno.into(),
+    };
+    let mut inner = task.inner_exclusive_access();
+    let Some(fd) = inner.alloc_fd() else {
+        return Errno::EMFILE.into();
+    };
+    inner.fd_table[fd] = Some(event);
+    fd as isize
+}
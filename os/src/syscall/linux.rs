@@ -0,0 +1,1206 @@
+//! Linux syscalls for programs not linked against our user library, such
+//! as static musl binaries
+//!
+//! Numbers and structures follow the asm-generic ABI of riscv64. Calls that
+//! already match ours go straight to the native implementation; the rest
+//! translate flags and layouts first. There are no threads, no
+//! close-on-exec and no working directory but the root.
+use super::strace::Arg::{self, *};
+use super::*;
+use crate::config::{CLOCK_FREQ, MMAP_TOP, PAGE_SIZE};
+use crate::errno::{syscall_ret, SysResult};
+use crate::fs::{
+    File, FileKind, OSInode, OpenFlags, Stat, Stdin, Stdout, delete_file, make_dir, make_pipe,
+    open_file, remove_dir, rename_file_or_dir,
+};
+use crate::mm::{MapPermission, VirtAddr, frames_free};
+use crate::task::{
+    add_task, block_current_until, copy_from_user, copy_str_from_user, copy_to_user,
+    suspend_current_and_run_next, user_buffer, SignalFlags, RLIMIT_AS, RLIMIT_NOFILE, RLIM_NLIMITS,
+};
+use crate::timer::{add_wakeup_timer, get_time};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const SYS_GETCWD: usize = 17;
+const SYS_DUP: usize = 23;
+const SYS_DUP3: usize = 24;
+const SYS_FCNTL: usize = 25;
+const SYS_IOCTL: usize = 29;
+const SYS_MKDIRAT: usize = 34;
+const SYS_UNLINKAT: usize = 35;
+const SYS_RENAMEAT: usize = 38;
+const SYS_FACCESSAT: usize = 48;
+const SYS_OPENAT: usize = 56;
+const SYS_CLOSE: usize = 57;
+const SYS_PIPE2: usize = 59;
+const SYS_GETDENTS64: usize = 61;
+const SYS_LSEEK: usize = 62;
+const SYS_READ: usize = 63;
+const SYS_WRITE: usize = 64;
+const SYS_READV: usize = 65;
+const SYS_WRITEV: usize = 66;
+const SYS_NEWFSTATAT: usize = 79;
+const SYS_FSTAT: usize = 80;
+const SYS_EXIT: usize = 93;
+const SYS_EXIT_GROUP: usize = 94;
+const SYS_SET_TID_ADDRESS: usize = 96;
+const SYS_NANOSLEEP: usize = 101;
+const SYS_CLOCK_GETTIME: usize = 113;
+const SYS_SYSLOG: usize = 116;
+const SYS_SCHED_YIELD: usize = 124;
+const SYS_KILL: usize = 129;
+const SYS_TKILL: usize = 130;
+const SYS_TGKILL: usize = 131;
+const SYS_SIGALTSTACK: usize = 132;
+const SYS_RT_SIGACTION: usize = 134;
+const SYS_RT_SIGPROCMASK: usize = 135;
+const SYS_RT_SIGRETURN: usize = 139;
+const SYS_SETGID: usize = 144;
+const SYS_SETUID: usize = 146;
+const SYS_TIMES: usize = 153;
+const SYS_SETPGID: usize = 154;
+const SYS_GETPGID: usize = 155;
+const SYS_SETSID: usize = 157;
+const SYS_UNAME: usize = 160;
+const SYS_GETRLIMIT: usize = 163;
+const SYS_SETRLIMIT: usize = 164;
+const SYS_GETRUSAGE: usize = 165;
+const SYS_GETPID: usize = 172;
+const SYS_GETPPID: usize = 173;
+const SYS_GETUID: usize = 174;
+const SYS_GETEUID: usize = 175;
+const SYS_GETGID: usize = 176;
+const SYS_GETEGID: usize = 177;
+const SYS_GETTID: usize = 178;
+const SYS_BRK: usize = 214;
+const SYS_MUNMAP: usize = 215;
+const SYS_CLONE: usize = 220;
+const SYS_EXECVE: usize = 221;
+const SYS_MMAP: usize = 222;
+const SYS_MPROTECT: usize = 226;
+const SYS_MADVISE: usize = 233;
+const SYS_PERF_EVENT_OPEN: usize = 241;
+const SYS_WAIT4: usize = 260;
+const SYS_PRLIMIT64: usize = 261;
+const SYS_RENAMEAT2: usize = 276;
+const SYS_GETRANDOM: usize = 278;
+
+pub fn dispatch(syscall_id: usize, args: [usize; 6]) -> isize {
+    match syscall_id {
+        SYS_GETCWD => sys_getcwd(args[0] as *mut u8, args[1]),
+        SYS_DUP => sys_dup(args[0]),
+        SYS_DUP3 => sys_dup3(args[0], args[1], args[2] as u32),
+        SYS_FCNTL => sys_fcntl(args[0], args[1], args[2]),
+        SYS_IOCTL => sys_linux_ioctl(args[0], args[1], args[2]),
+        SYS_MKDIRAT => sys_mkdirat(args[0] as isize, args[1] as *const u8),
+        SYS_UNLINKAT => sys_unlinkat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+        SYS_RENAMEAT => sys_renameat2(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as isize,
+            args[3] as *const u8,
+            0,
+        ),
+        SYS_FACCESSAT => sys_faccessat(args[0] as isize, args[1] as *const u8),
+        SYS_OPENAT => sys_openat(args[0] as isize, args[1] as *const u8, args[2] as u32),
+        SYS_CLOSE => sys_close(args[0]),
+        SYS_PIPE2 => sys_pipe2(args[0] as *mut [i32; 2], args[1] as u32),
+        SYS_GETDENTS64 => sys_getdents64(args[0], args[1] as *mut u8, args[2]),
+        SYS_LSEEK => sys_lseek(args[0], args[1] as isize, args[2]),
+        SYS_READ => sys_read(args[0], args[1] as *const u8, args[2]),
+        SYS_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
+        SYS_READV => sys_readv(args[0], args[1] as *const IoVec, args[2]),
+        SYS_WRITEV => sys_writev(args[0], args[1] as *const IoVec, args[2]),
+        SYS_NEWFSTATAT => sys_newfstatat(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as *mut LinuxStat,
+            args[3] as u32,
+        ),
+        SYS_FSTAT => sys_fstat(args[0], args[1] as *mut LinuxStat),
+        SYS_EXIT | SYS_EXIT_GROUP => {
+            // the parent only ever sees the low 8 bits
+            sys_exit((args[0] & 0xff) as i32)
+        }
+        // there is one thread per process, its tid is the pid
+        SYS_SET_TID_ADDRESS | SYS_GETTID => sys_getpid(),
+        SYS_NANOSLEEP => sys_nanosleep(args[0] as *const TimeSpec, args[1] as *mut TimeSpec),
+        SYS_CLOCK_GETTIME => sys_clock_gettime(args[0], args[1] as *mut TimeSpec),
+        SYS_SYSLOG => sys_syslog(args[0], args[1] as *mut u8, args[2]),
+        SYS_SCHED_YIELD => sys_yield(),
+        SYS_KILL => sys_kill(args[0] as isize, args[1] as i32),
+        SYS_TKILL => sys_tkill(args[0] as isize, args[1] as i32),
+        SYS_TGKILL => sys_tkill(args[1] as isize, args[2] as i32),
+        SYS_SIGALTSTACK => sys_sigaltstack(
+            args[0] as *const SignalStack,
+            args[1] as *mut SignalStack,
+        ),
+        SYS_RT_SIGACTION => sys_rt_sigaction(
+            args[0],
+            args[1] as *const KSigAction,
+            args[2] as *mut KSigAction,
+        ),
+        SYS_RT_SIGPROCMASK => {
+            sys_rt_sigprocmask(args[0], args[1] as *const u64, args[2] as *mut u64)
+        }
+        SYS_RT_SIGRETURN => sys_sigreturn(),
+        SYS_SETGID => sys_setgid(args[0] as u32),
+        SYS_SETUID => sys_setuid(args[0] as u32),
+        SYS_TIMES => sys_times(args[0] as *mut Tms),
+        SYS_SETPGID => sys_setpgid(args[0], args[1]),
+        SYS_GETPGID => sys_getpgid(args[0]),
+        SYS_SETSID => sys_setsid(),
+        SYS_UNAME => sys_uname(args[0] as *mut UtsName),
+        SYS_GETRLIMIT => sys_getrlimit(args[0], args[1] as *mut RLimit),
+        SYS_SETRLIMIT => sys_setrlimit(args[0], args[1] as *const RLimit),
+        SYS_GETRUSAGE => sys_getrusage(args[0] as isize, args[1] as *mut RUsage),
+        SYS_GETPID => sys_getpid(),
+        SYS_GETPPID => sys_getppid(),
+        SYS_GETUID => sys_getuid(),
+        SYS_GETEUID => sys_geteuid(),
+        SYS_GETGID => sys_getgid(),
+        SYS_GETEGID => sys_getegid(),
+        SYS_BRK => sys_brk(args[0]),
+        SYS_MUNMAP => sys_munmap(args[0], args[1]),
+        SYS_CLONE => sys_clone(args[0], args[1]),
+        SYS_EXECVE => sys_exec(
+            args[0] as *const u8,
+            args[1] as *const usize,
+            args[2] as *const usize,
+        ),
+        SYS_MMAP => sys_mmap(args[0], args[1], args[2], args[3], args[4], args[5]),
+        SYS_MPROTECT => sys_mprotect(args[0], args[1], args[2]),
+        // advice only, ignoring it is always allowed
+        SYS_MADVISE => 0,
+        SYS_PERF_EVENT_OPEN => sys_perf_event_open(
+            args[0] as *const PerfEventAttr,
+            args[1] as isize,
+            args[2] as isize,
+            args[3] as isize,
+            args[4],
+        ),
+        SYS_WAIT4 => sys_wait4(args[0] as isize, args[1] as *mut i32, args[2]),
+        SYS_PRLIMIT64 => sys_prlimit64(
+            args[0],
+            args[1],
+            args[2] as *const RLimit,
+            args[3] as *mut RLimit,
+        ),
+        SYS_RENAMEAT2 => sys_renameat2(
+            args[0] as isize,
+            args[1] as *const u8,
+            args[2] as isize,
+            args[3] as *const u8,
+            args[4] as u32,
+        ),
+        SYS_GETRANDOM => sys_getrandom(args[0] as *mut u8, args[1]),
+        _ => Errno::ENOSYS.into(),
+    }
+}
+
+/// Name and argument kinds of Linux syscall `syscall_id`, for strace
+pub(super) fn signature(syscall_id: usize) -> Option<(&'static str, &'static [Arg])> {
+    let signature: (&str, &[Arg]) = match syscall_id {
+        SYS_GETCWD => ("getcwd", &[Hex, Int]),
+        SYS_DUP => ("dup", &[Int]),
+        SYS_DUP3 => ("dup3", &[Int, Int, Hex]),
+        SYS_FCNTL => ("fcntl", &[Int, Int, Hex]),
+        SYS_IOCTL => ("ioctl", &[Int, Hex, Hex]),
+        SYS_MKDIRAT => ("mkdirat", &[Int, Str, Hex]),
+        SYS_UNLINKAT => ("unlinkat", &[Int, Str, Hex]),
+        SYS_RENAMEAT => ("renameat", &[Int, Str, Int, Str]),
+        SYS_FACCESSAT => ("faccessat", &[Int, Str, Hex]),
+        SYS_OPENAT => ("openat", &[Int, Str, Hex, Hex]),
+        SYS_CLOSE => ("close", &[Int]),
+        SYS_PIPE2 => ("pipe2", &[Hex, Hex]),
+        SYS_GETDENTS64 => ("getdents64", &[Int, Hex, Int]),
+        SYS_LSEEK => ("lseek", &[Int, Int, Int]),
+        SYS_READ => ("read", &[Int, Hex, Int]),
+        SYS_WRITE => ("write", &[Int, Hex, Int]),
+        SYS_READV => ("readv", &[Int, Hex, Int]),
+        SYS_WRITEV => ("writev", &[Int, Hex, Int]),
+        SYS_NEWFSTATAT => ("newfstatat", &[Int, Str, Hex, Hex]),
+        SYS_FSTAT => ("fstat", &[Int, Hex]),
+        SYS_EXIT => ("exit", &[Int]),
+        SYS_EXIT_GROUP => ("exit_group", &[Int]),
+        SYS_SET_TID_ADDRESS => ("set_tid_address", &[Hex]),
+        SYS_NANOSLEEP => ("nanosleep", &[Hex, Hex]),
+        SYS_CLOCK_GETTIME => ("clock_gettime", &[Int, Hex]),
+        SYS_SYSLOG => ("syslog", &[Int, Hex, Int]),
+        SYS_SCHED_YIELD => ("sched_yield", &[]),
+        SYS_KILL => ("kill", &[Int, Int]),
+        SYS_TKILL => ("tkill", &[Int, Int]),
+        SYS_TGKILL => ("tgkill", &[Int, Int, Int]),
+        SYS_SIGALTSTACK => ("sigaltstack", &[Hex, Hex]),
+        SYS_RT_SIGACTION => ("rt_sigaction", &[Int, Hex, Hex, Int]),
+        SYS_RT_SIGPROCMASK => ("rt_sigprocmask", &[Int, Hex, Hex, Int]),
+        SYS_RT_SIGRETURN => ("rt_sigreturn", &[]),
+        SYS_SETGID => ("setgid", &[Int]),
+        SYS_SETUID => ("setuid", &[Int]),
+        SYS_TIMES => ("times", &[Hex]),
+        SYS_SETPGID => ("setpgid", &[Int, Int]),
+        SYS_GETPGID => ("getpgid", &[Int]),
+        SYS_SETSID => ("setsid", &[]),
+        SYS_UNAME => ("uname", &[Hex]),
+        SYS_GETRLIMIT => ("getrlimit", &[Int, Hex]),
+        SYS_SETRLIMIT => ("setrlimit", &[Int, Hex]),
+        SYS_GETRUSAGE => ("getrusage", &[Int, Hex]),
+        SYS_GETPID => ("getpid", &[]),
+        SYS_GETPPID => ("getppid", &[]),
+        SYS_GETUID => ("getuid", &[]),
+        SYS_GETEUID => ("geteuid", &[]),
+        SYS_GETGID => ("getgid", &[]),
+        SYS_GETEGID => ("getegid", &[]),
+        SYS_GETTID => ("gettid", &[]),
+        SYS_BRK => ("brk", &[Hex]),
+        SYS_MUNMAP => ("munmap", &[Hex, Int]),
+        SYS_CLONE => ("clone", &[Hex, Hex, Hex, Hex, Hex]),
+        SYS_EXECVE => ("execve", &[Str, Hex, Hex]),
+        SYS_MMAP => ("mmap", &[Hex, Int, Hex, Hex, Int, Hex]),
+        SYS_MPROTECT => ("mprotect", &[Hex, Int, Hex]),
+        SYS_MADVISE => ("madvise", &[Hex, Int, Int]),
+        SYS_PERF_EVENT_OPEN => ("perf_event_open", &[Hex, Int, Int, Int, Hex]),
+        SYS_WAIT4 => ("wait4", &[Int, Hex, Hex, Hex]),
+        SYS_PRLIMIT64 => ("prlimit64", &[Int, Int, Hex, Hex]),
+        SYS_RENAMEAT2 => ("renameat2", &[Int, Str, Int, Str, Hex]),
+        SYS_GETRANDOM => ("getrandom", &[Hex, Int, Hex]),
+        _ => return None,
+    };
+    Some(signature)
+}
+
+/// dirfd of the *at calls standing for the working directory
+const AT_FDCWD: isize = -100;
+/// unlinkat: remove a directory instead
+const AT_REMOVEDIR: u32 = 0x200;
+/// newfstatat: an empty path means `dirfd` itself
+const AT_EMPTY_PATH: u32 = 0x1000;
+
+/// Open file behind `fd`
+fn fd_file(fd: usize) -> SysResult<Arc<dyn File + Send + Sync>> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    match inner.fd_table.get(fd) {
+        Some(Some(file)) => Ok(file.clone()),
+        _ => Err(Errno::EBADF),
+    }
+}
+
+/// Give `file` the lowest free fd
+fn install_fd(file: Arc<dyn File + Send + Sync>) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let Some(fd) = inner.alloc_fd() else {
+        return Errno::EMFILE.into();
+    };
+    inner.fd_table[fd] = Some(file);
+    fd as isize
+}
+
+/// Read the path an *at call names relative to `dirfd`. The working
+/// directory is the root; directory fds do not remember their path, so
+/// other fds only go with absolute paths.
+fn at_path(dirfd: isize, path: *const u8) -> SysResult<String> {
+    let path = copy_str_from_user(path)?;
+    check_at_path(dirfd, &path)?;
+    Ok(path)
+}
+
+fn check_at_path(dirfd: isize, path: &str) -> SysResult<()> {
+    if path.is_empty() {
+        Err(Errno::ENOENT)
+    } else if dirfd != AT_FDCWD && !path.starts_with('/') {
+        Err(Errno::EINVAL)
+    } else {
+        Ok(())
+    }
+}
+
+fn sys_getcwd(buf: *mut u8, size: usize) -> isize {
+    const CWD: &[u8; 2] = b"/\0";
+    if size < CWD.len() {
+        return Errno::EINVAL.into();
+    }
+    match copy_to_user(buf as *mut [u8; 2], CWD) {
+        Ok(()) => CWD.len() as isize,
+        Err(errno) => errno.into(),
+    }
+}
+
+/// Flag of dup3, pipe2 and openat we accept and ignore, see the module docs
+const O_CLOEXEC: u32 = 0x80000;
+
+fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    if flags & !O_CLOEXEC != 0 || old_fd == new_fd {
+        return Errno::EINVAL.into();
+    }
+    let file = match fd_file(old_fd) {
+        Ok(file) => file,
+        Err(errno) => return errno.into(),
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if new_fd >= inner.rlimits[RLIMIT_NOFILE].cur {
+        return Errno::EBADF.into();
+    }
+    if inner.fd_table.len() <= new_fd {
+        inner.fd_table.resize(new_fd + 1, None);
+    }
+    inner.fd_table[new_fd] = Some(file);
+    new_fd as isize
+}
+
+const F_DUPFD: usize = 0;
+const F_GETFD: usize = 1;
+const F_SETFD: usize = 2;
+const F_GETFL: usize = 3;
+const F_SETFL: usize = 4;
+const F_DUPFD_CLOEXEC: usize = 1030;
+
+const O_WRONLY: u32 = 0x1;
+const O_RDWR: u32 = 0x2;
+const O_CREAT: u32 = 0x40;
+const O_EXCL: u32 = 0x80;
+const O_TRUNC: u32 = 0x200;
+const O_APPEND: u32 = 0x400;
+const O_DIRECTORY: u32 = 0x10000;
+
+fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    let file = match fd_file(fd) {
+        Ok(file) => file,
+        Err(errno) => return errno.into(),
+    };
+    match cmd {
+        F_DUPFD | F_DUPFD_CLOEXEC => {
+            let task = current_task().unwrap();
+            let mut inner = task.inner_exclusive_access();
+            let limit = inner.rlimits[RLIMIT_NOFILE].cur;
+            let Some(new_fd) =
+                (arg..limit).find(|fd| inner.fd_table.get(*fd).is_none_or(|file| file.is_none()))
+            else {
+                return Errno::EMFILE.into();
+            };
+            if inner.fd_table.len() <= new_fd {
+                inner.fd_table.resize(new_fd + 1, None);
+            }
+            inner.fd_table[new_fd] = Some(file);
+            new_fd as isize
+        }
+        // no fd is ever close-on-exec, and status flags cannot change
+        F_GETFD | F_SETFD | F_SETFL => 0,
+        F_GETFL => match (file.readable(), file.writable()) {
+            (true, true) => O_RDWR as isize,
+            (false, true) => O_WRONLY as isize,
+            _ => 0,
+        },
+        _ => Errno::EINVAL.into(),
+    }
+}
+
+/// Get the size of the terminal
+const TIOCGWINSZ: usize = 0x5413;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct WinSize {
+    row: u16,
+    col: u16,
+    xpixel: u16,
+    ypixel: u16,
+}
+
+/// The console reports the size of a classic terminal, every other
+/// request is ours
+fn sys_linux_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    if request != TIOCGWINSZ {
+        return sys_ioctl(fd, request, arg);
+    }
+    let file = match fd_file(fd) {
+        Ok(file) => file,
+        Err(errno) => return errno.into(),
+    };
+    if !file.as_any().is::<Stdin>() && !file.as_any().is::<Stdout>() {
+        return Errno::ENOTTY.into();
+    }
+    let size = WinSize {
+        row: 24,
+        col: 80,
+        xpixel: 0,
+        ypixel: 0,
+    };
+    match copy_to_user(arg as *mut WinSize, &size) {
+        Ok(()) => 0,
+        Err(errno) => errno.into(),
+    }
+}
+
+fn sys_mkdirat(dirfd: isize, path: *const u8) -> isize {
+    match at_path(dirfd, path) {
+        Ok(path) => syscall_ret(make_dir(&path).map(|_| 0)),
+        Err(errno) => errno.into(),
+    }
+}
+
+fn sys_unlinkat(dirfd: isize, path: *const u8, flags: u32) -> isize {
+    if flags & !AT_REMOVEDIR != 0 {
+        return Errno::EINVAL.into();
+    }
+    let path = match at_path(dirfd, path) {
+        Ok(path) => path,
+        Err(errno) => return errno.into(),
+    };
+    let result = if flags & AT_REMOVEDIR != 0 {
+        remove_dir(&path)
+    } else {
+        delete_file(&path)
+    };
+    syscall_ret(result.map(|_| 0))
+}
+
+/// Only a plain rename that does not replace the target, as ours
+fn sys_renameat2(
+    old_dirfd: isize,
+    old_path: *const u8,
+    new_dirfd: isize,
+    new_path: *const u8,
+    flags: u32,
+) -> isize {
+    if flags != 0 {
+        return Errno::EINVAL.into();
+    }
+    match (at_path(old_dirfd, old_path), at_path(new_dirfd, new_path)) {
+        (Ok(old_path), Ok(new_path)) => {
+            syscall_ret(rename_file_or_dir(&old_path, &new_path).map(|_| 0))
+        }
+        (Err(errno), _) | (_, Err(errno)) => errno.into(),
+    }
+}
+
+/// Files have no permissions, so access is granted to whatever exists
+fn sys_faccessat(dirfd: isize, path: *const u8) -> isize {
+    match at_path(dirfd, path) {
+        Ok(path) => syscall_ret(open_file(&path, OpenFlags::RDONLY).map(|_| 0)),
+        Err(errno) => errno.into(),
+    }
+}
+
+/// Open `path` with Linux `flags`. Ours truncate an existing file on
+/// CREATE, so O_CREAT only creates missing files. O_APPEND starts at the
+/// end but does not seek before each write.
+fn sys_openat(dirfd: isize, path: *const u8, flags: u32) -> isize {
+    let path = match at_path(dirfd, path) {
+        Ok(path) => path,
+        Err(errno) => return errno.into(),
+    };
+    // looked up before anything is truncated
+    if flags & O_CREAT != 0
+        && flags & O_EXCL != 0
+        && open_file(&path, OpenFlags::RDONLY).is_ok()
+    {
+        return Errno::EEXIST.into();
+    }
+    let mut native = OpenFlags::from_bits_truncate(flags & (O_WRONLY | O_RDWR));
+    if flags & O_TRUNC != 0 {
+        native |= OpenFlags::TRUNC;
+    }
+    let file = match open_file(&path, native) {
+        Ok(file) => file,
+        Err(Errno::ENOENT) if flags & O_CREAT != 0 => {
+            match open_file(&path, native | OpenFlags::CREATE) {
+                Ok(file) => file,
+                Err(errno) => return errno.into(),
+            }
+        }
+        Err(errno) => return errno.into(),
+    };
+    if flags & O_DIRECTORY != 0 && file.stat().kind != FileKind::Directory {
+        return Errno::ENOTDIR.into();
+    }
+    if flags & O_APPEND != 0 {
+        // SEEK_END
+        let _ = file.seek(0, 2);
+    }
+    install_fd(file)
+}
+
+fn sys_pipe2(pipe: *mut [i32; 2], flags: u32) -> isize {
+    if flags & !O_CLOEXEC != 0 {
+        return Errno::EINVAL.into();
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let (pipe_read, pipe_write) = make_pipe();
+    let Some(read_fd) = inner.alloc_fd() else {
+        return Errno::EMFILE.into();
+    };
+    inner.fd_table[read_fd] = Some(pipe_read);
+    let Some(write_fd) = inner.alloc_fd() else {
+        inner.fd_table[read_fd] = None;
+        return Errno::EMFILE.into();
+    };
+    inner.fd_table[write_fd] = Some(pipe_write);
+    let fds = [read_fd as i32, write_fd as i32];
+    if let Err(errno) = inner.memory_set.copy_to_user(pipe, &fds) {
+        inner.fd_table[read_fd] = None;
+        inner.fd_table[write_fd] = None;
+        return errno.into();
+    }
+    0
+}
+
+/// `struct linux_dirent64` up to the name: ino, off, reclen and type
+const DIRENT_HEADER: usize = 8 + 8 + 2 + 1;
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+
+/// Fill `buf` with as many `struct linux_dirent64` as fit, returns the
+/// bytes used, 0 at the end of the directory
+fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let file = match fd_file(fd) {
+        Ok(file) => file,
+        Err(errno) => return errno.into(),
+    };
+    let Some(dir) = file.as_any().downcast_ref::<OSInode>() else {
+        return Errno::ENOTDIR.into();
+    };
+    let mut dirents: Vec<u8> = Vec::new();
+    let mut full = false;
+    let result = dir.read_dir(|ino, name, is_dir| {
+        let reclen = (DIRENT_HEADER + name.len() + 1).next_multiple_of(8);
+        if dirents.len() + reclen > len {
+            full = true;
+            return false;
+        }
+        let next = dirents.len() + reclen;
+        dirents.extend_from_slice(&(ino as u64).to_le_bytes());
+        dirents.extend_from_slice(&(next as i64).to_le_bytes());
+        dirents.extend_from_slice(&(reclen as u16).to_le_bytes());
+        dirents.push(if is_dir { DT_DIR } else { DT_REG });
+        dirents.extend_from_slice(name.as_bytes());
+        dirents.resize(next, 0);
+        true
+    });
+    if let Err(errno) = result {
+        return errno.into();
+    }
+    // not even one entry fits
+    if dirents.is_empty() && full {
+        return Errno::EINVAL.into();
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    match inner.memory_set.write_user(buf as usize, &dirents) {
+        Ok(()) => dirents.len() as isize,
+        Err(errno) => errno.into(),
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct IoVec {
+    base: usize,
+    len: usize,
+}
+
+/// Most buffers readv and writev take
+const IOV_MAX: usize = 1024;
+
+/// Run `rw` on each buffer of `iov` in turn, stopping at the first short
+/// transfer. An error after some bytes went through returns the count.
+fn vectored_rw(
+    fd: usize,
+    iov: *const IoVec,
+    iovcnt: usize,
+    rw: fn(usize, *const u8, usize) -> isize,
+) -> isize {
+    if iovcnt > IOV_MAX {
+        return Errno::EINVAL.into();
+    }
+    let mut total = 0;
+    for i in 0..iovcnt {
+        let vec = match copy_from_user(iov.wrapping_add(i)) {
+            Ok(vec) => vec,
+            Err(errno) => return errno.into(),
+        };
+        let ret = rw(fd, vec.base as *const u8, vec.len);
+        if ret < 0 {
+            return if total == 0 { ret } else { total as isize };
+        }
+        total += ret as usize;
+        if (ret as usize) < vec.len {
+            break;
+        }
+    }
+    total as isize
+}
+
+fn sys_readv(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    vectored_rw(fd, iov, iovcnt, sys_read)
+}
+
+fn sys_writev(fd: usize, iov: *const IoVec, iovcnt: usize) -> isize {
+    vectored_rw(fd, iov, iovcnt, sys_write)
+}
+
+const S_IFIFO: u32 = 0o010000;
+const S_IFCHR: u32 = 0o020000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+/// asm-generic `struct stat`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct LinuxStat {
+    dev: u64,
+    ino: u64,
+    mode: u32,
+    nlink: u32,
+    uid: u32,
+    gid: u32,
+    rdev: u64,
+    pad1: u64,
+    size: i64,
+    blksize: i32,
+    pad2: i32,
+    blocks: i64,
+    atime: i64,
+    atime_nsec: u64,
+    mtime: i64,
+    mtime_nsec: u64,
+    ctime: i64,
+    ctime_nsec: u64,
+    unused: [u32; 2],
+}
+
+impl From<Stat> for LinuxStat {
+    /// Everything is owned by root and open to all, times are unknown
+    fn from(stat: Stat) -> Self {
+        let mode = match stat.kind {
+            FileKind::Regular => S_IFREG | 0o755,
+            FileKind::Directory => S_IFDIR | 0o755,
+            FileKind::CharDevice => S_IFCHR | 0o666,
+            FileKind::Fifo => S_IFIFO | 0o666,
+        };
+        Self {
+            ino: stat.ino,
+            mode,
+            nlink: 1,
+            size: stat.size as i64,
+            blksize: 512,
+            blocks: stat.size.div_ceil(512) as i64,
+            ..Self::default()
+        }
+    }
+}
+
+fn sys_fstat(fd: usize, statbuf: *mut LinuxStat) -> isize {
+    let file = match fd_file(fd) {
+        Ok(file) => file,
+        Err(errno) => return errno.into(),
+    };
+    match copy_to_user(statbuf, &LinuxStat::from(file.stat())) {
+        Ok(()) => 0,
+        Err(errno) => errno.into(),
+    }
+}
+
+fn sys_newfstatat(dirfd: isize, path: *const u8, statbuf: *mut LinuxStat, flags: u32) -> isize {
+    let path = match copy_str_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno.into(),
+    };
+    if path.is_empty() && flags & AT_EMPTY_PATH != 0 && dirfd != AT_FDCWD {
+        return sys_fstat(dirfd as usize, statbuf);
+    }
+    if let Err(errno) = check_at_path(dirfd, &path) {
+        return errno.into();
+    }
+    let file = match open_file(&path, OpenFlags::RDONLY) {
+        Ok(file) => file,
+        Err(errno) => return errno.into(),
+    };
+    match copy_to_user(statbuf, &LinuxStat::from(file.stat())) {
+        Ok(()) => 0,
+        Err(errno) => errno.into(),
+    }
+}
+
+const NSEC_PER_SEC: usize = 1_000_000_000;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct TimeSpec {
+    sec: usize,
+    nsec: usize,
+}
+
+impl TimeSpec {
+    /// None if the ticks overflow
+    fn to_ticks(self) -> Option<usize> {
+        self.sec
+            .checked_mul(CLOCK_FREQ)?
+            .checked_add(self.nsec.checked_mul(CLOCK_FREQ)? / NSEC_PER_SEC)
+    }
+    fn from_ticks(ticks: usize) -> Self {
+        Self {
+            sec: ticks / CLOCK_FREQ,
+            nsec: ticks % CLOCK_FREQ * NSEC_PER_SEC / CLOCK_FREQ,
+        }
+    }
+}
+
+/// Sleep for `req`; a signal cuts it short with EINTR, leaving the time
+/// not slept in `rem` unless it is null
+fn sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec) -> isize {
+    let req = match copy_from_user(req) {
+        Ok(req) => req,
+        Err(errno) => return errno.into(),
+    };
+    if req.nsec >= NSEC_PER_SEC || (req.sec as isize) < 0 {
+        return Errno::EINVAL.into();
+    }
+    let pid = current_task().unwrap().getpid();
+    let Some(deadline) = req.to_ticks().and_then(|ticks| get_time().checked_add(ticks)) else {
+        return Errno::EINVAL.into();
+    };
+    add_wakeup_timer(deadline, pid);
+    let interrupted = block_current_until(|inner| {
+        if inner.killed.is_some() || !inner.deliverable_signals().is_empty() {
+            return Some(true);
+        }
+        (get_time() >= deadline).then_some(false)
+    });
+    if !interrupted {
+        return 0;
+    }
+    if !rem.is_null() {
+        let left = TimeSpec::from_ticks(deadline.saturating_sub(get_time()));
+        if let Err(errno) = copy_to_user(rem, &left) {
+            return errno.into();
+        }
+    }
+    Errno::EINTR.into()
+}
+
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+const CLOCK_MONOTONIC_RAW: usize = 4;
+const CLOCK_REALTIME_COARSE: usize = 5;
+const CLOCK_MONOTONIC_COARSE: usize = 6;
+const CLOCK_BOOTTIME: usize = 7;
+
+/// There is no wall clock, every clock counts from boot
+fn sys_clock_gettime(clock: usize, tp: *mut TimeSpec) -> isize {
+    match clock {
+        CLOCK_REALTIME | CLOCK_MONOTONIC | CLOCK_MONOTONIC_RAW | CLOCK_REALTIME_COARSE
+        | CLOCK_MONOTONIC_COARSE | CLOCK_BOOTTIME => {}
+        _ => return Errno::EINVAL.into(),
+    }
+    match copy_to_user(tp, &TimeSpec::from_ticks(get_time())) {
+        Ok(()) => 0,
+        Err(errno) => errno.into(),
+    }
+}
+
+/// Every process has a single thread, so a tid is a pid
+fn sys_tkill(tid: isize, signum: i32) -> isize {
+    if tid <= 0 {
+        return Errno::EINVAL.into();
+    }
+    sys_kill(tid, signum)
+}
+
+/// Signal sets number signal n as bit n - 1, ours as bit n
+fn set_from_linux(set: u64) -> SignalFlags {
+    SignalFlags::from_bits_truncate(set << 1)
+}
+
+fn set_to_linux(set: SignalFlags) -> u64 {
+    set.bits() >> 1
+}
+
+/// `struct sigaction` as the kernel takes it on riscv64, without a
+/// restorer
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct KSigAction {
+    handler: usize,
+    flags: usize,
+    mask: u64,
+}
+
+fn sys_rt_sigaction(signum: usize, action: *const KSigAction, old_action: *mut KSigAction) -> isize {
+    let Some(signal) = SignalFlags::from_signum(signum).filter(|_| signum != 0) else {
+        return Errno::EINVAL.into();
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let new_action = if action.is_null() {
+        None
+    } else {
+        if signal == SignalFlags::SIGKILL || signal == SignalFlags::SIGSTOP {
+            return Errno::EINVAL.into();
+        }
+        match inner.memory_set.copy_from_user(action) {
+            Ok(new_action) => Some(new_action),
+            Err(errno) => return errno.into(),
+        }
+    };
+    if !old_action.is_null() {
+        let prev_action = inner.signal_actions.table[signum];
+        let old = KSigAction {
+            handler: prev_action.handler,
+            flags: prev_action.flags as usize,
+            mask: set_to_linux(prev_action.mask),
+        };
+        if let Err(errno) = inner.memory_set.copy_to_user(old_action, &old) {
+            return errno.into();
+        }
+    }
+    if let Some(new_action) = new_action {
+        let new_action = SignalAction {
+            handler: new_action.handler,
+            mask: set_from_linux(new_action.mask),
+            flags: new_action.flags as u32,
+        };
+        inner.set_signal_action(signum, new_action);
+    }
+    0
+}
+
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+fn sys_rt_sigprocmask(how: usize, set: *const u64, old_set: *mut u64) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old_mask = inner.signal_mask;
+    let new_mask = if set.is_null() {
+        None
+    } else {
+        let set = match inner.memory_set.copy_from_user(set) {
+            Ok(set) => set_from_linux(set),
+            Err(errno) => return errno.into(),
+        };
+        match how {
+            SIG_BLOCK => Some(old_mask | set),
+            SIG_UNBLOCK => Some(old_mask - set),
+            SIG_SETMASK => Some(set),
+            _ => return Errno::EINVAL.into(),
+        }
+    };
+    if !old_set.is_null() {
+        if let Err(errno) = inner.memory_set.copy_to_user(old_set, &set_to_linux(old_mask)) {
+            return errno.into();
+        }
+    }
+    if let Some(mask) = new_mask {
+        // SIGKILL and SIGSTOP cannot be blocked
+        inner.signal_mask = mask - (SignalFlags::SIGKILL | SignalFlags::SIGSTOP);
+    }
+    0
+}
+
+/// `struct utsname`: sysname, nodename, release, version, machine and
+/// domainname
+type UtsName = [[u8; 65]; 6];
+
+fn sys_uname(buf: *mut UtsName) -> isize {
+    let mut uts: UtsName = [[0; 65]; 6];
+    let fields = ["ACore", "acore", "0.1.0", "#1", "riscv64", ""];
+    for (field, value) in uts.iter_mut().zip(fields) {
+        field[..value.len()].copy_from_slice(value.as_bytes());
+    }
+    match copy_to_user(buf, &uts) {
+        Ok(()) => 0,
+        Err(errno) => errno.into(),
+    }
+}
+
+/// 0 once the parent is gone
+fn sys_getppid() -> isize {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner
+        .parent
+        .as_ref()
+        .and_then(|parent| parent.upgrade())
+        .map_or(0, |parent| parent.getpid() as isize)
+}
+
+/// Move the end of the heap to `addr`. Returns the new end, or the old
+/// one if `addr` is 0 or the heap cannot move there.
+fn sys_brk(addr: usize) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let (bottom, brk) = (inner.heap_bottom, inner.program_brk);
+    if addr < bottom || addr > MMAP_TOP {
+        return brk as isize;
+    }
+    let old_end = VirtAddr::from(brk).ceil();
+    let new_end = VirtAddr::from(addr).ceil();
+    let resized = if new_end >= old_end {
+        let growth = (new_end.0 - old_end.0) * PAGE_SIZE;
+        inner.memory_set.user_size() + growth <= inner.rlimits[RLIMIT_AS].cur
+            && enough_frames(growth)
+            && inner.memory_set.range_free(old_end, new_end)
+            && inner.memory_set.append_to(bottom.into(), addr.into())
+    } else {
+        inner.memory_set.shrink_to(bottom.into(), addr.into())
+    };
+    if !resized {
+        return brk as isize;
+    }
+    inner.program_brk = addr;
+    addr as isize
+}
+
+/// Whether there are free frames for `len` bytes of memory mapped at
+/// once, and for the page tables they may need
+fn enough_frames(len: usize) -> bool {
+    let pages = len.div_ceil(PAGE_SIZE);
+    let tables = pages.div_ceil(512) + pages.div_ceil(512 * 512) + 1;
+    pages + tables <= frames_free()
+}
+
+const PROT_READ: usize = 0x1;
+const PROT_WRITE: usize = 0x2;
+const PROT_EXEC: usize = 0x4;
+const MAP_PRIVATE: usize = 0x2;
+const MAP_TYPE: usize = 0xf;
+const MAP_FIXED: usize = 0x10;
+const MAP_ANONYMOUS: usize = 0x20;
+
+/// Page permission for `prot`; writable pages are readable as well, as
+/// Sv39 has no write-only pages
+fn prot_to_perm(prot: usize) -> Option<MapPermission> {
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+        return None;
+    }
+    let mut perm = MapPermission::U;
+    if prot & (PROT_READ | PROT_WRITE) != 0 {
+        perm |= MapPermission::R;
+    }
+    if prot & PROT_WRITE != 0 {
+        perm |= MapPermission::W;
+    }
+    if prot & PROT_EXEC != 0 {
+        perm |= MapPermission::X;
+    }
+    Some(perm)
+}
+
+/// Private mappings only, anonymous or a copy of a file. Without
+/// MAP_FIXED the hint is ignored and mappings go top down from MMAP_TOP.
+fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    if len == 0
+        || len > MMAP_TOP
+        || addr % PAGE_SIZE != 0
+        || offset % PAGE_SIZE != 0
+        || flags & MAP_TYPE != MAP_PRIVATE
+    {
+        return Errno::EINVAL.into();
+    }
+    let Some(perm) = prot_to_perm(prot) else {
+        return Errno::EINVAL.into();
+    };
+    let data = if flags & MAP_ANONYMOUS != 0 {
+        Vec::new()
+    } else {
+        let file = match fd_file(fd) {
+            Ok(file) if file.readable() => file,
+            Ok(_) => return Errno::EBADF.into(),
+            Err(errno) => return errno.into(),
+        };
+        let Some(inode) = file.as_any().downcast_ref::<OSInode>() else {
+            return Errno::EINVAL.into();
+        };
+        // past the end of the file the pages stay zero
+        let size = inode.stat().size.saturating_sub(offset);
+        let mut data = vec![0u8; len.min(size)];
+        let read = inode.read_at(offset, &mut data);
+        data.truncate(read);
+        data
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.memory_set.user_size() + len > inner.rlimits[RLIMIT_AS].cur || !enough_frames(len)
+    {
+        return Errno::ENOMEM.into();
+    }
+    let start = if flags & MAP_FIXED != 0 {
+        if addr == 0 || addr.checked_add(len).is_none_or(|end| end > MMAP_TOP) {
+            return Errno::EINVAL.into();
+        }
+        let end = VirtAddr::from(addr + len).ceil();
+        inner.memory_set.unmap_range(VirtAddr::from(addr).floor(), end);
+        VirtAddr::from(addr)
+    } else {
+        match inner.memory_set.find_free_range(len) {
+            Some(start) => start,
+            None => return Errno::ENOMEM.into(),
+        }
+    };
+    let end = VirtAddr::from(start.0 + len);
+    inner
+        .memory_set
+        .insert_framed_area_with_data(start, end, perm, &data);
+    start.0 as isize
+}
+
+fn sys_munmap(addr: usize, len: usize) -> isize {
+    let Some(end) = addr.checked_add(len) else {
+        return Errno::EINVAL.into();
+    };
+    if len == 0 || addr % PAGE_SIZE != 0 {
+        return Errno::EINVAL.into();
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner
+        .memory_set
+        .unmap_range(VirtAddr::from(addr).floor(), VirtAddr::from(end).ceil());
+    0
+}
+
+/// Fails with ENOMEM if part of the range is not mapped
+fn sys_mprotect(addr: usize, len: usize, prot: usize) -> isize {
+    let Some(end) = addr.checked_add(len) else {
+        return Errno::ENOMEM.into();
+    };
+    if addr % PAGE_SIZE != 0 {
+        return Errno::EINVAL.into();
+    }
+    let Some(perm) = prot_to_perm(prot) else {
+        return Errno::EINVAL.into();
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let (start, end) = (VirtAddr::from(addr).floor(), VirtAddr::from(end).ceil());
+    if inner.memory_set.protect_range(start, end, perm) {
+        0
+    } else {
+        Errno::ENOMEM.into()
+    }
+}
+
+/// The exit signal in the low byte of the clone flags
+const CSIGNAL: usize = 0xff;
+const CLONE_VM: usize = 0x100;
+const CLONE_VFORK: usize = 0x4000;
+
+/// clone as fork does it: no threads and no shared memory, so only the
+/// exit signal may be given, or CLONE_VM|CLONE_VFORK as musl's vfork and
+/// posix_spawn give them. Such a child gets a copy of our memory instead
+/// and we do not wait for it to exec. The child starts on `stack` unless
+/// it is 0.
+fn sys_clone(flags: usize, stack: usize) -> isize {
+    let shared = flags & !CSIGNAL;
+    if shared != 0 && shared != CLONE_VM | CLONE_VFORK {
+        return Errno::EINVAL.into();
+    }
+    let current_task = current_task().unwrap();
+    if !current_task.inner_exclusive_access().may_add_child() {
+        return Errno::EAGAIN.into();
+    }
+    let new_task = current_task.fork();
+    let new_pid = new_task.getpid();
+    let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
+    trap_cx.x[10] = 0;
+    if stack != 0 {
+        trap_cx.x[2] = stack;
+    }
+    add_task(new_task);
+    new_pid as isize
+}
+
+/// Do not wait for a child to change state
+const WNOHANG: usize = 1;
+
+/// Wait status of a child that exited with `exit_code`, ours being -sig
+/// for a child killed by a signal
+fn wait_status(exit_code: i32) -> i32 {
+    if exit_code < 0 {
+        -exit_code
+    } else {
+        (exit_code & 0xff) << 8
+    }
+}
+
+/// waitpid that sleeps until a child has something to report. Resource
+/// usage is not reported.
+fn sys_wait4(pid: isize, status: *mut i32, options: usize) -> isize {
+    let eagain: isize = Errno::EAGAIN.into();
+    loop {
+        let ret = wait_child(pid, status, options, wait_status);
+        if ret != eagain {
+            return ret;
+        }
+        if options & WNOHANG != 0 {
+            return 0;
+        }
+        let task = current_task().unwrap();
+        let inner = task.inner_exclusive_access();
+        if inner.killed.is_some() || !inner.deliverable_signals().is_empty() {
+            return Errno::EINTR.into();
+        }
+        drop(inner);
+        drop(task);
+        suspend_current_and_run_next();
+    }
+}
+
+/// getrlimit and setrlimit in one, for the caller only
+fn sys_prlimit64(
+    pid: usize,
+    resource: usize,
+    new_limit: *const RLimit,
+    old_limit: *mut RLimit,
+) -> isize {
+    if pid != 0 && pid != current_task().unwrap().getpid() {
+        return Errno::EPERM.into();
+    }
+    if resource >= RLIM_NLIMITS {
+        return Errno::EINVAL.into();
+    }
+    if !old_limit.is_null() {
+        let ret = sys_getrlimit(resource, old_limit);
+        if ret < 0 {
+            return ret;
+        }
+    }
+    if !new_limit.is_null() {
+        return sys_setrlimit(resource, new_limit);
+    }
+    0
+}
+
+/// Fill `buf` from a xorshift generator seeded by the clock; good enough
+/// for hash seeds, not for keys
+fn sys_getrandom(buf: *mut u8, len: usize) -> isize {
+    let mut buffer = match user_buffer(buf, len, true) {
+        Ok(buffer) => buffer,
+        Err(errno) => return errno.into(),
+    };
+    let mut state = (get_time() as u64 ^ (buf as u64).rotate_left(32)) | 1;
+    for slice in buffer.buffers.iter_mut() {
+        for byte in slice.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *byte = (state >> 24) as u8;
+        }
+    }
+    len as isize
+}
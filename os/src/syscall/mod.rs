@@ -1,4 +1,5 @@
 const SYSCALL_DUP: usize = 24;
+const SYSCALL_IOCTL: usize = 29;
 const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_DELETE: usize = 58;
@@ -10,28 +11,80 @@ const SYSCALL_RENAME: usize = 82;
 const SYSCALL_MKDIR: usize = 83;
 const SYSCALL_RMDIR: usize = 84;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_GETITIMER: usize = 102;
+const SYSCALL_SETITIMER: usize = 103;
+const SYSCALL_SYSLOG: usize = 116;
+const SYSCALL_PTRACE: usize = 117;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGALTSTACK: usize = 132;
+const SYSCALL_SIGSUSPEND: usize = 133;
 const SYSCALL_SIGACTION: usize = 134;
 const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGTIMEDWAIT: usize = 137;
+const SYSCALL_SIGQUEUE: usize = 138;
 const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_SETGID: usize = 144;
+const SYSCALL_SETUID: usize = 146;
+const SYSCALL_TIMES: usize = 153;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_SETSID: usize = 157;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETUID: usize = 174;
+const SYSCALL_GETEUID: usize = 175;
+const SYSCALL_GETGID: usize = 176;
+const SYSCALL_GETEGID: usize = 177;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
+const SYSCALL_PERF_EVENT_OPEN: usize = 241;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_SPAWN: usize = 400;
+const SYSCALL_STRACE: usize = 401;
+const SYSCALL_PROFILE: usize = 402;
 
 mod fs;
+mod linux;
 mod process;
+mod strace;
 
 use fs::*;
 use process::*;
 
-use crate::task::SignalAction;
+use crate::errno::Errno;
+use crate::perf::PerfEventAttr;
+use crate::task::{
+    current_task, Personality, ProfSample, RLimit, RUsage, SignalAction, SignalInfo, SignalStack,
+    Tms,
+};
+use crate::timer::{ITimerVal, TimeVal};
 
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
+/// Run syscall `syscall_id` with the arguments the user passed in a0-a5,
+/// numbered as the personality of the running program has it
+pub fn syscall(syscall_id: usize, args: [usize; 6]) -> isize {
+    let (personality, strace) = {
+        let task = current_task().unwrap();
+        let inner = task.inner_exclusive_access();
+        (inner.personality, inner.strace)
+    };
+    let dispatch: fn(usize, [usize; 6]) -> isize = match personality {
+        Personality::Native => dispatch,
+        Personality::Linux => linux::dispatch,
+    };
+    if strace {
+        return strace::traced_syscall(personality, syscall_id, args, dispatch);
+    }
+    dispatch(syscall_id, args)
+}
+
+fn dispatch(syscall_id: usize, args: [usize; 6]) -> isize {
     match syscall_id {
         SYSCALL_DUP => sys_dup(args[0]),
+        SYSCALL_IOCTL => sys_ioctl(args[0], args[1], args[2]),
         SYSCALL_OPEN => sys_open(args[0] as *const u8, args[1] as u32),
         SYSCALL_CLOSE => sys_close(args[0]),
         SYSCALL_DELETE => sys_delete(args[0] as *const u8),
@@ -43,20 +96,71 @@ pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
         SYSCALL_MKDIR => sys_mkdir(args[0] as *const u8),
         SYSCALL_RMDIR => sys_rmdir(args[0] as *const u8),
         SYSCALL_EXIT => sys_exit(args[0] as i32),
+        SYSCALL_GETITIMER => sys_getitimer(args[0], args[1] as *mut ITimerVal),
+        SYSCALL_SETITIMER => sys_setitimer(
+            args[0],
+            args[1] as *const ITimerVal,
+            args[2] as *mut ITimerVal,
+        ),
+        SYSCALL_SYSLOG => sys_syslog(args[0], args[1] as *mut u8, args[2]),
+        SYSCALL_PTRACE => sys_ptrace(args[0], args[1], args[2], args[3]),
         SYSCALL_YIELD => sys_yield(),
-        SYSCALL_KILL => sys_kill(args[0], args[1] as i32),
+        SYSCALL_KILL => sys_kill(args[0] as isize, args[1] as i32),
+        SYSCALL_SIGALTSTACK => sys_sigaltstack(
+            args[0] as *const SignalStack,
+            args[1] as *mut SignalStack,
+        ),
+        SYSCALL_SIGSUSPEND => sys_sigsuspend(args[0] as u64),
         SYSCALL_SIGACTION => sys_sigaction(
             args[0] as i32,
             args[1] as *const SignalAction,
             args[2] as *mut SignalAction,
         ),
-        SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0] as u32),
+        SYSCALL_SIGPROCMASK => sys_sigprocmask(args[0] as u64),
+        SYSCALL_SIGTIMEDWAIT => sys_sigtimedwait(
+            args[0] as u64,
+            args[1] as *mut SignalInfo,
+            args[2] as *const TimeVal,
+        ),
+        SYSCALL_SIGQUEUE => sys_sigqueue(args[0], args[1], args[2]),
         SYSCALL_SIGRETURN => sys_sigreturn(),
+        SYSCALL_SETGID => sys_setgid(args[0] as u32),
+        SYSCALL_SETUID => sys_setuid(args[0] as u32),
+        SYSCALL_TIMES => sys_times(args[0] as *mut Tms),
+        SYSCALL_SETPGID => sys_setpgid(args[0], args[1]),
+        SYSCALL_GETPGID => sys_getpgid(args[0]),
+        SYSCALL_SETSID => sys_setsid(),
+        SYSCALL_GETRLIMIT => sys_getrlimit(args[0], args[1] as *mut RLimit),
+        SYSCALL_SETRLIMIT => sys_setrlimit(args[0], args[1] as *const RLimit),
+        SYSCALL_GETRUSAGE => sys_getrusage(args[0] as isize, args[1] as *mut RUsage),
         SYSCALL_GET_TIME => sys_get_time(),
         SYSCALL_GETPID => sys_getpid(),
+        SYSCALL_GETUID => sys_getuid(),
+        SYSCALL_GETEUID => sys_geteuid(),
+        SYSCALL_GETGID => sys_getgid(),
+        SYSCALL_GETEGID => sys_getegid(),
         SYSCALL_FORK => sys_fork(),
-        SYSCALL_EXEC => sys_exec(args[0] as *const u8, args[1] as *const usize),
-        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+        SYSCALL_EXEC => sys_exec(
+            args[0] as *const u8,
+            args[1] as *const usize,
+            args[2] as *const usize,
+        ),
+        SYSCALL_PERF_EVENT_OPEN => sys_perf_event_open(
+            args[0] as *const PerfEventAttr,
+            args[1] as isize,
+            args[2] as isize,
+            args[3] as isize,
+            args[4],
+        ),
+        SYSCALL_WAITPID => sys_waitpid(args[0] as isize, args[1] as *mut i32, args[2]),
+        SYSCALL_SPAWN => sys_spawn(
+            args[0] as *const u8,
+            args[1] as *const usize,
+            args[2] as *const usize,
+            args[3] as *const SpawnAttr,
+        ),
+        SYSCALL_STRACE => sys_strace(args[0]),
+        SYSCALL_PROFILE => sys_profile(args[0], args[1], args[2] as *mut ProfSample, args[3]),
+        _ => Errno::ENOSYS.into(),
     }
 }
@@ -1,12 +1,25 @@
 // use crate::loader::get_app_data_by_name;
+use crate::config::{LOG_BUF_SIZE, USER_STACK_LIMIT};
+use crate::errno::{syscall_ret, Errno, SysResult};
 use crate::fs::{OpenFlags, open_file};
-use crate::mm::{translated_ref, translated_refmut, translated_str};
+use crate::logging;
 use crate::task::{
-    add_task, current_task, current_user_token, exit_current_and_run_next, pid2task,
-    suspend_current_and_run_next, SignalAction, SignalFlags, MAX_SIG,
+    add_task, all_tasks, block_current_until, copy_from_user, copy_str_from_user, copy_to_user,
+    current_task,
+    exit_current_and_run_next, pgid2tasks, pid2task, send_signal_info, suspend_current_and_run_next, Credentials, SignalAction,
+    SignalFlags, SignalInfo, SI_QUEUE, SI_USER, SignalStack, TaskControlBlock, IDLE_PID, MAX_SIG, SS_DISABLE,
+    SS_ONSTACK, restore_signal_frame, RLimit, RUsage, Tms, RLIMIT_NOFILE, RLIMIT_STACK, RLIM_NLIMITS,
+    detach, insert_step_breakpoints, peek_user, poke_user, resume, TraceState, UserRegs,
+    PTRACE_ATTACH, PTRACE_CONT, PTRACE_DETACH, PTRACE_GETREGS, PTRACE_KILL, PTRACE_PEEKDATA,
+    PTRACE_PEEKTEXT, PTRACE_POKEDATA, PTRACE_POKETEXT, PTRACE_SETREGS, PTRACE_SINGLESTEP,
+    PTRACE_TRACEME, user_buffer, ProfSample,
+};
+use crate::timer::{
+    add_timer, add_wakeup_timer, get_time, get_time_ms, ticks_to_clock_t, ITimer, ITimerVal,
+    TimeVal, ITIMER_PROF, ITIMER_REAL,
 };
-use crate::timer::get_time_ms;
 use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
 use alloc::sync::Arc;
 
@@ -24,12 +37,118 @@ pub fn sys_get_time() -> isize {
     get_time_ms() as isize
 }
 
+/// Current setting of interval timer `which`, with the time left until it
+/// next expires
+fn itimer_value(timer: &ITimer, which: usize) -> ITimerVal {
+    let remaining = if which == ITIMER_REAL && timer.value != 0 {
+        timer.value.saturating_sub(get_time()).max(1)
+    } else {
+        timer.value
+    };
+    ITimerVal {
+        interval: TimeVal::from_ticks(timer.interval),
+        value: TimeVal::from_ticks(remaining),
+    }
+}
+
+pub fn sys_getitimer(which: usize, curr_value: *mut ITimerVal) -> isize {
+    if which > ITIMER_PROF {
+        return Errno::EINVAL.into();
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let value = itimer_value(&inner.itimers[which], which);
+    match inner.memory_set.copy_to_user(curr_value, &value) {
+        Ok(()) => 0,
+        Err(errno) => errno.into(),
+    }
+}
+
+/// Arm or, with a zero value, disarm interval timer `which`. ITIMER_REAL
+/// counts wall-clock time and raises SIGALRM, ITIMER_VIRTUAL counts user
+/// time and raises SIGVTALRM, ITIMER_PROF counts user and kernel time and
+/// raises SIGPROF.
+pub fn sys_setitimer(which: usize, new_value: *const ITimerVal, old_value: *mut ITimerVal) -> isize {
+    if which > ITIMER_PROF {
+        return Errno::EINVAL.into();
+    }
+    let new = match copy_from_user(new_value) {
+        Ok(new) => new,
+        Err(errno) => return errno.into(),
+    };
+    if new.value.usec >= 1_000_000 || new.interval.usec >= 1_000_000 {
+        return Errno::EINVAL.into();
+    }
+    let (Some(value), Some(interval)) = (new.value.to_ticks(), new.interval.to_ticks()) else {
+        return Errno::EINVAL.into();
+    };
+    let mut timer = ITimer { value, interval };
+    if which == ITIMER_REAL && timer.value != 0 {
+        match timer.value.checked_add(get_time()) {
+            Some(deadline) => timer.value = deadline,
+            None => return Errno::EINVAL.into(),
+        }
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if !old_value.is_null() {
+        let old = itimer_value(&inner.itimers[which], which);
+        if let Err(errno) = inner.memory_set.copy_to_user(old_value, &old) {
+            return errno.into();
+        }
+    }
+    if which == ITIMER_REAL && timer.value != 0 {
+        add_timer(timer.value, task.getpid());
+    }
+    inner.itimers[which] = timer;
+    0
+}
+
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+
+/// CPU times of the caller and its reaped children. Returns the clock_t
+/// ticks since boot.
+pub fn sys_times(tms: *mut Tms) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if !tms.is_null() {
+        let times = Tms {
+            utime: ticks_to_clock_t(inner.usage.utime),
+            stime: ticks_to_clock_t(inner.usage.stime),
+            cutime: ticks_to_clock_t(inner.child_usage.utime),
+            cstime: ticks_to_clock_t(inner.child_usage.stime),
+        };
+        if let Err(errno) = inner.memory_set.copy_to_user(tms, &times) {
+            return errno.into();
+        }
+    }
+    ticks_to_clock_t(get_time()) as isize
+}
+
+pub fn sys_getrusage(who: isize, usage: *mut RUsage) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let rusage = match who {
+        RUSAGE_SELF => inner.usage.to_rusage(),
+        RUSAGE_CHILDREN => inner.child_usage.to_rusage(),
+        _ => return Errno::EINVAL.into(),
+    };
+    match inner.memory_set.copy_to_user(usage, &rusage) {
+        Ok(()) => 0,
+        Err(errno) => errno.into(),
+    }
+}
+
 pub fn sys_getpid() -> isize {
     current_task().unwrap().pid.0 as isize
 }
 
 pub fn sys_fork() -> isize {
     let current_task = current_task().unwrap();
+    if !current_task.inner_exclusive_access().may_add_child() {
+        return Errno::EAGAIN.into();
+    }
     let new_task = current_task.fork();
     let new_pid = new_task.pid.0;
     let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
@@ -38,98 +157,803 @@ pub fn sys_fork() -> isize {
     new_pid as isize
 }
 
-pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
-    println!("[kernel] exec");
-    let token = current_user_token();
-    let path = translated_str(token, path);
+/// Read a null-terminated array of string pointers from user space, a
+/// null array reads as empty
+fn copy_args_from_user(mut args: *const usize) -> SysResult<Vec<String>> {
     let mut args_vec: Vec<String> = Vec::new();
+    if args.is_null() {
+        return Ok(args_vec);
+    }
     loop {
-        let arg_str_ptr = *translated_ref(token, args);
+        let arg_str_ptr = copy_from_user(args)?;
         if arg_str_ptr == 0 {
             break;
         }
-        args_vec.push(translated_str(token, arg_str_ptr as *const u8));
-        unsafe {
-            args = args.add(1);
+        args_vec.push(copy_str_from_user(arg_str_ptr as *const u8)?);
+        args = args.wrapping_add(1);
+    }
+    Ok(args_vec)
+}
+
+/// Replace our image with the ELF at `path`, passing `args` and the
+/// environment `envp`. The new image finds both on its stack; a0 is 0 as
+/// the psABI asks.
+pub fn sys_exec(path: *const u8, args: *const usize, envp: *const usize) -> isize {
+    let path = match copy_str_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno.into(),
+    };
+    debug!("pid {} exec {}", current_task().unwrap().getpid(), path);
+    let (args_vec, envs_vec) = match (copy_args_from_user(args), copy_args_from_user(envp)) {
+        (Ok(args_vec), Ok(envs_vec)) => (args_vec, envs_vec),
+        (Err(errno), _) | (_, Err(errno)) => return errno.into(),
+    };
+    let result = load_program(path, args_vec).and_then(|(all_data, args_vec)| {
+        current_task().unwrap().exec(all_data.as_slice(), args_vec, envs_vec)
+    });
+    syscall_ret(result.map(|_| 0))
+}
+
+/// How many nested `#!` interpreters exec follows
+const MAX_INTERP_DEPTH: usize = 4;
+
+/// Read the program at `path` for exec. A script starting with
+/// `#!interpreter [arg]` runs as `interpreter [arg] path args[1..]`, the
+/// interpreter may be a script itself. Returns the ELF image and the
+/// arguments to start it with, or ENOEXEC if there is nothing to run.
+fn load_program(mut path: String, mut args: Vec<String>) -> SysResult<(Vec<u8>, Vec<String>)> {
+    for _ in 0..=MAX_INTERP_DEPTH {
+        let data = open_file(path.as_str(), OpenFlags::RDONLY)?.read_all();
+        if data.starts_with(b"\x7fELF") {
+            return Ok((data, args));
+        }
+        if !data.starts_with(b"#!") {
+            return Err(Errno::ENOEXEC);
+        }
+        let line_end = data.iter().position(|&c| c == b'\n').unwrap_or(data.len());
+        let line = core::str::from_utf8(&data[2..line_end])
+            .map_err(|_| Errno::ENOEXEC)?
+            .trim();
+        let (interp, arg) = match line.split_once([' ', '\t']) {
+            Some((interp, arg)) => (interp, arg.trim()),
+            None => (line, ""),
+        };
+        if interp.is_empty() {
+            return Err(Errno::ENOEXEC);
+        }
+        let mut interp_args = vec![String::from(interp)];
+        if !arg.is_empty() {
+            interp_args.push(String::from(arg));
         }
+        interp_args.push(path);
+        interp_args.extend(args.into_iter().skip(1));
+        args = interp_args;
+        path = String::from(interp);
     }
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_inode.read_all();
-        let task = current_task().unwrap();
-        let argc = args_vec.len();
-        task.exec(all_data.as_slice(), args_vec);
-        argc as isize
+    Err(Errno::ENOEXEC)
+}
+
+/// `SpawnAttr::flags`: put the child in process group `pgroup`, 0 for a
+/// new group led by the child
+pub const SPAWN_SETPGROUP: u32 = 2;
+/// `SpawnAttr::flags`: start the child with signal mask `sigmask`
+pub const SPAWN_SETSIGMASK: u32 = 8;
+/// `SpawnAction::op`: make `newfd` refer to what `fd` refers to
+pub const SPAWN_DUP2: u32 = 1;
+/// `SpawnAction::op`: close `fd`
+pub const SPAWN_CLOSE: u32 = 2;
+
+/// One file action, applied in order to the child's copy of our fd table
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SpawnAction {
+    pub op: u32,
+    pub fd: usize,
+    pub newfd: usize,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SpawnAttr {
+    pub flags: u32,
+    pub pgroup: usize,
+    pub sigmask: u64,
+    pub actions: *const SpawnAction,
+    pub actions_len: usize,
+}
+
+/// Create a child running the ELF at `path` with `args` and the
+/// environment `envp`, like fork and exec but without copying our address
+/// space. `envp` and `attr` may be null. Returns the child's pid.
+pub fn sys_spawn(
+    path: *const u8,
+    args: *const usize,
+    envp: *const usize,
+    attr: *const SpawnAttr,
+) -> isize {
+    let path = match copy_str_from_user(path) {
+        Ok(path) => path,
+        Err(errno) => return errno.into(),
+    };
+    let (args_vec, envs_vec) = match (copy_args_from_user(args), copy_args_from_user(envp)) {
+        (Ok(args_vec), Ok(envs_vec)) => (args_vec, envs_vec),
+        (Err(errno), _) | (_, Err(errno)) => return errno.into(),
+    };
+    let attr = if attr.is_null() {
+        None
     } else {
-        -1
+        match copy_from_user(attr) {
+            Ok(attr) => Some(attr),
+            Err(errno) => return errno.into(),
+        }
+    };
+    let task = current_task().unwrap();
+    let (mut fd_table, sid, nofile) = {
+        let inner = task.inner_exclusive_access();
+        if !inner.may_add_child() {
+            return Errno::EAGAIN.into();
+        }
+        (inner.fd_table.clone(), inner.sid, inner.rlimits[RLIMIT_NOFILE].cur)
+    };
+    let mut pgroup = None;
+    let mut sigmask = None;
+    if let Some(attr) = attr {
+        for i in 0..attr.actions_len {
+            let action = match copy_from_user(attr.actions.wrapping_add(i)) {
+                Ok(action) => action,
+                Err(errno) => return errno.into(),
+            };
+            match action.op {
+                SPAWN_DUP2 => {
+                    let Some(Some(file)) = fd_table.get(action.fd).cloned() else {
+                        return Errno::EBADF.into();
+                    };
+                    if action.newfd >= nofile {
+                        return Errno::EBADF.into();
+                    }
+                    if fd_table.len() <= action.newfd {
+                        fd_table.resize(action.newfd + 1, None);
+                    }
+                    fd_table[action.newfd] = Some(file);
+                }
+                SPAWN_CLOSE => match fd_table.get_mut(action.fd) {
+                    Some(slot) if slot.is_some() => *slot = None,
+                    _ => return Errno::EBADF.into(),
+                },
+                _ => return Errno::EINVAL.into(),
+            }
+        }
+        if attr.flags & SPAWN_SETPGROUP != 0 {
+            // like setpgid, only groups in our session can be joined
+            if attr.pgroup != 0
+                && !pgid2tasks(attr.pgroup)
+                    .iter()
+                    .any(|member| member.inner_exclusive_access().sid == sid)
+            {
+                return Errno::EPERM.into();
+            }
+            pgroup = Some(attr.pgroup);
+        }
+        if attr.flags & SPAWN_SETSIGMASK != 0 {
+            match SignalFlags::from_bits(attr.sigmask) {
+                Some(mask) => sigmask = Some(mask - (SignalFlags::SIGKILL | SignalFlags::SIGSTOP)),
+                None => return Errno::EINVAL.into(),
+            }
+        }
     }
+    let (all_data, args_vec) = match load_program(path, args_vec) {
+        Ok(program) => program,
+        Err(errno) => return errno.into(),
+    };
+    let child = match task.spawn(all_data.as_slice(), args_vec, envs_vec) {
+        Ok(child) => child,
+        Err(errno) => return errno.into(),
+    };
+    let pid = child.getpid();
+    let mut child_inner = child.inner_exclusive_access();
+    child_inner.fd_table = fd_table;
+    if let Some(pgroup) = pgroup {
+        child_inner.pgid = if pgroup == 0 { pid } else { pgroup };
+    }
+    if let Some(mask) = sigmask {
+        child_inner.signal_mask = mask;
+    }
+    drop(child_inner);
+    add_task(child);
+    pid as isize
+}
+
+/// Report children stopped by a signal
+const WUNTRACED: usize = 2;
+/// Report stopped children resumed by SIGCONT
+const WCONTINUED: usize = 8;
+/// Report an exited child but leave it to be waited for again
+const WNOWAIT: usize = 0x0100_0000;
+
+/// pid > 0 selects that child, -1 any child, 0 our own process group and
+/// pid < -1 the process group -pid
+fn wait_target_matches(pid: isize, our_pgid: usize, child: &TaskControlBlock) -> bool {
+    match pid {
+        -1 => true,
+        0 => child.inner_exclusive_access().pgid == our_pgid,
+        pid if pid < 0 => child.inner_exclusive_access().pgid == (-pid) as usize,
+        pid => pid as usize == child.getpid(),
+    }
+}
+
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> isize {
+    wait_child(pid, exit_code_ptr, options, |exit_code| exit_code)
 }
 
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// Reap or report one child as waitpid does, storing the status of a
+/// reaped child as `encode` makes it out of its exit code. A null
+/// `exit_code_ptr` stores nothing. Fails with EAGAIN if no child has
+/// anything to report yet.
+pub fn wait_child(
+    pid: isize,
+    exit_code_ptr: *mut i32,
+    options: usize,
+    encode: fn(i32) -> i32,
+) -> isize {
     let task = current_task().unwrap();
     let mut inner = task.inner_exclusive_access();
+    let our_pgid = inner.pgid;
     if !inner
         .children
         .iter()
-        .any(|p| pid == -1 || pid as usize == p.getpid())
+        .any(|p| wait_target_matches(pid, our_pgid, p))
     {
-        return -1;
+        return Errno::ECHILD.into();
     }
     let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
+        wait_target_matches(pid, our_pgid, p) && p.inner_exclusive_access().is_zombie()
     });
     if let Some((idx, _)) = pair {
+        // a bad pointer leaves the zombie to be waited for again
+        let exit_code = encode(inner.children[idx].inner_exclusive_access().exit_code);
+        if !exit_code_ptr.is_null() {
+            if let Err(errno) = inner.memory_set.copy_to_user(exit_code_ptr, &exit_code) {
+                return errno.into();
+            }
+        }
+        if options & WNOWAIT != 0 {
+            return inner.children[idx].getpid() as isize;
+        }
+        // the hart it exited on may still hold a reference until it has
+        // switched away; the TCB is freed by whoever drops last
         let child = inner.children.remove(idx);
-        assert_eq!(Arc::strong_count(&child), 1);
         let found_pid = child.getpid();
-        let exit_code = child.inner_exclusive_access().exit_code;
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
+        let child_inner = child.inner_exclusive_access();
+        // the child's usage, and that of what it reaped, becomes ours
+        inner.child_usage.add(&child_inner.usage);
+        inner.child_usage.add(&child_inner.child_usage);
+        drop(child_inner);
+        return found_pid as isize;
+    }
+    // stopped children report (sig << 8) | 0x7f, continued ones 0xffff
+    let report = inner.children.iter().find_map(|child| {
+        if !wait_target_matches(pid, our_pgid, child) {
+            return None;
+        }
+        let mut child_inner = child.inner_exclusive_access();
+        // the tracer hears of stops without asking
+        let traced = child_inner.trace.is_some();
+        let status = if (options & WUNTRACED != 0 || traced) && child_inner.stop_report.is_some() {
+            (child_inner.stop_report.take().unwrap() << 8) as i32 | 0x7f
+        } else if options & WCONTINUED != 0 && child_inner.cont_report {
+            child_inner.cont_report = false;
+            0xffff
+        } else {
+            return None;
+        };
+        Some((child.getpid(), status))
+    });
+    match report {
+        Some((pid, _)) if exit_code_ptr.is_null() => pid as isize,
+        Some((pid, status)) => match inner.memory_set.copy_to_user(exit_code_ptr, &status) {
+            Ok(()) => pid as isize,
+            Err(errno) => errno.into(),
+        },
+        // nothing to report yet
+        None => Errno::EAGAIN.into(),
+    }
+}
+/// Whether a task with credentials `cred` in session `sid` may send
+/// `signal` to `target`: root may signal anyone, others only tasks of
+/// their own user, except that SIGCONT reaches the whole session
+fn may_signal(
+    cred: &Credentials,
+    sid: usize,
+    target: &Arc<TaskControlBlock>,
+    signal: SignalFlags,
+) -> bool {
+    let target_inner = target.inner_exclusive_access();
+    cred.is_root()
+        || cred.uid == target_inner.cred.uid
+        || cred.euid == target_inner.cred.uid
+        || (signal == SignalFlags::SIGCONT && sid == target_inner.sid)
+}
+
+/// pid > 0 signals that process, 0 our own process group, -1 every
+/// process but initproc and ourselves, and pid < -1 the process group -pid.
+/// Fails with ESRCH if there is no such process, EPERM if we may signal
+/// none of them and EAGAIN if their real-time signal queues are full.
+/// Signal 0 sends nothing, only checks.
+pub fn sys_kill(pid: isize, signum: i32) -> isize {
+    if !(0..=MAX_SIG as i32).contains(&signum) {
+        return Errno::EINVAL.into();
+    }
+    let flag = match signum {
+        0 => SignalFlags::empty(),
+        signum => match SignalFlags::from_bits(1 << signum) {
+            Some(flag) => flag,
+            None => return Errno::EINVAL.into(),
+        },
+    };
+    let task = current_task().unwrap();
+    let me = task.getpid();
+    let (cred, sid, pgid) = {
+        let inner = task.inner_exclusive_access();
+        (inner.cred, inner.sid, inner.pgid)
+    };
+    drop(task);
+    let info = (signum != 0).then(|| {
+        let mut info = SignalInfo::new(flag.signum(), SI_USER, me, 0);
+        info.uid = cred.uid;
+        info
+    });
+    let targets: Vec<Arc<TaskControlBlock>> = match pid {
+        0 => pgid2tasks(pgid),
+        -1 => all_tasks()
+            .into_iter()
+            .filter(|task| task.getpid() != me && task.getpid() != IDLE_PID)
+            .collect(),
+        pid if pid < 0 => pgid2tasks((-pid) as usize),
+        pid => pid2task(pid as usize).into_iter().collect(),
+    };
+    let mut permitted = false;
+    let mut sent = false;
+    for target in targets.iter() {
+        if may_signal(&cred, sid, target, flag) {
+            permitted = true;
+            sent |= info.is_none_or(|info| send_signal_info(target, info));
+        }
+    }
+    if sent {
+        0
+    } else if targets.is_empty() {
+        Errno::ESRCH.into()
+    } else if permitted {
+        Errno::EAGAIN.into()
+    } else {
+        Errno::EPERM.into()
+    }
+}
+
+/// Queue signal `signum` carrying `value` on process `pid`. Every call
+/// queues a new instance for real-time signals, or fails with EAGAIN once
+/// SIGQUEUE_MAX of them are queued.
+pub fn sys_sigqueue(pid: usize, signum: usize, value: usize) -> isize {
+    let flag = match SignalFlags::from_signum(signum) {
+        Some(flag) if signum != 0 => flag,
+        _ => return Errno::EINVAL.into(),
+    };
+    let task = current_task().unwrap();
+    let me = task.getpid();
+    let (cred, sid) = {
+        let inner = task.inner_exclusive_access();
+        (inner.cred, inner.sid)
+    };
+    drop(task);
+    let mut info = SignalInfo::new(flag.signum(), SI_QUEUE, me, value);
+    info.uid = cred.uid;
+    let Some(target) = pid2task(pid) else {
+        return Errno::ESRCH.into();
+    };
+    if !may_signal(&cred, sid, &target, flag) {
+        return Errno::EPERM.into();
+    }
+    if send_signal_info(&target, info) { 0 } else { Errno::EAGAIN.into() }
+}
+
+pub fn sys_getuid() -> isize {
+    current_task().unwrap().inner_exclusive_access().cred.uid as isize
+}
+
+pub fn sys_geteuid() -> isize {
+    current_task().unwrap().inner_exclusive_access().cred.euid as isize
+}
+
+pub fn sys_getgid() -> isize {
+    current_task().unwrap().inner_exclusive_access().cred.gid as isize
+}
+
+pub fn sys_getegid() -> isize {
+    current_task().unwrap().inner_exclusive_access().cred.egid as isize
+}
+
+/// Root sets both the real and effective uid; anyone else may only set
+/// the effective uid back to the real one
+pub fn sys_setuid(uid: u32) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.cred.is_root() {
+        inner.cred.uid = uid;
+        inner.cred.euid = uid;
+    } else if uid == inner.cred.uid {
+        inner.cred.euid = uid;
+    } else {
+        return Errno::EPERM.into();
+    }
+    0
+}
+
+/// Like setuid, for the group ids
+pub fn sys_setgid(gid: u32) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.cred.is_root() {
+        inner.cred.gid = gid;
+        inner.cred.egid = gid;
+    } else if gid == inner.cred.gid {
+        inner.cred.egid = gid;
     } else {
-        -2
+        return Errno::EPERM.into();
     }
+    0
 }
-pub fn sys_kill(pid: usize, signum: i32) -> isize {
-    if let Some(task) = pid2task(pid) {
-        if let Some(flag) = SignalFlags::from_bits(1 << signum) {
-            let mut task_ref = task.inner_exclusive_access();
-            if task_ref.signals.contains(flag) {
-                return -1;
+
+/// Trace a child as Linux ptrace(request, pid, addr, data) does. Except
+/// for TRACEME, ATTACH and KILL the child must be stopped for us.
+pub fn sys_ptrace(request: usize, pid: usize, addr: usize, data: usize) -> isize {
+    let task = current_task().unwrap();
+    if request == PTRACE_TRACEME {
+        let mut inner = task.inner_exclusive_access();
+        if inner.trace.is_some() || inner.parent.is_none() {
+            return Errno::EPERM.into();
+        }
+        inner.trace = Some(TraceState::default());
+        return 0;
+    }
+    let (me, cred, child) = {
+        let inner = task.inner_exclusive_access();
+        let child = inner.children.iter().find(|child| child.getpid() == pid).cloned();
+        (task.getpid(), inner.cred, child)
+    };
+    // only our own children can be traced
+    let Some(child) = child else {
+        return Errno::ESRCH.into();
+    };
+    let mut child_inner = child.inner_exclusive_access();
+    if child_inner.is_zombie() {
+        return Errno::ESRCH.into();
+    }
+    if request == PTRACE_ATTACH {
+        if child_inner.trace.is_some() || !(cred.is_root() || cred.uid == child_inner.cred.uid) {
+            return Errno::EPERM.into();
+        }
+        child_inner.trace = Some(TraceState::default());
+        drop(child_inner);
+        send_signal_info(&child, SignalInfo::new(SignalFlags::SIGSTOP.signum(), SI_USER, me, 0));
+        return 0;
+    }
+    let stopped = match child_inner.trace.as_ref() {
+        Some(trace) => trace.stopped,
+        None => return Errno::ESRCH.into(),
+    };
+    if request == PTRACE_KILL {
+        drop(child_inner);
+        send_signal_info(&child, SignalInfo::new(SignalFlags::SIGKILL.signum(), SI_USER, me, 0));
+        return 0;
+    }
+    if !stopped {
+        return Errno::ESRCH.into();
+    }
+    match request {
+        PTRACE_PEEKTEXT | PTRACE_PEEKDATA => {
+            let mut word = [0u8; core::mem::size_of::<usize>()];
+            if !peek_user(&child_inner.memory_set, addr, &mut word) {
+                return Errno::EFAULT.into();
+            }
+            drop(child_inner);
+            match copy_to_user(data as *mut usize, &usize::from_le_bytes(word)) {
+                Ok(()) => 0,
+                Err(errno) => errno.into(),
             }
-            task_ref.signals.insert(flag);
+        }
+        PTRACE_POKETEXT | PTRACE_POKEDATA => {
+            if poke_user(&mut child_inner.memory_set, addr, &data.to_le_bytes()) {
+                0
+            } else {
+                Errno::EFAULT.into()
+            }
+        }
+        PTRACE_GETREGS => {
+            let cx = child_inner.get_trap_cx();
+            let mut regs: UserRegs = cx.x;
+            regs[0] = cx.sepc;
+            drop(child_inner);
+            match copy_to_user(data as *mut UserRegs, &regs) {
+                Ok(()) => 0,
+                Err(errno) => errno.into(),
+            }
+        }
+        PTRACE_SETREGS => {
+            let regs = match copy_from_user(data as *const UserRegs) {
+                Ok(regs) => regs,
+                Err(errno) => return errno.into(),
+            };
+            let cx = child_inner.get_trap_cx();
+            cx.sepc = regs[0];
+            cx.x[1..].copy_from_slice(&regs[1..]);
+            0
+        }
+        PTRACE_CONT => {
+            if resume(&mut child_inner, data) { 0 } else { Errno::EINVAL.into() }
+        }
+        PTRACE_SINGLESTEP => {
+            if data > MAX_SIG {
+                return Errno::EINVAL.into();
+            }
+            if !insert_step_breakpoints(&mut child_inner) {
+                return Errno::EFAULT.into();
+            }
+            resume(&mut child_inner, data);
             0
-        } else {
-            -1
         }
+        PTRACE_DETACH => {
+            if !resume(&mut child_inner, data) {
+                return Errno::EINVAL.into();
+            }
+            detach(&mut child_inner);
+            0
+        }
+        _ => Errno::EINVAL.into(),
+    }
+}
+
+/// Turn syscall logging for the current task on (non-zero `enable`) or
+/// off. Children forked or spawned afterwards inherit the setting, and it
+/// survives exec.
+pub fn sys_strace(enable: usize) -> isize {
+    current_task().unwrap().inner_exclusive_access().strace = enable != 0;
+    0
+}
+
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> isize {
+    if resource >= RLIM_NLIMITS {
+        return Errno::EINVAL.into();
+    }
+    let limit = current_task().unwrap().inner_exclusive_access().rlimits[resource];
+    match copy_to_user(rlim, &limit) {
+        Ok(()) => 0,
+        Err(errno) => errno.into(),
+    }
+}
+
+/// Anyone may lower a limit, only root may raise a hard limit. The soft
+/// limit never exceeds the hard one and the stack never grows past
+/// USER_STACK_LIMIT.
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> isize {
+    if resource >= RLIM_NLIMITS {
+        return Errno::EINVAL.into();
+    }
+    let limit = match copy_from_user(rlim) {
+        Ok(limit) => limit,
+        Err(errno) => return errno.into(),
+    };
+    if limit.cur > limit.max || (resource == RLIMIT_STACK && limit.max > USER_STACK_LIMIT) {
+        return Errno::EINVAL.into();
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if limit.max > inner.rlimits[resource].max && !inner.cred.is_root() {
+        return Errno::EPERM.into();
+    }
+    inner.rlimits[resource] = limit;
+    0
+}
+
+pub fn sys_getpgid(pid: usize) -> isize {
+    let task = if pid == 0 {
+        current_task()
+    } else {
+        pid2task(pid)
+    };
+    match task {
+        Some(task) => task.inner_exclusive_access().pgid as isize,
+        None => Errno::ESRCH.into(),
+    }
+}
+
+/// Move `pid` (0 for ourselves) into process group `pgid` (0 for its own
+/// pid). The target must be ourselves or one of our children, in our
+/// session and not a session leader; an existing group must be in the
+/// same session.
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    let task = current_task().unwrap();
+    let target = if pid == 0 || pid == task.getpid() {
+        task.clone()
     } else {
-        -1
+        let inner = task.inner_exclusive_access();
+        match inner.children.iter().find(|child| child.getpid() == pid) {
+            Some(child) => child.clone(),
+            None => return Errno::ESRCH.into(),
+        }
+    };
+    let pgid = if pgid == 0 { target.getpid() } else { pgid };
+    let sid = task.inner_exclusive_access().sid;
+    let target_inner = target.inner_exclusive_access();
+    if target_inner.sid != sid || target.getpid() == sid {
+        return Errno::EPERM.into();
+    }
+    drop(target_inner);
+    if pgid != target.getpid()
+        && !pgid2tasks(pgid)
+            .iter()
+            .any(|member| member.inner_exclusive_access().sid == sid)
+    {
+        return Errno::EPERM.into();
     }
+    target.inner_exclusive_access().pgid = pgid;
+    0
 }
 
-pub fn sys_sigprocmask(mask: u32) -> isize {
+/// Start a new session and process group led by the caller
+pub fn sys_setsid() -> isize {
+    let task = current_task().unwrap();
+    let pid = task.getpid();
+    if !pgid2tasks(pid).is_empty() {
+        return Errno::EPERM.into();
+    }
+    let mut inner = task.inner_exclusive_access();
+    inner.pgid = pid;
+    inner.sid = pid;
+    pid as isize
+}
+
+pub fn sys_sigprocmask(mask: u64) -> isize {
     if let Some(task) = current_task() {
         let mut inner = task.inner_exclusive_access();
         let old_mask = inner.signal_mask;
         if let Some(flag) = SignalFlags::from_bits(mask) {
-            inner.signal_mask = flag;
+            // SIGKILL and SIGSTOP cannot be blocked
+            inner.signal_mask = flag - (SignalFlags::SIGKILL | SignalFlags::SIGSTOP);
             old_mask.bits() as isize
         } else {
-            -1
+            Errno::EINVAL.into()
         }
     } else {
-        -1
+        Errno::ESRCH.into()
     }
 }
 
-pub fn sys_sigreturn() -> isize {
-    if let Some(task) = current_task() {
-        let mut inner = task.inner_exclusive_access();
-        inner.handling_sig = -1;
-        let trap_ctx = inner.get_trap_cx();
-        *trap_ctx = inner.trap_ctx_backup.unwrap();
-        trap_ctx.x[10] as isize
+/// Replace the signal mask with `mask` and sleep until a signal is
+/// delivered, then fail with EINTR. The old mask is back once the handler
+/// returns.
+pub fn sys_sigsuspend(mask: u64) -> isize {
+    let mask = match SignalFlags::from_bits(mask) {
+        Some(mask) => mask - (SignalFlags::SIGKILL | SignalFlags::SIGSTOP),
+        None => return Errno::EINVAL.into(),
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.saved_mask = Some(inner.signal_mask);
+    inner.signal_mask = mask;
+    drop(inner);
+    drop(task);
+    block_current_until(|inner| {
+        (inner.killed.is_some() || !inner.deliverable_signals().is_empty()).then_some(())
+    });
+    Errno::EINTR.into()
+}
+
+/// Wait for one of the signals in `set` and dequeue it without running its
+/// handler, storing its details in `info`. Returns the signal number;
+/// fails with EAGAIN once `timeout` runs out and EINTR if another signal
+/// interrupts the wait. A null `timeout` waits forever.
+pub fn sys_sigtimedwait(set: u64, info: *mut SignalInfo, timeout: *const TimeVal) -> isize {
+    let set = match SignalFlags::from_bits(set) {
+        Some(set) => set - (SignalFlags::SIGKILL | SignalFlags::SIGSTOP),
+        None => return Errno::EINVAL.into(),
+    };
+    let pid = current_task().unwrap().getpid();
+    let deadline = if timeout.is_null() {
+        None
     } else {
-        -1
+        let timeout = match copy_from_user(timeout) {
+            Ok(timeout) => timeout,
+            Err(errno) => return errno.into(),
+        };
+        let Some(deadline) = timeout.to_ticks().and_then(|ticks| get_time().checked_add(ticks))
+        else {
+            return Errno::EINVAL.into();
+        };
+        add_wakeup_timer(deadline, pid);
+        Some(deadline)
+    };
+    let result = block_current_until(|inner| {
+        let pending = inner.signals & set;
+        if let Some(sig) = (1..=MAX_SIG).find(|sig| {
+            pending.contains(SignalFlags::from_signum(*sig).unwrap())
+        }) {
+            inner.sig_wait = SignalFlags::empty();
+            return Some(Ok(inner.take_signal(sig)));
+        }
+        if inner.killed.is_some() || !inner.deliverable_signals().is_empty() {
+            inner.sig_wait = SignalFlags::empty();
+            return Some(Err(Errno::EINTR));
+        }
+        if deadline.is_some_and(|deadline| get_time() >= deadline) {
+            inner.sig_wait = SignalFlags::empty();
+            return Some(Err(Errno::EAGAIN));
+        }
+        inner.sig_wait = set;
+        None
+    });
+    match result {
+        Ok(siginfo) => {
+            // the signal is taken even if it cannot be reported
+            if !info.is_null() {
+                if let Err(errno) = copy_to_user(info, &siginfo) {
+                    return errno.into();
+                }
+            }
+            siginfo.signo as isize
+        }
+        Err(errno) => errno.into(),
+    }
+}
+
+pub fn sys_sigreturn() -> isize {
+    match restore_signal_frame() {
+        Some(a0) => a0 as isize,
+        None => Errno::EFAULT.into(),
+    }
+}
+
+/// Smallest alternate stack accepted by sigaltstack
+const MINSIGSTKSZ: usize = 2048;
+
+pub fn sys_sigaltstack(ss: *const SignalStack, old_ss: *mut SignalStack) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let user_sp = inner.get_trap_cx().x[2];
+    let on_stack = inner
+        .altstack
+        .is_some_and(|(base, size)| (base..base + size).contains(&user_sp));
+    if !old_ss.is_null() {
+        let (sp, size) = inner.altstack.unwrap_or((0, 0));
+        let flags = if inner.altstack.is_none() {
+            SS_DISABLE
+        } else if on_stack {
+            SS_ONSTACK
+        } else {
+            0
+        };
+        let old = SignalStack { sp, flags, size };
+        if let Err(errno) = inner.memory_set.copy_to_user(old_ss, &old) {
+            return errno.into();
+        }
     }
+    if !ss.is_null() {
+        // cannot switch stacks while a handler is running on it
+        if on_stack {
+            return Errno::EPERM.into();
+        }
+        let new = match inner.memory_set.copy_from_user(ss) {
+            Ok(new) => new,
+            Err(errno) => return errno.into(),
+        };
+        if new.flags & SS_DISABLE != 0 {
+            inner.altstack = None;
+        } else if new.size < MINSIGSTKSZ {
+            return Errno::ENOMEM.into();
+        } else {
+            inner.altstack = Some((new.sp, new.size));
+        }
+    }
+    0
 }
 
 fn check_sigaction_error(signal: SignalFlags, action: usize, old_action: usize) -> bool {
@@ -149,21 +973,128 @@ pub fn sys_sigaction(
     action: *const SignalAction,
     old_action: *mut SignalAction,
 ) -> isize {
-    let token = current_user_token();
     let task = current_task().unwrap();
     let mut inner = task.inner_exclusive_access();
     if signum as usize > MAX_SIG {
-        return -1;
+        return Errno::EINVAL.into();
     }
     if let Some(flag) = SignalFlags::from_bits(1 << signum) {
         if check_sigaction_error(flag, action as usize, old_action as usize) {
-            return -1;
+            return Errno::EINVAL.into();
         }
         let prev_action = inner.signal_actions.table[signum as usize];
-        *translated_refmut(token, old_action) = prev_action;
-        inner.signal_actions.table[signum as usize] = *translated_ref(token, action);
+        let new_action = match inner.memory_set.copy_from_user(action) {
+            Ok(new_action) => new_action,
+            Err(errno) => return errno.into(),
+        };
+        if let Err(errno) = inner.memory_set.copy_to_user(old_action, &prev_action) {
+            return errno.into();
+        }
+        inner.set_signal_action(signum as usize, new_action);
         0
     } else {
-        -1
+        Errno::EINVAL.into()
+    }
+}
+
+const SYSLOG_ACTION_CLOSE: usize = 0;
+const SYSLOG_ACTION_OPEN: usize = 1;
+const SYSLOG_ACTION_READ: usize = 2;
+const SYSLOG_ACTION_READ_ALL: usize = 3;
+const SYSLOG_ACTION_READ_CLEAR: usize = 4;
+const SYSLOG_ACTION_CLEAR: usize = 5;
+const SYSLOG_ACTION_CONSOLE_OFF: usize = 6;
+const SYSLOG_ACTION_CONSOLE_ON: usize = 7;
+const SYSLOG_ACTION_CONSOLE_LEVEL: usize = 8;
+const SYSLOG_ACTION_SIZE_UNREAD: usize = 9;
+const SYSLOG_ACTION_SIZE_BUFFER: usize = 10;
+
+/// Read or control the kernel log as Linux syslog(2) does, except that
+/// SYSLOG_ACTION_READ returns 0 instead of waiting when nothing is unread.
+/// `len` is the console level for SYSLOG_ACTION_CONSOLE_LEVEL. Only root
+/// may do more than SYSLOG_ACTION_READ_ALL and SYSLOG_ACTION_SIZE_BUFFER.
+pub fn sys_syslog(action: usize, buf: *mut u8, len: usize) -> isize {
+    if action != SYSLOG_ACTION_READ_ALL
+        && action != SYSLOG_ACTION_SIZE_BUFFER
+        && !current_task().unwrap().inner_exclusive_access().cred.is_root()
+    {
+        return Errno::EPERM.into();
+    }
+    let read: fn(usize) -> Vec<u8> = match action {
+        SYSLOG_ACTION_CLOSE | SYSLOG_ACTION_OPEN => return 0,
+        SYSLOG_ACTION_READ => logging::read,
+        SYSLOG_ACTION_READ_ALL => |len| logging::read_all(len, false),
+        SYSLOG_ACTION_READ_CLEAR => |len| logging::read_all(len, true),
+        SYSLOG_ACTION_CLEAR => {
+            logging::clear();
+            return 0;
+        }
+        SYSLOG_ACTION_CONSOLE_OFF => {
+            logging::console_off();
+            return 0;
+        }
+        SYSLOG_ACTION_CONSOLE_ON => {
+            logging::console_on();
+            return 0;
+        }
+        SYSLOG_ACTION_CONSOLE_LEVEL if logging::set_console_level(len) => return 0,
+        SYSLOG_ACTION_SIZE_UNREAD => return logging::unread() as isize,
+        SYSLOG_ACTION_SIZE_BUFFER => return LOG_BUF_SIZE as isize,
+        _ => return Errno::EINVAL.into(),
+    };
+    if buf.is_null() || (len as isize) < 0 {
+        return Errno::EINVAL.into();
+    }
+    // check the buffer before a read takes anything out of the log
+    let mut buffer = match user_buffer(buf, len, true) {
+        Ok(buffer) => buffer,
+        Err(errno) => return errno.into(),
+    };
+    let bytes = read(len);
+    let mut copied = 0;
+    for slice in buffer.buffers.iter_mut() {
+        let n = slice.len().min(bytes.len() - copied);
+        slice[..n].copy_from_slice(&bytes[copied..copied + n]);
+        copied += n;
+    }
+    copied as isize
+}
+
+const PROF_START: usize = 0;
+const PROF_STOP: usize = 1;
+const PROF_READ: usize = 2;
+
+/// Control the sampling profile of `pid`, 0 for ourselves; otherwise it
+/// must be one of our children, which stays readable after it exits until
+/// it is reaped (see WNOWAIT). PROF_START clears the samples and starts
+/// taking them, PROF_STOP stops; PROF_READ copies up to `len` samples to
+/// `buf` and returns how many there are in all.
+pub fn sys_profile(cmd: usize, pid: usize, buf: *mut ProfSample, len: usize) -> isize {
+    let task = current_task().unwrap();
+    let target = if pid == 0 || pid == task.getpid() {
+        task
+    } else {
+        let inner = task.inner_exclusive_access();
+        match inner.children.iter().find(|child| child.getpid() == pid) {
+            Some(child) => child.clone(),
+            None => return Errno::ESRCH.into(),
+        }
+    };
+    let mut target_inner = target.inner_exclusive_access();
+    match cmd {
+        PROF_START => target_inner.profile.start(),
+        PROF_STOP => target_inner.profile.running = false,
+        PROF_READ => {
+            let samples = target_inner.profile.samples();
+            drop(target_inner);
+            for (i, sample) in samples.iter().take(len).enumerate() {
+                if let Err(errno) = copy_to_user(buf.wrapping_add(i), sample) {
+                    return errno.into();
+                }
+            }
+            return samples.len() as isize;
+        }
+        _ => return Errno::EINVAL.into(),
     }
+    0
 }
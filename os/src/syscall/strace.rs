@@ -0,0 +1,151 @@
+//! Syscall logging for tasks with the strace flag set
+//!
+//! Each call is printed as `[pid] name(args) = ret <sec.usec>` once it
+//! returns. Arguments are decoded before the call runs, as exec replaces
+//! the memory a path points into.
+use super::*;
+use crate::mm::MemorySet;
+use crate::task::current_task;
+use crate::timer::{get_time, TimeVal};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// How to print one argument
+#[derive(Clone, Copy)]
+pub(super) enum Arg {
+    /// signed decimal: fds, pids, lengths
+    Int,
+    /// hexadecimal: pointers, flags, masks
+    Hex,
+    /// NUL-terminated user string
+    Str,
+}
+
+use Arg::*;
+
+/// Name and argument kinds of syscall `syscall_id` under `personality`
+fn signature(
+    personality: Personality,
+    syscall_id: usize,
+) -> Option<(&'static str, &'static [Arg])> {
+    match personality {
+        Personality::Native => native_signature(syscall_id),
+        Personality::Linux => linux::signature(syscall_id),
+    }
+}
+
+fn native_signature(syscall_id: usize) -> Option<(&'static str, &'static [Arg])> {
+    let signature: (&str, &[Arg]) = match syscall_id {
+        SYSCALL_DUP => ("dup", &[Int]),
+        SYSCALL_IOCTL => ("ioctl", &[Int, Hex, Hex]),
+        SYSCALL_OPEN => ("open", &[Str, Hex]),
+        SYSCALL_CLOSE => ("close", &[Int]),
+        SYSCALL_DELETE => ("delete", &[Str]),
+        SYSCALL_PIPE => ("pipe", &[Hex]),
+        SYSCALL_LSEEK => ("lseek", &[Int, Int, Int]),
+        SYSCALL_READ => ("read", &[Int, Hex, Int]),
+        SYSCALL_WRITE => ("write", &[Int, Hex, Int]),
+        SYSCALL_RENAME => ("rename", &[Str, Str]),
+        SYSCALL_MKDIR => ("mkdir", &[Str]),
+        SYSCALL_RMDIR => ("rmdir", &[Str]),
+        SYSCALL_EXIT => ("exit", &[Int]),
+        SYSCALL_GETITIMER => ("getitimer", &[Int, Hex]),
+        SYSCALL_SETITIMER => ("setitimer", &[Int, Hex, Hex]),
+        SYSCALL_SYSLOG => ("syslog", &[Int, Hex, Int]),
+        SYSCALL_PTRACE => ("ptrace", &[Int, Int, Hex, Hex]),
+        SYSCALL_YIELD => ("yield", &[]),
+        SYSCALL_KILL => ("kill", &[Int, Int]),
+        SYSCALL_SIGALTSTACK => ("sigaltstack", &[Hex, Hex]),
+        SYSCALL_SIGSUSPEND => ("sigsuspend", &[Hex]),
+        SYSCALL_SIGACTION => ("sigaction", &[Int, Hex, Hex]),
+        SYSCALL_SIGPROCMASK => ("sigprocmask", &[Hex]),
+        SYSCALL_SIGTIMEDWAIT => ("sigtimedwait", &[Hex, Hex, Hex]),
+        SYSCALL_SIGQUEUE => ("sigqueue", &[Int, Int, Hex]),
+        SYSCALL_SIGRETURN => ("sigreturn", &[]),
+        SYSCALL_SETGID => ("setgid", &[Int]),
+        SYSCALL_SETUID => ("setuid", &[Int]),
+        SYSCALL_TIMES => ("times", &[Hex]),
+        SYSCALL_SETPGID => ("setpgid", &[Int, Int]),
+        SYSCALL_GETPGID => ("getpgid", &[Int]),
+        SYSCALL_SETSID => ("setsid", &[]),
+        SYSCALL_GETRLIMIT => ("getrlimit", &[Int, Hex]),
+        SYSCALL_SETRLIMIT => ("setrlimit", &[Int, Hex]),
+        SYSCALL_GETRUSAGE => ("getrusage", &[Int, Hex]),
+        SYSCALL_GET_TIME => ("get_time", &[]),
+        SYSCALL_GETPID => ("getpid", &[]),
+        SYSCALL_GETUID => ("getuid", &[]),
+        SYSCALL_GETEUID => ("geteuid", &[]),
+        SYSCALL_GETGID => ("getgid", &[]),
+        SYSCALL_GETEGID => ("getegid", &[]),
+        SYSCALL_FORK => ("fork", &[]),
+        SYSCALL_EXEC => ("exec", &[Str, Hex, Hex]),
+        SYSCALL_PERF_EVENT_OPEN => ("perf_event_open", &[Hex, Int, Int, Int, Hex]),
+        SYSCALL_WAITPID => ("waitpid", &[Int, Hex, Hex]),
+        SYSCALL_SPAWN => ("spawn", &[Str, Hex, Hex, Hex]),
+        SYSCALL_STRACE => ("strace", &[Int]),
+        SYSCALL_PROFILE => ("profile", &[Int, Int, Hex, Int]),
+        _ => return None,
+    };
+    Some(signature)
+}
+
+fn format_arg(memory_set: &MemorySet, kind: Arg, value: usize) -> String {
+    match kind {
+        Int => format!("{}", value as isize),
+        Hex => format!("{:#x}", value),
+        Str if value == 0 => String::from("NULL"),
+        // a bad pointer prints as one, the call fails with EFAULT
+        Str => match memory_set.read_user_str(value) {
+            Ok(string) => format!("{:?}", string),
+            Err(_) => format!("{:#x}", value),
+        },
+    }
+}
+
+/// `name(args)` of a call about to run
+fn format_call(
+    memory_set: &MemorySet,
+    personality: Personality,
+    syscall_id: usize,
+    args: [usize; 6],
+) -> String {
+    let Some((name, kinds)) = signature(personality, syscall_id) else {
+        let args: Vec<String> = args.iter().map(|arg| format!("{:#x}", arg)).collect();
+        return format!("syscall_{}({})", syscall_id, args.join(", "));
+    };
+    let mut call = format!("{}(", name);
+    for (i, (kind, value)) in kinds.iter().zip(args).enumerate() {
+        if i != 0 {
+            call.push_str(", ");
+        }
+        call.push_str(&format_arg(memory_set, *kind, value));
+    }
+    call.push(')');
+    call
+}
+
+/// Run syscall `syscall_id` through `dispatch` and log it
+pub fn traced_syscall(
+    personality: Personality,
+    syscall_id: usize,
+    args: [usize; 6],
+    dispatch: fn(usize, [usize; 6]) -> isize,
+) -> isize {
+    let task = current_task().unwrap();
+    let pid = task.getpid();
+    let call = {
+        let inner = task.inner_exclusive_access();
+        format_call(&inner.memory_set, personality, syscall_id, args)
+    };
+    drop(task);
+    if matches!(signature(personality, syscall_id), Some(("exit" | "exit_group", _))) {
+        // never returns
+        println!("[{}] {} = ?", pid, call);
+    }
+    let start = get_time();
+    let ret = dispatch(syscall_id, args);
+    let duration = TimeVal::from_ticks(get_time() - start);
+    println!("[{}] {} = {} <{}.{:06}>", pid, call, ret, duration.sec, duration.usec);
+    ret
+}
@@ -1,10 +1,22 @@
 use crate::task::{SignalFlags, MAX_SIG};
 
+/// Handler wants (signum, &SignalInfo, &SignalFrame); the kernel passes
+/// these to every handler, the flag only documents the expectation
+pub const SA_SIGINFO: u32 = 0x4;
+/// Run the handler on the alternate signal stack
+pub const SA_ONSTACK: u32 = 0x0800_0000;
+/// Do not block the signal while its own handler runs
+pub const SA_NODEFER: u32 = 0x4000_0000;
+/// Restore the default disposition once the handler is entered
+pub const SA_RESETHAND: u32 = 0x8000_0000;
+
 #[repr(C, align(16))]
 #[derive(Debug, Clone, Copy)]
 pub struct SignalAction {
     pub handler: usize,
+    /// signals blocked while the handler runs
     pub mask: SignalFlags,
+    pub flags: u32,
 }
 
 impl Default for SignalAction {
@@ -12,6 +24,7 @@ impl Default for SignalAction {
         Self {
             handler: 0,
             mask: SignalFlags::from_bits(40).unwrap(),
+            flags: 0,
         }
     }
 }
@@ -0,0 +1,245 @@
+//! ELF core files for processes killed by a signal that dumps core
+//!
+//! The file holds one PT_LOAD segment per user `MapArea` and a PT_NOTE
+//! with an NT_PRSTATUS laid out as riscv64 Linux does, so gdb on the host
+//! reads it like any other core: `riscv64-unknown-elf-gdb prog core.<pid>`.
+use super::{current_task, SignalDefault, SignalFlags, RLIMIT_CORE, UserRegs};
+use crate::config::PAGE_SIZE;
+use crate::fs::{open_file, OpenFlags};
+use crate::mm::{FrameTracker, MapPermission, MemorySet, VirtPageNum};
+use crate::timer::TimeVal;
+use alloc::format;
+use alloc::vec::Vec;
+
+const ET_CORE: u16 = 4;
+const EM_RISCV: u16 = 243;
+/// RVC, double-float ABI
+const EF_RISCV: u32 = 0x5;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+/// Owner of the notes, padded to a multiple of 4
+const NOTE_NAME: &[u8; 8] = b"CORE\0\0\0\0";
+
+#[repr(C)]
+struct Elf64Ehdr {
+    ident: [u8; 16],
+    e_type: u16,
+    machine: u16,
+    version: u32,
+    entry: u64,
+    phoff: u64,
+    shoff: u64,
+    flags: u32,
+    ehsize: u16,
+    phentsize: u16,
+    phnum: u16,
+    shentsize: u16,
+    shnum: u16,
+    shstrndx: u16,
+}
+
+#[repr(C)]
+struct Elf64Phdr {
+    p_type: u32,
+    flags: u32,
+    offset: u64,
+    vaddr: u64,
+    paddr: u64,
+    filesz: u64,
+    memsz: u64,
+    align: u64,
+}
+
+#[repr(C)]
+struct Elf64Nhdr {
+    namesz: u32,
+    descsz: u32,
+    n_type: u32,
+}
+
+/// `struct elf_prstatus` of riscv64 Linux
+#[repr(C)]
+struct Prstatus {
+    signo: i32,
+    code: i32,
+    errno: i32,
+    cursig: i16,
+    _pad: i16,
+    sigpend: u64,
+    sighold: u64,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    utime: TimeVal,
+    stime: TimeVal,
+    cutime: TimeVal,
+    cstime: TimeVal,
+    /// the pc, then x1..x31
+    reg: UserRegs,
+    fpvalid: i32,
+    _pad2: i32,
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>()) }
+}
+
+/// Non-empty user-accessible areas of `memory_set` as (start, end,
+/// permission)
+fn dump_areas(memory_set: &MemorySet) -> Vec<(VirtPageNum, VirtPageNum, MapPermission)> {
+    memory_set
+        .user_areas()
+        .into_iter()
+        .filter(|(start, end, perm)| perm.contains(MapPermission::U) && start != end)
+        .collect()
+}
+
+/// If the current task is being killed by a signal whose default action
+/// dumps core, write `core.<pid>` and return true. Nothing is written if
+/// RLIMIT_CORE is smaller than the file would be. The headers and the page
+/// frames are taken under the task lock, the file is written without it.
+pub fn dump_core_of_current() -> bool {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let Some(sig) = inner.killed else {
+        return false;
+    };
+    let signal = SignalFlags::from_signum(sig).unwrap();
+    if signal.default_action() != SignalDefault::Core {
+        return false;
+    }
+    let areas = dump_areas(&inner.memory_set);
+    let phnum = areas.len() + 1;
+    let note_offset = core::mem::size_of::<Elf64Ehdr>() + phnum * core::mem::size_of::<Elf64Phdr>();
+    let note_size = core::mem::size_of::<Elf64Nhdr>() + NOTE_NAME.len() + core::mem::size_of::<Prstatus>();
+    let data_offset = (note_offset + note_size).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+    let data_size: usize = areas
+        .iter()
+        .map(|(start, end, _)| (end.0 - start.0) * PAGE_SIZE)
+        .sum();
+    if data_offset + data_size > inner.rlimits[RLIMIT_CORE].cur {
+        return false;
+    }
+
+    let mut ident = [0u8; 16];
+    // magic, 64-bit, little endian, version 1
+    ident[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    let ehdr = Elf64Ehdr {
+        ident,
+        e_type: ET_CORE,
+        machine: EM_RISCV,
+        version: 1,
+        entry: 0,
+        phoff: core::mem::size_of::<Elf64Ehdr>() as u64,
+        shoff: 0,
+        flags: EF_RISCV,
+        ehsize: core::mem::size_of::<Elf64Ehdr>() as u16,
+        phentsize: core::mem::size_of::<Elf64Phdr>() as u16,
+        phnum: phnum as u16,
+        shentsize: 0,
+        shnum: 0,
+        shstrndx: 0,
+    };
+    let mut header: Vec<u8> = Vec::new();
+    header.extend_from_slice(as_bytes(&ehdr));
+    let note = Elf64Phdr {
+        p_type: PT_NOTE,
+        flags: 0,
+        offset: note_offset as u64,
+        vaddr: 0,
+        paddr: 0,
+        filesz: note_size as u64,
+        memsz: 0,
+        align: 4,
+    };
+    header.extend_from_slice(as_bytes(&note));
+    let mut offset = data_offset;
+    for (start, end, perm) in areas.iter() {
+        let size = (end.0 - start.0) * PAGE_SIZE;
+        let mut flags = 0;
+        if perm.contains(MapPermission::R) {
+            flags |= PF_R;
+        }
+        if perm.contains(MapPermission::W) {
+            flags |= PF_W;
+        }
+        if perm.contains(MapPermission::X) {
+            flags |= PF_X;
+        }
+        let load = Elf64Phdr {
+            p_type: PT_LOAD,
+            flags,
+            offset: offset as u64,
+            vaddr: (start.0 * PAGE_SIZE) as u64,
+            paddr: 0,
+            filesz: size as u64,
+            memsz: size as u64,
+            align: PAGE_SIZE as u64,
+        };
+        header.extend_from_slice(as_bytes(&load));
+        offset += size;
+    }
+
+    let cx = inner.get_trap_cx();
+    let mut reg: UserRegs = cx.x;
+    reg[0] = cx.sepc;
+    let parent = inner.parent.as_ref().and_then(|parent| parent.upgrade());
+    let prstatus = Prstatus {
+        signo: sig as i32,
+        code: 0,
+        errno: 0,
+        cursig: sig as i16,
+        _pad: 0,
+        sigpend: inner.signals.bits(),
+        sighold: inner.signal_mask.bits(),
+        pid: task.getpid() as i32,
+        ppid: parent.map_or(0, |parent| parent.getpid() as i32),
+        pgrp: inner.pgid as i32,
+        sid: inner.sid as i32,
+        utime: TimeVal::from_ticks(inner.usage.utime),
+        stime: TimeVal::from_ticks(inner.usage.stime),
+        cutime: TimeVal::from_ticks(inner.child_usage.utime),
+        cstime: TimeVal::from_ticks(inner.child_usage.stime),
+        reg,
+        fpvalid: 0,
+        _pad2: 0,
+    };
+    let nhdr = Elf64Nhdr {
+        namesz: 5,
+        descsz: core::mem::size_of::<Prstatus>() as u32,
+        n_type: NT_PRSTATUS,
+    };
+    header.extend_from_slice(as_bytes(&nhdr));
+    header.extend_from_slice(NOTE_NAME);
+    header.extend_from_slice(as_bytes(&prstatus));
+    header.resize(data_offset, 0);
+    // held on to, so they stay ours while the file is written
+    let frames: Vec<Option<FrameTracker>> = areas
+        .iter()
+        .flat_map(|(start, end, _)| start.0..end.0)
+        .map(|vpn| inner.memory_set.frame(VirtPageNum(vpn)))
+        .collect();
+    drop(inner);
+
+    let Ok(file) = open_file(
+        format!("core.{}", task.getpid()).as_str(),
+        OpenFlags::CREATE | OpenFlags::WRONLY,
+    ) else {
+        return false;
+    };
+    file.write_all(&header);
+    // page by page, unmapped pages read as zeros
+    let zeros = [0u8; PAGE_SIZE];
+    for frame in frames.iter() {
+        match frame {
+            Some(frame) => file.write_all(frame.ppn.get_bytes_array()),
+            None => file.write_all(&zeros),
+        };
+    }
+    true
+}
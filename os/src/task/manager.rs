@@ -1,8 +1,9 @@
 //!Implementation of [`TaskManager`]
 use super::TaskControlBlock;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinMutex;
 use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
 
 pub struct TaskManager {
@@ -24,29 +25,42 @@ impl TaskManager {
 }
 
 lazy_static! {
-    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
-        unsafe { UPSafeCell::new(TaskManager::new()) };
-        pub static ref PID2TCB: UPSafeCell<BTreeMap<usize, Arc<TaskControlBlock>>> =
-        unsafe { UPSafeCell::new(BTreeMap::new()) };
+    pub static ref TASK_MANAGER: SpinMutex<TaskManager> =
+        SpinMutex::new(TaskManager::new());
+        pub static ref PID2TCB: SpinMutex<BTreeMap<usize, Arc<TaskControlBlock>>> =
+        SpinMutex::new(BTreeMap::new());
 }
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
     PID2TCB
-        .exclusive_access()
+        .lock()
         .insert(task.getpid(), Arc::clone(&task));
-    TASK_MANAGER.exclusive_access().add(task);
+    TASK_MANAGER.lock().add(task);
 }
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
-    TASK_MANAGER.exclusive_access().fetch()
+    TASK_MANAGER.lock().fetch()
 }
 
 pub fn pid2task(pid: usize) -> Option<Arc<TaskControlBlock>> {
-    let map = PID2TCB.exclusive_access();
+    let map = PID2TCB.lock();
     map.get(&pid).map(Arc::clone)
 }
 
+/// All live tasks, in pid order
+pub fn all_tasks() -> Vec<Arc<TaskControlBlock>> {
+    PID2TCB.lock().values().cloned().collect()
+}
+
+/// Live tasks in process group `pgid`
+pub fn pgid2tasks(pgid: usize) -> Vec<Arc<TaskControlBlock>> {
+    all_tasks()
+        .into_iter()
+        .filter(|task| task.inner_exclusive_access().pgid == pgid)
+        .collect()
+}
+
 pub fn remove_from_pid2task(pid: usize) {
-    let mut map = PID2TCB.exclusive_access();
+    let mut map = PID2TCB.lock();
     if map.remove(&pid).is_none() {
         panic!("cannot find pid {} in pid2task!", pid);
     }
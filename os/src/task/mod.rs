@@ -1,49 +1,106 @@
 mod action;
 mod context;
+mod coredump;
 mod manager;
 mod pid;
 mod processor;
+mod profile;
+mod ptrace;
 mod signal;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
 
 use crate::lang_items::shutdown;
+use crate::config::SIGRETURN_TRAMPOLINE;
 use crate::fs::{OpenFlags, open_file};
+use crate::timer::{add_timer, get_time, ITIMER_REAL};
 use alloc::sync::Arc;
 use lazy_static::*;
-pub use manager::{TaskManager, fetch_task, remove_from_pid2task};
+pub use manager::{TaskManager, all_tasks, fetch_task, pgid2tasks, remove_from_pid2task};
 use switch::__switch;
-use task::{TaskControlBlock, TaskStatus};
+use task::{TaskControlBlock, TaskControlBlockInner, TaskStatus};
 
-pub use action::{SignalAction, SignalActions};
+pub use action::{SignalAction, SignalActions, SA_NODEFER, SA_ONSTACK, SA_RESETHAND, SA_SIGINFO};
 pub use context::TaskContext;
 pub use manager::{add_task, pid2task};
 pub use pid::{KernelStack, PidAllocator, PidHandle, pid_alloc};
+pub use task::{
+    Credentials, Personality, RLimit, RUsage, ResourceUsage, Tms, RLIMIT_AS, RLIMIT_CORE,
+    RLIMIT_NOFILE, RLIMIT_STACK, RLIM_NLIMITS,
+};
+pub use coredump::dump_core_of_current;
+pub use profile::{discard_tick, note_tick, sample_current, ProfSample, Profile};
 pub use processor::{
-    Processor, current_task, current_trap_cx, current_user_token, handle_cow, run_tasks, schedule,
-    take_current_task,
+    Processor, copy_from_user, copy_str_from_user, copy_to_user, current_pid, current_task,
+    current_trap_cx, current_user_token, handle_cow, run_tasks, schedule, take_current_task,
+    user_buffer,
+};
+pub use ptrace::{
+    detach, handle_breakpoint, insert_step_breakpoints, peek_user, poke_user, resume, TraceState,
+    UserRegs, PTRACE_ATTACH, PTRACE_CONT, PTRACE_DETACH, PTRACE_GETREGS, PTRACE_KILL,
+    PTRACE_PEEKDATA, PTRACE_PEEKTEXT, PTRACE_POKEDATA, PTRACE_POKETEXT, PTRACE_SETREGS,
+    PTRACE_SINGLESTEP, PTRACE_TRACEME,
+};
+pub use signal::{
+    SignalDefault, SignalFlags, SignalFrame, SignalInfo, SignalStack, CLD_CONTINUED, CLD_EXITED,
+    CLD_STOPPED, CLD_TRAPPED, MAX_SIG, SIGQUEUE_MAX, SIGRTMAX, SIGRTMIN, SIG_DFL, SIG_IGN, SI_KERNEL, SI_QUEUE,
+    SI_USER, SS_DISABLE, SS_ONSTACK, TRAP_BRKPT, TRAP_TRACE, termination,
 };
-pub use signal::{SignalFlags, MAX_SIG};
 
 pub fn suspend_current_and_run_next() {
-    let task = take_current_task().unwrap();
+    let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
     let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
     task_inner.task_status = TaskStatus::Ready;
     drop(task_inner);
-    add_task(task);
+    drop(task);
+    // the idle context re-queues us once our context has been saved
     schedule(task_cx_ptr);
 }
 
+/// Block the current task until `check`, run under its lock, returns a
+/// value. The task sleeps off the ready queue in between and is woken by
+/// `wakeup_task`, after which `check` runs again.
+pub fn block_current_until<T>(mut check: impl FnMut(&mut TaskControlBlockInner) -> Option<T>) -> T {
+    loop {
+        let task = current_task().unwrap();
+        let mut task_inner = task.inner_exclusive_access();
+        if let Some(result) = check(&mut task_inner) {
+            return result;
+        }
+        let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+        task_inner.task_status = TaskStatus::Blocked;
+        drop(task_inner);
+        drop(task);
+        schedule(task_cx_ptr);
+    }
+}
+
+/// Make a blocked `task` ready again. A task that has not finished
+/// switching away yet is queued by the hart it is leaving instead.
+pub fn wakeup_task(task: &Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner.task_status != TaskStatus::Blocked {
+        return;
+    }
+    task_inner.task_status = TaskStatus::Ready;
+    let on_cpu = task_inner.on_cpu;
+    drop(task_inner);
+    if !on_cpu {
+        add_task(task.clone());
+    }
+}
+
 pub const IDLE_PID: usize = 0;
 
 pub fn exit_current_and_run_next(exit_code: i32) {
 
-    let task = take_current_task().unwrap();
+    // the processor keeps its reference until we are off this kernel stack
+    let task = current_task().unwrap();
 
     let pid = task.getpid();
-    println!("[kernel] Exit current task {} with exit_code {}",pid, exit_code);
+    info!("pid {} exited with code {}", pid, exit_code);
     if pid == IDLE_PID {
         println!(
             "[kernel] Idle process exit with exit_code {} ...",
@@ -58,24 +115,31 @@ pub fn exit_current_and_run_next(exit_code: i32) {
 
     remove_from_pid2task(task.getpid());
     let mut inner = task.inner_exclusive_access();
+    // settle our times before the parent can reap them
+    inner.charge_time(false);
     inner.task_status = TaskStatus::Zombie;
     inner.exit_code = exit_code;
+    let children = core::mem::take(&mut inner.children);
+    inner.memory_set.recycle_data_pages();
+    inner.fd_table.clear();
+    drop(inner);
+    notify_parent(&task, CLD_EXITED, exit_code as usize);
 
+    // lock parents before children, as sys_waitpid does
     {
         let mut initproc_inner = INITPROC.inner_exclusive_access();
-        for child in inner.children.iter() {
-            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
-            initproc_inner.children.push(child.clone());
+        for child in children {
+            let mut child_inner = child.inner_exclusive_access();
+            // initproc traces nobody
+            detach(&mut child_inner);
+            child_inner.parent = Some(Arc::downgrade(&INITPROC));
+            drop(child_inner);
+            initproc_inner.children.push(child);
         }
     }
-
-    inner.children.clear();
-    inner.memory_set.recycle_data_pages();
-    inner.fd_table.clear();
-    drop(inner);
     drop(task);
     let mut _unused = TaskContext::zero_init();
-    println!("[kernel] Switch to next task ...");
+    trace!("switch to next task");
     schedule(&mut _unused as *mut _);
 }
 
@@ -95,85 +159,251 @@ pub fn add_initproc() {
 pub fn check_signals_error_of_current() -> Option<(i32, &'static str)> {
     let task = current_task().unwrap();
     let task_inner = task.inner_exclusive_access();
-    task_inner.signals.check_error()
+    task_inner.killed.map(termination)
+}
+
+/// Charge the current task for the time since its last stamp, as user
+/// time on entry from user mode and as kernel time on the way back
+pub fn charge_current_time(user: bool) {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().charge_time(user);
+}
+
+/// An ITIMER_REAL entry of `pid` set to expire at `expire` came due.
+/// Entries left behind by a timer that was since reset are ignored.
+pub fn fire_real_timer(pid: usize, expire: usize) {
+    let Some(task) = pid2task(pid) else {
+        return;
+    };
+    let mut task_inner = task.inner_exclusive_access();
+    let timer = &mut task_inner.itimers[ITIMER_REAL];
+    if timer.value != expire {
+        return;
+    }
+    if timer.interval == 0 {
+        timer.value = 0;
+    } else {
+        // skip periods we were too late for rather than firing a burst
+        timer.value = expire + timer.interval;
+        let now = get_time();
+        if timer.value <= now {
+            timer.value = now + timer.interval;
+        }
+        add_timer(timer.value, pid);
+    }
+    drop(task_inner);
+    send_signal(&task, SignalFlags::SIGALRM);
 }
 
 pub fn current_add_signal(signal: SignalFlags) {
     let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
-    task_inner.signals |= signal;
+    task_inner.post_signal(SignalInfo::kernel(signal));
 }
 
-fn call_kernel_signal_handler(signal: SignalFlags) {
+/// Post a kernel-generated `signal` to `task`
+pub fn send_signal(task: &Arc<TaskControlBlock>, signal: SignalFlags) {
+    send_signal_info(task, SignalInfo::kernel(signal));
+}
+
+/// Queue `info` on `task`, returns false if its queue is full. SIGCONT
+/// resumes a stopped task right away, whatever its handler or mask, and
+/// discards pending stop signals; a stop signal discards a pending SIGCONT.
+/// A task blocked in a signal wait is woken if the signal can reach it.
+pub fn send_signal_info(task: &Arc<TaskControlBlock>, info: SignalInfo) -> bool {
+    let signal = SignalFlags::from_signum(info.signo as usize).unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let mut resumed = false;
+    // only the tracer resumes a task stopped for it
+    let trace_stopped = task_inner.trace.as_ref().is_some_and(|trace| trace.stopped);
+    if signal == SignalFlags::SIGCONT {
+        task_inner.discard_signals(
+            SignalFlags::SIGSTOP | SignalFlags::SIGTSTP | SignalFlags::SIGTTIN | SignalFlags::SIGTTOU,
+        );
+        if !trace_stopped {
+            task_inner.stop_report = None;
+        }
+        if task_inner.frozen && !trace_stopped {
+            task_inner.frozen = false;
+            task_inner.cont_report = true;
+            resumed = true;
+        }
+    } else if signal.is_stop() {
+        task_inner.discard_signals(SignalFlags::SIGCONT);
+    }
+    let queued = task_inner.post_signal(info);
+    let wake = task_inner.task_status == TaskStatus::Blocked
+        && (!task_inner.signal_mask.contains(signal) || task_inner.sig_wait.contains(signal));
+    drop(task_inner);
+    if wake {
+        wakeup_task(task);
+    }
+    if resumed {
+        notify_parent(task, CLD_CONTINUED, SignalFlags::SIGCONT.signum());
+    }
+    queued
+}
+
+/// Queue `info` on every task in process group `pgid`, returns whether
+/// the group had any member
+pub fn signal_group(pgid: usize, info: SignalInfo) -> bool {
+    let tasks = pgid2tasks(pgid);
+    for task in tasks.iter() {
+        send_signal_info(task, info);
+    }
+    !tasks.is_empty()
+}
+
+/// Tell the parent of `task` that it exited, stopped or continued
+fn notify_parent(task: &Arc<TaskControlBlock>, code: i32, status: usize) {
+    let (parent, uid) = {
+        let task_inner = task.inner_exclusive_access();
+        (task_inner.parent.as_ref().and_then(|p| p.upgrade()), task_inner.cred.uid)
+    };
+    if let Some(parent) = parent {
+        let mut info = SignalInfo::new(SignalFlags::SIGCHLD.signum(), code, task.getpid(), status);
+        info.uid = uid;
+        send_signal_info(&parent, info);
+    }
+}
+
+/// Carry out the default disposition of `signal`
+fn call_kernel_signal_handler(sig: usize, signal: SignalFlags) {
     let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
-    match signal {
-        SignalFlags::SIGSTOP => {
+    task_inner.take_signal(sig);
+    match signal.default_action() {
+        SignalDefault::Stop => {
             task_inner.frozen = true;
-            task_inner.signals ^= SignalFlags::SIGSTOP;
+            task_inner.stop_report = Some(sig);
+            task_inner.cont_report = false;
+            drop(task_inner);
+            notify_parent(&task, CLD_STOPPED, sig);
         }
-        SignalFlags::SIGCONT => {
-            if task_inner.signals.contains(SignalFlags::SIGCONT) {
-                task_inner.signals ^= SignalFlags::SIGCONT;
-                task_inner.frozen = false;
-            }
-        }
-        _ => {
-            task_inner.killed = true;
+        // SIGCONT already resumed us when it was sent
+        SignalDefault::Continue | SignalDefault::Ignore => {}
+        SignalDefault::Terminate | SignalDefault::Core => {
+            task_inner.killed = Some(sig);
         }
     }
 }
 
+/// Push a signal frame and redirect the user context into the handler.
+///
+/// The frame goes below the interrupted sp, or on top of the alternate
+/// stack for SA_ONSTACK handlers not already running there. The handler
+/// returns into the sigreturn trampoline, which pops the frame again.
 fn call_user_signal_handler(sig: usize, signal: SignalFlags) {
     let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
 
-    let handler = task_inner.signal_actions.table[sig].handler;
-    if handler != 0 {
-        task_inner.handling_sig = sig as isize;
-        task_inner.signals ^= signal;
-        let trap_ctx = task_inner.get_trap_cx();
-        task_inner.trap_ctx_backup = Some(*trap_ctx);
-        trap_ctx.sepc = handler;
-        trap_ctx.x[10] = sig;
-    } else {
-        println!("[K] task/call_user_signal_handler: default action: ignore it or kill process");
+    let action = task_inner.signal_actions.table[sig];
+    let info = task_inner.take_signal(sig);
+    let trap_ctx = task_inner.get_trap_cx();
+    let user_sp = trap_ctx.x[2];
+    let mut sp = match task_inner.altstack {
+        Some((base, size))
+            if action.flags & SA_ONSTACK != 0 && !(base..base + size).contains(&user_sp) =>
+        {
+            base + size
+        }
+        _ => user_sp,
+    };
+    sp = (sp - core::mem::size_of::<SignalFrame>()) & !0xf;
+    // sigreturn goes back to the mask from before sigsuspend
+    let saved_mask = task_inner.saved_mask.take();
+    let frame = SignalFrame {
+        x: trap_ctx.x,
+        sepc: trap_ctx.sepc,
+        mask: saved_mask.unwrap_or(task_inner.signal_mask),
+        prev: task_inner.sig_frame,
+        info,
+    };
+    if task_inner.memory_set.copy_to_user(sp as *mut SignalFrame, &frame).is_err() {
+        // no room for the frame, the process cannot handle anything
+        warn!("pid {}: cannot push signal frame at {:#x}", task.getpid(), sp);
+        task_inner.killed = Some(SignalFlags::SIGSEGV.signum());
+        return;
+    }
+    task_inner.sig_frame = sp;
+    task_inner.signal_mask |= action.mask;
+    if action.flags & SA_NODEFER == 0 {
+        task_inner.signal_mask |= signal;
+    }
+    task_inner.signal_mask -= SignalFlags::SIGKILL | SignalFlags::SIGSTOP;
+    if action.flags & SA_RESETHAND != 0 {
+        task_inner.signal_actions.table[sig] = SignalAction::default();
+    }
+    trap_ctx.x[2] = sp;
+    trap_ctx.x[1] = SIGRETURN_TRAMPOLINE;
+    trap_ctx.sepc = action.handler;
+    // handler(sig, &info, &frame); plain handlers ignore the extra arguments
+    trap_ctx.x[10] = sig;
+    trap_ctx.x[11] = sp + core::mem::offset_of!(SignalFrame, info);
+    trap_ctx.x[12] = sp;
+}
+
+/// Pop the innermost signal frame of the current task, restoring the
+/// registers and signal mask it saved. Returns the restored a0 so the
+/// syscall return path leaves it unchanged, or None if there is no frame.
+pub fn restore_signal_frame() -> Option<usize> {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let addr = task_inner.sig_frame;
+    if addr == 0 {
+        return None;
     }
+    let frame = task_inner
+        .memory_set
+        .copy_from_user(addr as *const SignalFrame)
+        .ok()?;
+    task_inner.sig_frame = frame.prev;
+    task_inner.signal_mask = frame.mask - (SignalFlags::SIGKILL | SignalFlags::SIGSTOP);
+    let trap_ctx = task_inner.get_trap_cx();
+    trap_ctx.x = frame.x;
+    trap_ctx.sepc = frame.sepc;
+    Some(trap_ctx.x[10])
 }
 
 fn check_pending_signals() {
     for sig in 0..(MAX_SIG + 1) {
         let task = current_task().unwrap();
-        let task_inner = task.inner_exclusive_access();
+        let mut task_inner = task.inner_exclusive_access();
         let signal = SignalFlags::from_bits(1 << sig).unwrap();
+        // handlers extend signal_mask with SignalAction::mask while they run
         if task_inner.signals.contains(signal) && (!task_inner.signal_mask.contains(signal)) {
-            let mut masked = true;
-            let handling_sig = task_inner.handling_sig;
-            if handling_sig == -1 {
-                masked = false;
-            } else {
-                let handling_sig = handling_sig as usize;
-                if !task_inner.signal_actions.table[handling_sig]
-                    .mask
-                    .contains(signal)
-                {
-                    masked = false;
+            debug!("pid {} takes {:?}", task.getpid(), signal);
+            // a traced task stops for its tracer first, and while stopped
+            // only SIGKILL gets through
+            if let Some(trace) = task_inner.trace.as_mut() {
+                if signal != SignalFlags::SIGKILL {
+                    if trace.stopped {
+                        continue;
+                    }
+                    if !trace.passed.contains(signal) {
+                        drop(task_inner);
+                        drop(task);
+                        ptrace::ptrace_stop(sig);
+                        return;
+                    }
+                    trace.passed.remove(signal);
                 }
             }
-            print!("{:?}", signal);
-            if !masked {
-                drop(task_inner);
-                drop(task);
-                if signal == SignalFlags::SIGKILL
-                    || signal == SignalFlags::SIGSTOP
-                    || signal == SignalFlags::SIGCONT
-                    || signal == SignalFlags::SIGDEF
-                {
-                    call_kernel_signal_handler(signal);
-                } else {
-                    call_user_signal_handler(sig, signal);
-                    return;
-                }
+            let handler = task_inner.signal_actions.table[sig].handler;
+            if handler == SIG_IGN {
+                task_inner.discard_signals(signal);
+                continue;
+            }
+            drop(task_inner);
+            drop(task);
+            if signal == SignalFlags::SIGKILL
+                || signal == SignalFlags::SIGSTOP
+                || handler == SIG_DFL
+            {
+                call_kernel_signal_handler(sig, signal);
+            } else {
+                call_user_signal_handler(sig, signal);
+                return;
             }
         }
     }
@@ -185,11 +415,17 @@ pub fn handle_signals() {
         let (frozen, killed) = {
             let task = current_task().unwrap();
             let task_inner = task.inner_exclusive_access();
-            (task_inner.frozen, task_inner.killed)
+            (task_inner.frozen, task_inner.killed.is_some())
         };
         if !frozen || killed {
             break;
         }
         suspend_current_and_run_next();
     }
+    // sigsuspend ended without running a handler
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    if let Some(mask) = task_inner.saved_mask.take() {
+        task_inner.signal_mask = mask;
+    }
 }
@@ -1,7 +1,8 @@
 //!Implementation of [`PidAllocator`]
 use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE};
 use crate::mm::{KERNEL_SPACE, MapPermission, VirtAddr};
-use crate::sync::UPSafeCell;
+use crate::smp::tlb_shootdown;
+use crate::sync::SpinMutex;
 use alloc::vec::Vec;
 use lazy_static::*;
 
@@ -38,18 +39,18 @@ impl PidAllocator {
     }
 }
 lazy_static! {
-    pub static ref PID_ALLOCATOR: UPSafeCell<PidAllocator> =
-        unsafe { UPSafeCell::new(PidAllocator::new()) };
+    pub static ref PID_ALLOCATOR: SpinMutex<PidAllocator> =
+        SpinMutex::new(PidAllocator::new());
 }
 
 impl Drop for PidHandle {
     fn drop(&mut self) {
-        PID_ALLOCATOR.exclusive_access().dealloc(self.0);
+        PID_ALLOCATOR.lock().dealloc(self.0);
     }
 }
 
 pub fn pid_alloc() -> PidHandle {
-    PID_ALLOCATOR.exclusive_access().alloc()
+    PID_ALLOCATOR.lock().alloc()
 }
 
 // impl Drop for PidAllocator {
@@ -72,7 +73,7 @@ impl KernelStack {
     pub fn new(pid_handle: &PidHandle) -> Self {
         let pid = pid_handle.0;
         let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(pid);
-        KERNEL_SPACE.exclusive_access().insert_framed_area(
+        KERNEL_SPACE.lock().insert_framed_area(
             kernel_stack_bottom.into(),
             kernel_stack_top.into(),
             MapPermission::R | MapPermission::W,
@@ -103,8 +104,12 @@ impl Drop for KernelStack {
     fn drop(&mut self) {
         let (kernel_stack_bottom, _) = kernel_stack_position(self.pid);
         let kernel_stack_bottom_va: VirtAddr = kernel_stack_bottom.into();
-        KERNEL_SPACE
-            .exclusive_access()
+        let area = KERNEL_SPACE
+            .lock()
             .remove_area_with_start_vpn(kernel_stack_bottom_va.into());
+        // other harts may still cache the mapping of this stack, its frames
+        // are freed once they have all flushed it
+        tlb_shootdown();
+        drop(area);
     }
 }
@@ -1,12 +1,18 @@
 use super::__switch;
 use super::{TaskContext, TaskControlBlock};
-use super::{TaskStatus, fetch_task};
+use super::{TaskStatus, add_task, discard_tick, fetch_task};
+use crate::config::MAX_HARTS;
+use crate::errno::SysResult;
+use crate::smp::{handle_ipi, hart_id};
 use crate::sync::UPSafeCell;
+use crate::timer::{check_timer, get_time};
 use crate::trap::TrapContext;
 use alloc::str;
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
-use crate::mm::VirtAddr;
+use crate::mm::{UserBuffer, VirtAddr};
 
 pub struct Processor {
     current: Option<Arc<TaskControlBlock>>,
@@ -35,18 +41,32 @@ impl Processor{
 }
 
 lazy_static!{
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    /// One processor per hart, each only accessed by its own hart
+    pub static ref PROCESSORS: Vec<UPSafeCell<Processor>> = (0..MAX_HARTS)
+        .map(|_| unsafe { UPSafeCell::new(Processor::new()) })
+        .collect();
+}
+
+fn current_processor() -> &'static UPSafeCell<Processor> {
+    &PROCESSORS[hart_id()]
 }
 
 pub fn run_tasks(){
     loop{
-        let mut processor = PROCESSOR.exclusive_access();
+        handle_ipi();
+        check_timer();
+        let mut processor = current_processor().exclusive_access();
         if let Some(task) = fetch_task() {
             // println!("[kernel] Switch to task ...");
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             let mut task_inner = task.inner_exclusive_access();
             let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
             task_inner.task_status = TaskStatus::Running;
+            task_inner.on_cpu = true;
+            task_inner.time_stamp = get_time();
+            // a tick noted while we were idle is nobody's
+            discard_tick();
+            task_inner.perf.switch_in();
             // println!("[kernel] Switch to task {} ... ra is {}", task.getpid(), task_inner.task_cx.get_ra());
             drop(task_inner);
             processor.current = Some(task);
@@ -54,45 +74,120 @@ pub fn run_tasks(){
             unsafe {
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            finish_switch();
+        }
+    }
+}
+
+/// Runs on the idle context once the previous task has been switched out.
+///
+/// A task that yielded is only put back into the ready queue here, after
+/// its context is saved, so no other hart can pick it up while it is still
+/// running on its kernel stack. The same goes for a blocked task woken up
+/// before it got off the hart. Exited tasks are released here as well.
+fn finish_switch() {
+    let task = current_processor().exclusive_access().take_current();
+    if let Some(task) = task {
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.on_cpu = false;
+        task_inner.charge_time(false);
+        task_inner.perf.switch_out();
+        let status = task_inner.task_status;
+        match status {
+            TaskStatus::Blocked => task_inner.usage.nvcsw += 1,
+            TaskStatus::Ready => task_inner.usage.nivcsw += 1,
+            _ => {}
+        }
+        drop(task_inner);
+        if status == TaskStatus::Ready {
+            add_task(task);
         }
     }
 }
 
+/// Pid of the task running on this hart, for panic reports: never panics
+/// itself, even on a hart that has no processor or holds it already
+pub fn current_pid() -> Option<usize> {
+    let processor = PROCESSORS.get(hart_id())?.try_exclusive_access()?;
+    processor.current.as_ref().map(|task| task.getpid())
+}
+
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    current_processor().exclusive_access().take_current()
 }
 
 pub fn handle_cow(fault_addr: VirtAddr) -> bool {
-    let task = PROCESSOR.exclusive_access().current().unwrap();
+    let task = current_task().unwrap();
     let mut task_inner = task.inner_exclusive_access();
     let memory_set = &mut task_inner.memory_set;
     if crate::mm::MemorySet::cow_judge(memory_set, fault_addr) {
         memory_set.cow(fault_addr);
+        task_inner.usage.minflt += 1;
         return true;
     }
     false
 }
 
+#[cfg_attr(feature = "lockdep", track_caller)]
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    current_processor().exclusive_access().current()
 }
 
+#[cfg_attr(feature = "lockdep", track_caller)]
 pub fn current_user_token() -> usize {
     let task = current_task().unwrap();
     let token = task.inner_exclusive_access().get_user_token();
     token
 }
 
+/// Read a `T` from user pointer `src` of the current task
+pub fn copy_from_user<T: Copy>(src: *const T) -> SysResult<T> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner.memory_set.copy_from_user(src)
+}
+
+/// Write `value` to user pointer `dst` of the current task, breaking COW
+/// sharing as needed
+pub fn copy_to_user<T: Copy>(dst: *mut T, value: &T) -> SysResult<()> {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner.memory_set.copy_to_user(dst, value)
+}
+
+/// Read the NUL-terminated string at `src` of the current task
+pub fn copy_str_from_user(src: *const u8) -> SysResult<String> {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    inner.memory_set.read_user_str(src as usize)
+}
+
+/// The `len` bytes at `buf` of the current task for a file to work on;
+/// `write` is set when the kernel stores into them, as read does
+pub fn user_buffer(buf: *const u8, len: usize, write: bool) -> SysResult<UserBuffer> {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let slices = if write {
+        inner.memory_set.user_slices_mut(buf as usize, len)?
+    } else {
+        inner.memory_set.user_slices(buf as usize, len)?
+    };
+    Ok(UserBuffer::new(slices))
+}
+
+#[cfg_attr(feature = "lockdep", track_caller)]
 pub fn current_trap_cx() -> &'static mut TrapContext {
     current_task().unwrap().inner_exclusive_access().get_trap_cx()
 }
 
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
     // println!("[kernel] schedule");
-    let mut processor = PROCESSOR.exclusive_access();
+    let mut processor = current_processor().exclusive_access();
     let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
     drop(processor);
+    #[cfg(feature = "lockdep")]
+    crate::sync::lockdep::check_switch();
     unsafe {
         __switch(switched_task_cx_ptr, idle_task_cx_ptr);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,101 @@
+//! Sampling profiler driven by the timer tick
+//!
+//! The machine-mode timer handler notes where each tick found its hart:
+//! at a kernel pc, or in user mode. The software interrupt it raises is
+//! taken once the hart is back in user mode, and charges the tick to the
+//! task running there if that task profiles, as a hit on its user pc or
+//! on the noted kernel pc. So a tick in the kernel counts for the task
+//! that next returns to user mode on that hart; ticks that found the hart
+//! idle are dropped when it switches to a task.
+use super::current_task;
+use crate::config::{MAX_HARTS, PROFILE_MAX_PCS};
+use crate::smp::hart_id;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// No tick since the last one was taken
+const NO_TICK: usize = 0;
+/// The tick came in user mode, kernel pcs are never this
+const USER_TICK: usize = usize::MAX;
+
+static TICK_PC: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(NO_TICK) }; MAX_HARTS];
+
+/// One histogram bucket as `sys_profile` hands them out
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct ProfSample {
+    pub pc: usize,
+    pub count: usize,
+    /// 1 for a kernel pc, 0 for a user one
+    pub kernel: usize,
+}
+
+/// Hits per pc of one task
+#[derive(Default)]
+pub struct Profile {
+    /// ticks are counted; a stopped profile keeps its samples
+    pub running: bool,
+    /// hits per (kernel, pc)
+    hits: BTreeMap<(bool, usize), usize>,
+}
+
+impl Profile {
+    /// Start over with no samples
+    pub fn start(&mut self) {
+        self.running = true;
+        self.hits.clear();
+    }
+    /// Forget the samples, as they refer to an image exec replaced
+    pub fn reset(&mut self) {
+        self.hits.clear();
+    }
+    /// Ticks at new pcs are dropped once PROFILE_MAX_PCS pcs have hits
+    fn add(&mut self, kernel: bool, pc: usize) {
+        if let Some(count) = self.hits.get_mut(&(kernel, pc)) {
+            *count += 1;
+        } else if self.hits.len() < PROFILE_MAX_PCS {
+            self.hits.insert((kernel, pc), 1);
+        }
+    }
+    pub fn samples(&self) -> Vec<ProfSample> {
+        self.hits
+            .iter()
+            .map(|(&(kernel, pc), &count)| ProfSample {
+                pc,
+                count,
+                kernel: kernel as usize,
+            })
+            .collect()
+    }
+}
+
+/// Called by the machine-mode timer handler of `hart` with the kernel pc
+/// it interrupted, None if it came from user mode
+pub fn note_tick(hart: usize, kernel_pc: Option<usize>) {
+    TICK_PC[hart].store(kernel_pc.unwrap_or(USER_TICK), Ordering::Relaxed);
+}
+
+/// Forget the tick noted on this hart, it found the hart idle
+pub fn discard_tick() {
+    TICK_PC[hart_id()].store(NO_TICK, Ordering::Relaxed);
+}
+
+/// Charge the tick noted on this hart, if any, to the current task, which
+/// was interrupted at `user_pc`
+pub fn sample_current(user_pc: usize) {
+    let pc = TICK_PC[hart_id()].swap(NO_TICK, Ordering::Relaxed);
+    if pc == NO_TICK {
+        return;
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if !inner.profile.running {
+        return;
+    }
+    if pc == USER_TICK {
+        inner.profile.add(false, user_pc);
+    } else {
+        inner.profile.add(true, pc);
+    }
+}
@@ -0,0 +1,256 @@
+//! Tracing of a child process, as the ptrace syscall sees it
+//!
+//! A traced task stops whenever a signal other than SIGKILL is about to be
+//! delivered to it and reports the stop to its parent, the tracer, through
+//! waitpid. While it is stopped the tracer may read and write its registers
+//! and memory, then resume it with or without the signal.
+//!
+//! RISC-V has no single-step mode for user code, so a single step plants a
+//! `c.ebreak` at every instruction that may run next and removes them all
+//! again once one of them is hit.
+use super::{
+    current_task, notify_parent, SignalFlags, SignalInfo, TaskControlBlockInner, CLD_TRAPPED,
+    TRAP_BRKPT, TRAP_TRACE,
+};
+use crate::mm::{translated_byte_buffer, MemorySet};
+use crate::trap::TrapContext;
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub const PTRACE_TRACEME: usize = 0;
+pub const PTRACE_PEEKTEXT: usize = 1;
+pub const PTRACE_PEEKDATA: usize = 2;
+pub const PTRACE_POKETEXT: usize = 4;
+pub const PTRACE_POKEDATA: usize = 5;
+pub const PTRACE_CONT: usize = 7;
+pub const PTRACE_KILL: usize = 8;
+pub const PTRACE_SINGLESTEP: usize = 9;
+pub const PTRACE_GETREGS: usize = 12;
+pub const PTRACE_SETREGS: usize = 13;
+pub const PTRACE_ATTACH: usize = 16;
+pub const PTRACE_DETACH: usize = 17;
+
+/// Registers as GETREGS and SETREGS see them: the pc, then x1..x31
+pub type UserRegs = [usize; 32];
+
+/// `c.ebreak`, two bytes so it fits over any instruction
+const C_EBREAK: u16 = 0x9002;
+
+/// Tracing state of a traced task
+pub struct TraceState {
+    /// in a ptrace stop, until the tracer resumes us
+    pub stopped: bool,
+    /// the signal we stopped for
+    pub stop_info: Option<SignalInfo>,
+    /// signals the tracer let through, delivered without stopping again
+    pub passed: SignalFlags,
+    /// single-step breakpoints as (address, original halfword)
+    pub step_bps: Vec<(usize, u16)>,
+}
+
+impl Default for TraceState {
+    fn default() -> Self {
+        Self {
+            stopped: false,
+            stop_info: None,
+            passed: SignalFlags::empty(),
+            step_bps: Vec::new(),
+        }
+    }
+}
+
+/// Copy user memory at `addr` of `memory_set` into `buf`
+pub fn peek_user(memory_set: &MemorySet, addr: usize, buf: &mut [u8]) -> bool {
+    memory_set.read_user(addr, buf).is_ok()
+}
+
+/// Copy `bytes` to user memory at `addr` of `memory_set`, read-only pages
+/// such as the program text included
+pub fn poke_user(memory_set: &mut MemorySet, addr: usize, bytes: &[u8]) -> bool {
+    if !memory_set.prepare_debug_write(addr, bytes.len()) {
+        return false;
+    }
+    let mut offset = 0;
+    for chunk in translated_byte_buffer(memory_set.token(), addr as *const u8, bytes.len()) {
+        chunk.copy_from_slice(&bytes[offset..offset + chunk.len()]);
+        offset += chunk.len();
+    }
+    true
+}
+
+fn peek_u16(memory_set: &MemorySet, addr: usize) -> Option<u16> {
+    let mut buf = [0u8; 2];
+    peek_user(memory_set, addr, &mut buf).then(|| u16::from_le_bytes(buf))
+}
+
+/// Sign-extend the low `bits` bits of `value`
+fn sign_extend(value: u32, bits: u32) -> usize {
+    (((value << (32 - bits)) as i32) >> (32 - bits)) as isize as usize
+}
+
+/// Addresses the instruction at the pc of `cx` may continue at
+fn next_pcs(memory_set: &MemorySet, cx: &TrapContext) -> Option<Vec<usize>> {
+    let pc = cx.sepc;
+    let low = peek_u16(memory_set, pc)? as u32;
+    if low & 0b11 != 0b11 {
+        // compressed instructions
+        let funct3 = low >> 13;
+        let rs1 = ((low >> 7) & 0x1f) as usize;
+        let rs2 = (low >> 2) & 0x1f;
+        let next = match (low & 0b11, funct3) {
+            // c.j
+            (0b01, 0b101) => {
+                let imm = ((low >> 1) & 0x800)
+                    | ((low << 2) & 0x400)
+                    | ((low >> 1) & 0x300)
+                    | ((low << 1) & 0x80)
+                    | ((low >> 1) & 0x40)
+                    | ((low << 3) & 0x20)
+                    | ((low >> 7) & 0x10)
+                    | ((low >> 2) & 0xe);
+                vec![pc.wrapping_add(sign_extend(imm, 12))]
+            }
+            // c.beqz, c.bnez
+            (0b01, 0b110) | (0b01, 0b111) => {
+                let imm = ((low >> 4) & 0x100)
+                    | ((low << 1) & 0xc0)
+                    | ((low << 3) & 0x20)
+                    | ((low >> 7) & 0x18)
+                    | ((low >> 2) & 0x6);
+                vec![pc + 2, pc.wrapping_add(sign_extend(imm, 9))]
+            }
+            // c.jr, c.jalr
+            (0b10, 0b100) if rs2 == 0 && rs1 != 0 => vec![cx.x[rs1] & !1],
+            _ => vec![pc + 2],
+        };
+        return Some(next);
+    }
+    let high = peek_u16(memory_set, pc + 2)? as u32;
+    let inst = low | (high << 16);
+    let next = match inst & 0x7f {
+        // jal
+        0x6f => {
+            let imm = ((inst >> 11) & 0x10_0000)
+                | (inst & 0xf_f000)
+                | ((inst >> 9) & 0x800)
+                | ((inst >> 20) & 0x7fe);
+            vec![pc.wrapping_add(sign_extend(imm, 21))]
+        }
+        // jalr
+        0x67 => {
+            let rs1 = ((inst >> 15) & 0x1f) as usize;
+            vec![cx.x[rs1].wrapping_add(sign_extend(inst >> 20, 12)) & !1]
+        }
+        // conditional branches
+        0x63 => {
+            let imm = ((inst >> 19) & 0x1000)
+                | ((inst << 4) & 0x800)
+                | ((inst >> 20) & 0x7e0)
+                | ((inst >> 7) & 0x1e);
+            vec![pc + 4, pc.wrapping_add(sign_extend(imm, 13))]
+        }
+        _ => vec![pc + 4],
+    };
+    Some(next)
+}
+
+/// Plant a breakpoint at every instruction the stopped, traced `inner` may
+/// run next
+pub fn insert_step_breakpoints(inner: &mut TaskControlBlockInner) -> bool {
+    let cx = inner.get_trap_cx();
+    let Some(mut targets) = next_pcs(&inner.memory_set, cx) else {
+        return false;
+    };
+    targets.dedup();
+    let mut step_bps = Vec::new();
+    for addr in targets {
+        let Some(orig) = peek_u16(&inner.memory_set, addr) else {
+            continue;
+        };
+        if poke_user(&mut inner.memory_set, addr, &C_EBREAK.to_le_bytes()) {
+            step_bps.push((addr, orig));
+        }
+    }
+    let trace = inner.trace.as_mut().unwrap();
+    trace.step_bps = step_bps;
+    !trace.step_bps.is_empty()
+}
+
+/// Put back the instructions single-step breakpoints replaced
+pub fn remove_step_breakpoints(inner: &mut TaskControlBlockInner) {
+    let Some(trace) = inner.trace.as_mut() else {
+        return;
+    };
+    let step_bps = core::mem::take(&mut trace.step_bps);
+    for (addr, orig) in step_bps {
+        poke_user(&mut inner.memory_set, addr, &orig.to_le_bytes());
+    }
+}
+
+/// Let the stopped, traced `inner` run on, delivering signal `sig` unless
+/// it is 0. Fails for a bad signal number.
+pub fn resume(inner: &mut TaskControlBlockInner, sig: usize) -> bool {
+    let Some(signal) = SignalFlags::from_signum(sig) else {
+        return false;
+    };
+    let trace = inner.trace.as_mut().unwrap();
+    trace.stopped = false;
+    let stop_info = trace.stop_info.take();
+    if sig != 0 {
+        trace.passed.insert(signal);
+        let info = match stop_info {
+            Some(info) if info.signo as usize == sig => info,
+            _ => SignalInfo::kernel(signal),
+        };
+        inner.post_signal(info);
+    }
+    inner.frozen = false;
+    inner.stop_report = None;
+    true
+}
+
+/// Stop the current, traced task for signal `sig` instead of delivering
+/// it, and tell the tracer
+pub fn ptrace_stop(sig: usize) {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let info = task_inner.take_signal(sig);
+    let trace = task_inner.trace.as_mut().unwrap();
+    trace.stopped = true;
+    trace.stop_info = Some(info);
+    task_inner.frozen = true;
+    task_inner.stop_report = Some(sig);
+    task_inner.cont_report = false;
+    drop(task_inner);
+    notify_parent(&task, CLD_TRAPPED, sig);
+}
+
+/// Stop tracing `inner` and let it run on
+pub fn detach(inner: &mut TaskControlBlockInner) {
+    remove_step_breakpoints(inner);
+    if let Some(trace) = inner.trace.take() {
+        if trace.stopped {
+            inner.frozen = false;
+            inner.stop_report = None;
+        }
+    }
+}
+
+/// An ebreak trapped: a single step finished, or the program hit a
+/// breakpoint. Either way the task gets a SIGTRAP.
+pub fn handle_breakpoint() {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let pc = task_inner.get_trap_cx().sepc;
+    let stepped = task_inner
+        .trace
+        .as_ref()
+        .is_some_and(|trace| trace.step_bps.iter().any(|(addr, _)| *addr == pc));
+    let code = if stepped {
+        remove_step_breakpoints(&mut task_inner);
+        TRAP_TRACE
+    } else {
+        TRAP_BRKPT
+    };
+    task_inner.post_signal(SignalInfo::new(SignalFlags::SIGTRAP.signum(), code, 0, pc));
+}
@@ -1,9 +1,13 @@
 use bitflags::*;
 
-pub const MAX_SIG: usize = 31;
+pub const MAX_SIG: usize = 63;
+/// First queued real-time signal
+pub const SIGRTMIN: usize = 32;
+/// Last queued real-time signal
+pub const SIGRTMAX: usize = MAX_SIG;
 
 bitflags! {
-    pub struct SignalFlags: u32 {
+    pub struct SignalFlags: u64 {
         const SIGDEF = 1; // Default signal handling
         const SIGHUP = 1 << 1;
         const SIGINT = 1 << 2;
@@ -36,26 +40,233 @@ bitflags! {
         const SIGIO = 1 << 29;
         const SIGPWR = 1 << 30;
         const SIGSYS = 1 << 31;
+        // real-time signals, SIGRTMIN + n
+        const SIGRT0 = 1 << 32;
+        const SIGRT1 = 1 << 33;
+        const SIGRT2 = 1 << 34;
+        const SIGRT3 = 1 << 35;
+        const SIGRT4 = 1 << 36;
+        const SIGRT5 = 1 << 37;
+        const SIGRT6 = 1 << 38;
+        const SIGRT7 = 1 << 39;
+        const SIGRT8 = 1 << 40;
+        const SIGRT9 = 1 << 41;
+        const SIGRT10 = 1 << 42;
+        const SIGRT11 = 1 << 43;
+        const SIGRT12 = 1 << 44;
+        const SIGRT13 = 1 << 45;
+        const SIGRT14 = 1 << 46;
+        const SIGRT15 = 1 << 47;
+        const SIGRT16 = 1 << 48;
+        const SIGRT17 = 1 << 49;
+        const SIGRT18 = 1 << 50;
+        const SIGRT19 = 1 << 51;
+        const SIGRT20 = 1 << 52;
+        const SIGRT21 = 1 << 53;
+        const SIGRT22 = 1 << 54;
+        const SIGRT23 = 1 << 55;
+        const SIGRT24 = 1 << 56;
+        const SIGRT25 = 1 << 57;
+        const SIGRT26 = 1 << 58;
+        const SIGRT27 = 1 << 59;
+        const SIGRT28 = 1 << 60;
+        const SIGRT29 = 1 << 61;
+        const SIGRT30 = 1 << 62;
+        const SIGRT31 = 1 << 63;
     }
 }
 
+/// Handler value asking for the default disposition
+pub const SIG_DFL: usize = 0;
+/// Handler value asking for the signal to be discarded
+pub const SIG_IGN: usize = 1;
+
+/// What happens to a process receiving a signal it has no handler for
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SignalDefault {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+    /// terminate as if by a fatal fault
+    Core,
+}
+
+/// Saved on the user stack while a handler runs, popped by sigreturn
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalFrame {
+    /// user registers when the signal was taken
+    pub x: [usize; 32],
+    pub sepc: usize,
+    /// signal mask to restore
+    pub mask: SignalFlags,
+    /// frame of the handler this one interrupted, 0 if none
+    pub prev: usize,
+    /// passed to SA_SIGINFO handlers as their second argument
+    pub info: SignalInfo,
+}
+
+/// Sent by kill
+pub const SI_USER: i32 = 0;
+/// Raised by the kernel itself
+pub const SI_KERNEL: i32 = 0x80;
+/// Sent by sigqueue
+pub const SI_QUEUE: i32 = -1;
+/// SIGCHLD codes: child exited, stopped, continued
+pub const CLD_EXITED: i32 = 1;
+/// stopped for its tracer
+pub const CLD_TRAPPED: i32 = 4;
+pub const CLD_STOPPED: i32 = 5;
+pub const CLD_CONTINUED: i32 = 6;
+
+/// SIGTRAP codes: hit a breakpoint, finished a single step
+pub const TRAP_BRKPT: i32 = 1;
+pub const TRAP_TRACE: i32 = 2;
+
+/// Most realtime signals a task may have queued at once
+pub const SIGQUEUE_MAX: usize = 64;
+
+/// One pending signal instance, laid out like the head of Linux `siginfo_t`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SignalInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    _pad: i32,
+    /// sending process
+    pub pid: i32,
+    pub uid: u32,
+    /// sigqueue value, or child status for SIGCHLD
+    pub value: usize,
+}
+
+impl SignalInfo {
+    pub fn new(signo: usize, code: i32, pid: usize, value: usize) -> Self {
+        Self {
+            signo: signo as i32,
+            code,
+            pid: pid as i32,
+            value,
+            ..Default::default()
+        }
+    }
+
+    /// Info for a signal raised by the kernel
+    pub fn kernel(signal: SignalFlags) -> Self {
+        Self::new(signal.signum(), SI_KERNEL, 0, 0)
+    }
+}
+
+/// Alternate signal stack is in use
+pub const SS_ONSTACK: u32 = 1;
+/// No alternate signal stack
+pub const SS_DISABLE: u32 = 2;
+
+/// Argument of sigaltstack, laid out like Linux `stack_t`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SignalStack {
+    pub sp: usize,
+    pub flags: u32,
+    pub size: usize,
+}
+
+/// Message printed when a process is killed by each signal
+const TERMINATION_MESSAGES: [&str; SIGRTMIN] = [
+    "Killed, SIGDEF=0",
+    "Hangup, SIGHUP=1",
+    "Killed, SIGINT=2",
+    "Quit, SIGQUIT=3",
+    "Illegal Instruction, SIGILL=4",
+    "Trace/Breakpoint Trap, SIGTRAP=5",
+    "Aborted, SIGABRT=6",
+    "Bus Error, SIGBUS=7",
+    "Erroneous Arithmetic Operation, SIGFPE=8",
+    "Killed, SIGKILL=9",
+    "User Defined Signal 1, SIGUSR1=10",
+    "Segmentation Fault, SIGSEGV=11",
+    "User Defined Signal 2, SIGUSR2=12",
+    "Broken Pipe, SIGPIPE=13",
+    "Alarm Clock, SIGALRM=14",
+    "Terminated, SIGTERM=15",
+    "Stack Fault, SIGSTKFLT=16",
+    "Child Exited, SIGCHLD=17",
+    "Continued, SIGCONT=18",
+    "Stopped, SIGSTOP=19",
+    "Stopped, SIGTSTP=20",
+    "Stopped, SIGTTIN=21",
+    "Stopped, SIGTTOU=22",
+    "Urgent I/O Condition, SIGURG=23",
+    "CPU Time Limit Exceeded, SIGXCPU=24",
+    "File Size Limit Exceeded, SIGXFSZ=25",
+    "Virtual Timer Expired, SIGVTALRM=26",
+    "Profiling Timer Expired, SIGPROF=27",
+    "Window Changed, SIGWINCH=28",
+    "I/O Possible, SIGIO=29",
+    "Power Failure, SIGPWR=30",
+    "Bad System Call, SIGSYS=31",
+];
+
 impl SignalFlags {
-    pub fn check_error(&self) -> Option<(i32, &'static str)> {
-        if self.contains(Self::SIGINT) {
-            Some((-2, "Killed, SIGINT=2"))
-        } else if self.contains(Self::SIGILL) {
-            Some((-4, "Illegal Instruction, SIGILL=4"))
-        } else if self.contains(Self::SIGABRT) {
-            Some((-6, "Aborted, SIGABRT=6"))
-        } else if self.contains(Self::SIGFPE) {
-            Some((-8, "Erroneous Arithmetic Operation, SIGFPE=8"))
-        } else if self.contains(Self::SIGKILL) {
-            Some((-9, "Killed, SIGKILL=9"))
-        } else if self.contains(Self::SIGSEGV) {
-            Some((-11, "Segmentation Fault, SIGSEGV=11"))
-        } else {
-            //println!("[K] signalflags check_error  {:?}", self);
+    /// Number of a single signal
+    pub fn signum(&self) -> usize {
+        self.bits().trailing_zeros() as usize
+    }
+
+    /// Flag of signal number `sig`
+    pub fn from_signum(sig: usize) -> Option<Self> {
+        if sig > MAX_SIG {
             None
+        } else {
+            Self::from_bits(1 << sig)
         }
     }
+
+    /// Real-time signals queue every instance, standard ones at most one
+    pub fn is_realtime(&self) -> bool {
+        self.signum() >= SIGRTMIN
+    }
+
+    /// Signals whose default action stops the process
+    pub fn is_stop(&self) -> bool {
+        self.intersects(Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU)
+    }
+
+    /// POSIX default disposition of a single signal
+    pub fn default_action(&self) -> SignalDefault {
+        if self.is_stop() {
+            SignalDefault::Stop
+        } else if *self == Self::SIGCONT {
+            SignalDefault::Continue
+        } else if self.intersects(Self::SIGDEF | Self::SIGCHLD | Self::SIGURG | Self::SIGWINCH) {
+            SignalDefault::Ignore
+        } else if self.intersects(
+            Self::SIGQUIT
+                | Self::SIGILL
+                | Self::SIGTRAP
+                | Self::SIGABRT
+                | Self::SIGBUS
+                | Self::SIGFPE
+                | Self::SIGSEGV
+                | Self::SIGXCPU
+                | Self::SIGXFSZ
+                | Self::SIGSYS,
+        ) {
+            SignalDefault::Core
+        } else {
+            SignalDefault::Terminate
+        }
+    }
+}
+
+/// Exit code and message of a process killed by signal `sig`
+pub fn termination(sig: usize) -> (i32, &'static str) {
+    let message = if sig < SIGRTMIN {
+        TERMINATION_MESSAGES[sig]
+    } else {
+        "Real-time Signal, SIGRTMIN..=SIGRTMAX"
+    };
+    (-(sig as i32), message)
 }
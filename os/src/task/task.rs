@@ -1,20 +1,54 @@
 //!Implementation of [`TaskControlBlock`]
-use super::{KernelStack, PidHandle, pid_alloc, SignalFlags};
-use super::{SignalActions, TaskContext};
-use crate::config::{TRAP_CONTEXT, kernel_stack_position};
+use super::{KernelStack, PidHandle, pid_alloc, SignalFlags, SignalInfo, TraceState, SIGQUEUE_MAX};
+use super::{SignalAction, SignalActions, SignalDefault, TaskContext, MAX_SIG, SIG_DFL, SIG_IGN};
+use super::Profile;
+use crate::config::{
+    CLOCK_FREQ, PAGE_SIZE, TRAP_CONTEXT, USER_STACK_LIMIT, USER_STACK_SIZE, kernel_stack_position,
+};
+use crate::errno::{Errno, SysResult};
 use crate::fs::{File, Stdin, Stdout};
-use crate::mm::{KERNEL_SPACE, MemorySet, PhysPageNum, VirtAddr, translated_refmut};
-use crate::sync::UPSafeCell;
+use crate::perf::TaskCounters;
+use crate::mm::{ElfInfo, KERNEL_SPACE, MemorySet, PhysPageNum, VirtAddr, translated_refmut};
+use crate::sync::{SpinMutex, SpinMutexGuard};
+use crate::timer::{get_time, ITimer, TimeVal, ITIMER_PROF, ITIMER_VIRTUAL};
 use crate::trap::{TrapContext, trap_handler_s};
 use alloc::string::String;
+use alloc::collections::VecDeque;
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
 use alloc::vec::Vec;
-use core::cell::RefMut;
+
+/// Auxiliary vector entry types
+pub const AT_NULL: usize = 0;
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_ENTRY: usize = 9;
+pub const AT_RANDOM: usize = 25;
+
+/// Resource limits, numbered as on Linux
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_NPROC: usize = 6;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIM_NLIMITS: usize = 16;
+pub const RLIM_INFINITY: usize = usize::MAX;
+/// Default limits on open files and live children
+const NOFILE_DEFAULT: usize = 256;
+const NOFILE_MAX: usize = 1024;
+const NPROC_DEFAULT: usize = 128;
+/// Largest core file written unless RLIMIT_CORE is raised
+const CORE_DEFAULT: usize = 1 << 20;
+
 pub struct TaskControlBlock {
-    pub pid: PidHandle,
+    /// dropped before `pid`: once the pid is free, another hart may map a
+    /// new kernel stack at the same place
     pub kernel_stack: KernelStack,
-    inner: UPSafeCell<TaskControlBlockInner>,
+    pub pid: PidHandle,
+    inner: SpinMutex<TaskControlBlockInner>,
 }
 
 pub struct TaskControlBlockInner {
@@ -23,18 +57,196 @@ pub struct TaskControlBlockInner {
     pub base_size: usize,
     pub task_cx: TaskContext,
     pub task_status: TaskStatus,
+    /// still on a hart's kernel stack, set until the switch away completes
+    pub on_cpu: bool,
     pub memory_set: MemorySet,
     pub parent: Option<Weak<TaskControlBlock>>,
     pub children: Vec<Arc<TaskControlBlock>>,
     pub exit_code: i32,
+    pub pgid: usize,
+    pub sid: usize,
+    pub cred: Credentials,
+    /// soft and hard resource limits, inherited across fork and exec
+    pub rlimits: [RLimit; RLIM_NLIMITS],
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    /// signals with at least one instance in `sig_queue`
     pub signals: SignalFlags,
+    pub sig_queue: VecDeque<SignalInfo>,
     pub signal_mask: SignalFlags,
-    pub handling_sig: isize,
+    /// mask to restore once a signal interrupts sigsuspend
+    pub saved_mask: Option<SignalFlags>,
+    /// signals a blocked sigtimedwait is waiting for, blocked or not
+    pub sig_wait: SignalFlags,
     pub signal_actions: SignalActions,
-    pub killed: bool,
+    /// signal that is terminating us
+    pub killed: Option<usize>,
     pub frozen: bool,
-    pub trap_ctx_backup: Option<TrapContext>,
+    /// signal that stopped us, until the parent has seen it in waitpid
+    pub stop_report: Option<usize>,
+    /// continued since the parent last looked
+    pub cont_report: bool,
+    /// innermost signal frame on the user stack, 0 outside handlers
+    pub sig_frame: usize,
+    /// alternate signal stack as (base, size)
+    pub altstack: Option<(usize, usize)>,
+    /// resources used by this task
+    pub usage: ResourceUsage,
+    /// resources used by reaped descendants
+    pub child_usage: ResourceUsage,
+    /// start of the interval not yet charged to user or kernel time
+    pub time_stamp: usize,
+    /// ITIMER_REAL, ITIMER_VIRTUAL and ITIMER_PROF
+    pub itimers: [ITimer; 3],
+    /// set while our parent traces us
+    pub trace: Option<TraceState>,
+    /// log every syscall we make, inherited by children
+    pub strace: bool,
+    /// sampling profile, kept across exec but not inherited
+    pub profile: Profile,
+    /// hardware counts while on a hart, for perf events
+    pub perf: TaskCounters,
+    /// syscall ABI of the running image
+    pub personality: Personality,
+    /// the heap (brk) area starts here, right above the user stack
+    pub heap_bottom: usize,
+    pub program_brk: usize,
+}
+
+/// Syscall ABI a program expects, decided at exec from its ELF
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Personality {
+    /// linked against our user library, tagged with a `.note.acore` note
+    Native,
+    /// anything else, e.g. static musl binaries, gets the Linux numbering
+    Linux,
+}
+
+impl Personality {
+    fn of(elf_info: &ElfInfo) -> Self {
+        if elf_info.native {
+            Self::Native
+        } else {
+            Self::Linux
+        }
+    }
+}
+
+/// User and group ids of a task. Root (uid 0) may do anything.
+#[derive(Clone, Copy, Default)]
+pub struct Credentials {
+    pub uid: u32,
+    pub euid: u32,
+    pub gid: u32,
+    pub egid: u32,
+}
+
+impl Credentials {
+    pub fn is_root(&self) -> bool {
+        self.euid == 0
+    }
+}
+
+/// A soft limit `cur` that the task may raise up to the hard limit `max`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+impl RLimit {
+    pub const INFINITY: Self = Self {
+        cur: RLIM_INFINITY,
+        max: RLIM_INFINITY,
+    };
+}
+
+/// Limits of the initial process
+fn default_rlimits() -> [RLimit; RLIM_NLIMITS] {
+    let mut rlimits = [RLimit::INFINITY; RLIM_NLIMITS];
+    rlimits[RLIMIT_STACK] = RLimit {
+        cur: USER_STACK_SIZE,
+        max: USER_STACK_LIMIT,
+    };
+    rlimits[RLIMIT_CORE] = RLimit {
+        cur: CORE_DEFAULT,
+        max: RLIM_INFINITY,
+    };
+    rlimits[RLIMIT_NPROC] = RLimit {
+        cur: NPROC_DEFAULT,
+        max: NPROC_DEFAULT,
+    };
+    rlimits[RLIMIT_NOFILE] = RLimit {
+        cur: NOFILE_DEFAULT,
+        max: NOFILE_MAX,
+    };
+    rlimits
+}
+
+/// Resource counters of a task, as reported by getrusage
+#[derive(Clone, Copy, Default)]
+pub struct ResourceUsage {
+    /// CPU time in user and kernel mode, in clock ticks
+    pub utime: usize,
+    pub stime: usize,
+    /// page faults served without I/O, i.e. copy-on-write; none need I/O,
+    /// as files are read in when they are mapped
+    pub minflt: usize,
+    /// switches away to wait, and preemptions
+    pub nvcsw: usize,
+    pub nivcsw: usize,
+}
+
+/// `struct rusage` as returned by getrusage
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct RUsage {
+    pub utime: TimeVal,
+    pub stime: TimeVal,
+    pub maxrss: usize,
+    pub ixrss: usize,
+    pub idrss: usize,
+    pub isrss: usize,
+    pub minflt: usize,
+    pub majflt: usize,
+    pub nswap: usize,
+    pub inblock: usize,
+    pub oublock: usize,
+    pub msgsnd: usize,
+    pub msgrcv: usize,
+    pub nsignals: usize,
+    pub nvcsw: usize,
+    pub nivcsw: usize,
+}
+
+/// `struct tms` as returned by times, in clock_t units
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+impl ResourceUsage {
+    pub fn to_rusage(&self) -> RUsage {
+        RUsage {
+            utime: TimeVal::from_ticks(self.utime),
+            stime: TimeVal::from_ticks(self.stime),
+            minflt: self.minflt,
+            nvcsw: self.nvcsw,
+            nivcsw: self.nivcsw,
+            ..RUsage::default()
+        }
+    }
+    pub fn add(&mut self, other: &ResourceUsage) {
+        self.utime += other.utime;
+        self.stime += other.stime;
+        self.minflt += other.minflt;
+        self.nvcsw += other.nvcsw;
+        self.nivcsw += other.nivcsw;
+    }
 }
 
 impl TaskControlBlockInner {
@@ -50,110 +262,266 @@ impl TaskControlBlockInner {
     pub fn is_zombie(&self) -> bool {
         self.get_status() == TaskStatus::Zombie
     }
-    pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
-            fd
+    /// Queue one signal instance. A standard signal that is already
+    /// pending is merged into it, one that is not is always queued, so
+    /// queued realtime signals cannot keep out SIGKILL; returns false if
+    /// the queue is full for a realtime one.
+    pub fn post_signal(&mut self, info: SignalInfo) -> bool {
+        let signal = SignalFlags::from_signum(info.signo as usize).unwrap();
+        if !signal.is_realtime() && self.signals.contains(signal) {
+            return true;
+        }
+        if signal.is_realtime() && self.sig_queue.len() >= SIGQUEUE_MAX {
+            return false;
+        }
+        self.sig_queue.push_back(info);
+        self.signals.insert(signal);
+        true
+    }
+    /// Dequeue the oldest instance of signal `sig`
+    pub fn take_signal(&mut self, sig: usize) -> SignalInfo {
+        let signal = SignalFlags::from_signum(sig).unwrap();
+        let info = match self.sig_queue.iter().position(|info| info.signo as usize == sig) {
+            Some(idx) => self.sig_queue.remove(idx).unwrap(),
+            None => SignalInfo::kernel(signal),
+        };
+        if !self.sig_queue.iter().any(|info| info.signo as usize == sig) {
+            self.signals.remove(signal);
+        }
+        info
+    }
+    /// Pending, unblocked signals that would not just be ignored when
+    /// we return to user mode
+    pub fn deliverable_signals(&self) -> SignalFlags {
+        let mut deliverable = SignalFlags::empty();
+        for sig in 1..=MAX_SIG {
+            let signal = SignalFlags::from_signum(sig).unwrap();
+            if !self.signals.contains(signal) || self.signal_mask.contains(signal) {
+                continue;
+            }
+            let ignored = match self.signal_actions.table[sig].handler {
+                SIG_IGN => true,
+                SIG_DFL => matches!(
+                    signal.default_action(),
+                    SignalDefault::Ignore | SignalDefault::Continue
+                ),
+                _ => false,
+            };
+            if !ignored {
+                deliverable |= signal;
+            }
+        }
+        deliverable
+    }
+    /// Install `action` for signal `sig`; a pending signal that is now
+    /// ignored is discarded. Returns the previous action.
+    pub fn set_signal_action(&mut self, sig: usize, action: SignalAction) -> SignalAction {
+        let signal = SignalFlags::from_signum(sig).unwrap();
+        let prev_action = core::mem::replace(&mut self.signal_actions.table[sig], action);
+        if action.handler == SIG_IGN
+            || (action.handler == SIG_DFL && signal.default_action() == SignalDefault::Ignore)
+        {
+            self.discard_signals(signal);
+        }
+        prev_action
+    }
+    /// Drop every pending instance of the signals in `signals`
+    pub fn discard_signals(&mut self, signals: SignalFlags) {
+        self.sig_queue.retain(|info| {
+            !signals.contains(SignalFlags::from_signum(info.signo as usize).unwrap())
+        });
+        self.signals.remove(signals);
+    }
+    /// Charge the time since the last stamp to user or kernel mode and
+    /// run down the CPU-time interval timers
+    pub fn charge_time(&mut self, user: bool) {
+        let now = get_time();
+        let delta = now.saturating_sub(self.time_stamp);
+        self.time_stamp = now;
+        if user {
+            self.usage.utime += delta;
+            self.tick_itimer(ITIMER_VIRTUAL, delta, SignalFlags::SIGVTALRM);
         } else {
+            self.usage.stime += delta;
+        }
+        self.tick_itimer(ITIMER_PROF, delta, SignalFlags::SIGPROF);
+        self.check_cpu_limit(delta);
+    }
+    /// SIGXCPU once the soft CPU limit is reached and every second after,
+    /// SIGKILL at the hard limit
+    fn check_cpu_limit(&mut self, delta: usize) {
+        let limit = self.rlimits[RLIMIT_CPU];
+        if limit.cur == RLIM_INFINITY {
+            return;
+        }
+        let used = self.usage.utime + self.usage.stime;
+        let (before, after) = ((used - delta) / CLOCK_FREQ, used / CLOCK_FREQ);
+        if limit.max != RLIM_INFINITY && after >= limit.max {
+            self.post_signal(SignalInfo::kernel(SignalFlags::SIGKILL));
+        } else if after > before && after >= limit.cur {
+            self.post_signal(SignalInfo::kernel(SignalFlags::SIGXCPU));
+        }
+    }
+    fn tick_itimer(&mut self, which: usize, delta: usize, signal: SignalFlags) {
+        let timer = &mut self.itimers[which];
+        if timer.value == 0 {
+            return;
+        }
+        if timer.value > delta {
+            timer.value -= delta;
+            return;
+        }
+        // a zero interval disarms the timer
+        timer.value = timer.interval;
+        self.post_signal(SignalInfo::kernel(signal));
+    }
+    /// Lowest free fd, None once RLIMIT_NOFILE fds are open
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        let limit = self.rlimits[RLIMIT_NOFILE].cur;
+        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
+            (fd < limit).then_some(fd)
+        } else if self.fd_table.len() < limit {
             self.fd_table.push(None);
-            self.fd_table.len() - 1
+            Some(self.fd_table.len() - 1)
+        } else {
+            None
         }
     }
+    /// Whether RLIMIT_NPROC allows another child
+    pub fn may_add_child(&self) -> bool {
+        self.children.len() < self.rlimits[RLIMIT_NPROC].cur
+    }
+    /// Size of the user stack for the next exec, from RLIMIT_STACK
+    pub fn user_stack_size(&self) -> usize {
+        let size = self.rlimits[RLIMIT_STACK].cur.min(USER_STACK_LIMIT);
+        (size.div_ceil(PAGE_SIZE) * PAGE_SIZE).max(PAGE_SIZE)
+    }
 }
 
 impl TaskControlBlock {
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
-        self.inner.exclusive_access()
+    #[cfg_attr(feature = "lockdep", track_caller)]
+    pub fn inner_exclusive_access(&self) -> SpinMutexGuard<'_, TaskControlBlockInner> {
+        self.inner.lock()
     }
     pub fn new(elf_data: &[u8]) -> Self {
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, user_sp, elf_info) =
+            MemorySet::from_elf(elf_data, USER_STACK_SIZE).unwrap();
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
         let pid_handle = pid_alloc();
+        let user_sp = init_user_stack(&memory_set, user_sp, &[], &[], &elf_info, pid_handle.0);
+        let pid = pid_handle.0;
         let kernel_stack = KernelStack::new(&pid_handle);
         let kernel_stack_top = kernel_stack.get_top();
         let task_control_block = Self {
             pid: pid_handle,
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    trap_cx_ppn,
-                    base_size: user_sp,
-                    task_cx: TaskContext::goto_trap_return_s(kernel_stack_top),
-                    task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: vec![
-                        Some(Arc::new(Stdin)),
-                        Some(Arc::new(Stdout)),
-                        Some(Arc::new(Stdout)),
-                    ],
-                    signals: SignalFlags::empty(),
-                    signal_mask: SignalFlags::empty(),
-                    handling_sig: -1,
-                    signal_actions: SignalActions::default(),
-                    killed: false,
-                    frozen: false,
-                    trap_ctx_backup: None,
-                })
-            },
+            inner: SpinMutex::new(TaskControlBlockInner {
+                trap_cx_ppn,
+                base_size: user_sp,
+                task_cx: TaskContext::goto_trap_return_s(kernel_stack_top),
+                task_status: TaskStatus::Ready,
+                on_cpu: false,
+                memory_set,
+                parent: None,
+                children: Vec::new(),
+                exit_code: 0,
+                pgid: pid,
+                sid: pid,
+                cred: Credentials::default(),
+                rlimits: default_rlimits(),
+                fd_table: vec![
+                    Some(Arc::new(Stdin)),
+                    Some(Arc::new(Stdout)),
+                    Some(Arc::new(Stdout)),
+                ],
+                signals: SignalFlags::empty(),
+                sig_queue: VecDeque::new(),
+                signal_mask: SignalFlags::empty(),
+                saved_mask: None,
+                sig_wait: SignalFlags::empty(),
+                signal_actions: SignalActions::default(),
+                killed: None,
+                frozen: false,
+                stop_report: None,
+                cont_report: false,
+                sig_frame: 0,
+                altstack: None,
+                usage: ResourceUsage::default(),
+                child_usage: ResourceUsage::default(),
+                time_stamp: get_time(),
+                itimers: [ITimer::default(); 3],
+                trace: None,
+                strace: false,
+                profile: Profile::default(),
+                perf: TaskCounters::default(),
+                personality: Personality::of(&elf_info),
+                heap_bottom: elf_info.heap_bottom,
+                program_brk: elf_info.heap_bottom,
+            }),
         };
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
         *trap_cx = TrapContext::app_init_context(
-            entry_point,
+            elf_info.entry,
             user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock().token(),
             kernel_stack_top,
             trap_handler_s as usize,
         );
         task_control_block
     }
-    pub fn exec(&self, elf_data: &[u8], args: Vec<String>) {
-        let (memory_set,mut user_sp, entry_point) = MemorySet::from_elf(elf_data);
+    /// Replace our image, fails leaving it intact with E2BIG if `args`
+    /// and `envs` do not fit on the user stack, or ENOMEM if the new image
+    /// would exceed RLIMIT_AS
+    pub fn exec(&self, elf_data: &[u8], args: Vec<String>, envs: Vec<String>) -> SysResult<()> {
+        let (stack_size, as_limit) = {
+            let inner = self.inner_exclusive_access();
+            (inner.user_stack_size(), inner.rlimits[RLIMIT_AS].cur)
+        };
+        if user_stack_needed(&args, &envs) > stack_size {
+            return Err(Errno::E2BIG);
+        }
+        let (memory_set, user_sp, elf_info) = MemorySet::from_elf(elf_data, stack_size)?;
+        if memory_set.user_size() > as_limit {
+            return Err(Errno::ENOMEM);
+        }
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
-        // push arguments on user stack
-        user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
-        let argv_base = user_sp;
-        let mut argv: Vec<_> = (0..=args.len())
-            .map(|arg| {
-                translated_refmut(
-                    memory_set.token(),
-                    (argv_base + arg * core::mem::size_of::<usize>()) as *mut usize,
-                )
-            })
-            .collect();
-        *argv[args.len()] = 0;
-        for i in 0..args.len() {
-            user_sp -= args[i].len() + 1;
-            *argv[i] = user_sp;
-            let mut p = user_sp;
-            for c in args[i].as_bytes() {
-                *translated_refmut(memory_set.token(), p as *mut u8) = *c;
-                p += 1;
-            }
-            *translated_refmut(memory_set.token(), p as *mut u8) = 0;
-        }
-        user_sp -= user_sp % core::mem::size_of::<usize>();
+        let user_sp = init_user_stack(&memory_set, user_sp, &args, &envs, &elf_info, self.getpid());
 
         let mut inner = self.inner_exclusive_access();
         inner.memory_set = memory_set;
         inner.trap_cx_ppn = trap_cx_ppn;
-        let mut trap_cx = TrapContext::app_init_context(
-            entry_point,
+        // handlers live in the old image, ignored signals stay ignored
+        for action in inner.signal_actions.table.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+        inner.sig_frame = 0;
+        inner.altstack = None;
+        inner.personality = Personality::of(&elf_info);
+        inner.heap_bottom = elf_info.heap_bottom;
+        inner.program_brk = elf_info.heap_bottom;
+        inner.profile.reset();
+        // breakpoints went away with the old image; stop for the tracer
+        // before the first instruction of the new one
+        if let Some(trace) = inner.trace.as_mut() {
+            trace.step_bps.clear();
+            inner.post_signal(SignalInfo::kernel(SignalFlags::SIGTRAP));
+        }
+        *inner.get_trap_cx() = TrapContext::app_init_context(
+            elf_info.entry,
             user_sp,
-            KERNEL_SPACE.exclusive_access().token(),
+            KERNEL_SPACE.lock().token(),
             self.kernel_stack.get_top(),
             trap_handler_s as usize,
         );
-        trap_cx.x[10] = args.len();
-        trap_cx.x[11] = argv_base;
-        *inner.get_trap_cx() = trap_cx;
+        Ok(())
     }
     pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
         let mut parent_inner = self.inner_exclusive_access();
@@ -177,41 +545,238 @@ impl TaskControlBlock {
         let task_control_block: Arc<TaskControlBlock> = Arc::new(TaskControlBlock {
             pid: pid_handle,
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    trap_cx_ppn,
-                    base_size: parent_inner.base_size,
-                    task_cx: TaskContext::goto_trap_return_s(kernel_stack_top),
-                    task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: new_fd_table,
-                    signals: SignalFlags::empty(),
-                    signal_mask: parent_inner.signal_mask,
-                    handling_sig: -1,
-                    signal_actions: parent_inner.signal_actions.clone(),
-                    killed: false,
-                    frozen: false,
-                    trap_ctx_backup: None,
-                })
-            },
+            inner: SpinMutex::new(TaskControlBlockInner {
+                trap_cx_ppn,
+                base_size: parent_inner.base_size,
+                task_cx: TaskContext::goto_trap_return_s(kernel_stack_top),
+                task_status: TaskStatus::Ready,
+                on_cpu: false,
+                memory_set,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                pgid: parent_inner.pgid,
+                sid: parent_inner.sid,
+                cred: parent_inner.cred,
+                rlimits: parent_inner.rlimits,
+                fd_table: new_fd_table,
+                signals: SignalFlags::empty(),
+                sig_queue: VecDeque::new(),
+                signal_mask: parent_inner.signal_mask,
+                saved_mask: None,
+                sig_wait: SignalFlags::empty(),
+                signal_actions: parent_inner.signal_actions.clone(),
+                killed: None,
+                frozen: false,
+                stop_report: None,
+                cont_report: false,
+                sig_frame: parent_inner.sig_frame,
+                altstack: parent_inner.altstack,
+                usage: ResourceUsage::default(),
+                child_usage: ResourceUsage::default(),
+                time_stamp: get_time(),
+                // timers are not inherited
+                itimers: [ITimer::default(); 3],
+                trace: None,
+                strace: parent_inner.strace,
+                profile: Profile::default(),
+                perf: TaskCounters::default(),
+                personality: parent_inner.personality,
+                heap_bottom: parent_inner.heap_bottom,
+                program_brk: parent_inner.program_brk,
+            }),
         });
         parent_inner.children.push(task_control_block.clone());
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
         trap_cx.kernel_sp = kernel_stack_top;
         task_control_block
     }
+    /// Start a child running `elf_data` with `args` directly, without
+    /// copying our address space first. It shares our open files, process
+    /// group and signal mask, and keeps only ignored signals as exec does.
+    /// Fails as exec does.
+    pub fn spawn(
+        self: &Arc<TaskControlBlock>,
+        elf_data: &[u8],
+        args: Vec<String>,
+        envs: Vec<String>,
+    ) -> SysResult<Arc<TaskControlBlock>> {
+        let (stack_size, as_limit) = {
+            let inner = self.inner_exclusive_access();
+            (inner.user_stack_size(), inner.rlimits[RLIMIT_AS].cur)
+        };
+        if user_stack_needed(&args, &envs) > stack_size {
+            return Err(Errno::E2BIG);
+        }
+        let (memory_set, user_sp, elf_info) = MemorySet::from_elf(elf_data, stack_size)?;
+        if memory_set.user_size() > as_limit {
+            return Err(Errno::ENOMEM);
+        }
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+        let pid_handle = pid_alloc();
+        let user_sp = init_user_stack(&memory_set, user_sp, &args, &envs, &elf_info, pid_handle.0);
+        let kernel_stack = KernelStack::new(&pid_handle);
+        let kernel_stack_top = kernel_stack.get_top();
+
+        let mut parent_inner = self.inner_exclusive_access();
+        let mut signal_actions = parent_inner.signal_actions.clone();
+        for action in signal_actions.table.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+        let task_control_block: Arc<TaskControlBlock> = Arc::new(TaskControlBlock {
+            pid: pid_handle,
+            kernel_stack,
+            inner: SpinMutex::new(TaskControlBlockInner {
+                trap_cx_ppn,
+                base_size: user_sp,
+                task_cx: TaskContext::goto_trap_return_s(kernel_stack_top),
+                task_status: TaskStatus::Ready,
+                on_cpu: false,
+                memory_set,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                pgid: parent_inner.pgid,
+                sid: parent_inner.sid,
+                cred: parent_inner.cred,
+                rlimits: parent_inner.rlimits,
+                fd_table: parent_inner.fd_table.clone(),
+                signals: SignalFlags::empty(),
+                sig_queue: VecDeque::new(),
+                signal_mask: parent_inner.signal_mask,
+                saved_mask: None,
+                sig_wait: SignalFlags::empty(),
+                signal_actions,
+                killed: None,
+                frozen: false,
+                stop_report: None,
+                cont_report: false,
+                sig_frame: 0,
+                altstack: None,
+                usage: ResourceUsage::default(),
+                child_usage: ResourceUsage::default(),
+                time_stamp: get_time(),
+                itimers: [ITimer::default(); 3],
+                trace: None,
+                strace: parent_inner.strace,
+                profile: Profile::default(),
+                perf: TaskCounters::default(),
+                personality: Personality::of(&elf_info),
+                heap_bottom: elf_info.heap_bottom,
+                program_brk: elf_info.heap_bottom,
+            }),
+        });
+        parent_inner.children.push(task_control_block.clone());
+        drop(parent_inner);
+        *task_control_block.inner_exclusive_access().get_trap_cx() = TrapContext::app_init_context(
+            elf_info.entry,
+            user_sp,
+            KERNEL_SPACE.lock().token(),
+            kernel_stack_top,
+            trap_handler_s as usize,
+        );
+        Ok(task_control_block)
+    }
     pub fn getpid(&self) -> usize {
         self.pid.0
     }
         
 }
 
+/// Write `bytes` to user memory at `addr`
+fn write_user_bytes(memory_set: &MemorySet, addr: usize, bytes: &[u8]) {
+    for (i, byte) in bytes.iter().enumerate() {
+        *translated_refmut(memory_set.token(), (addr + i) as *mut u8) = *byte;
+    }
+}
+
+/// Copy `s` and a terminating NUL below `user_sp`, returns its address
+fn push_str(memory_set: &MemorySet, user_sp: &mut usize, s: &str) -> usize {
+    *user_sp -= s.len() + 1;
+    write_user_bytes(memory_set, *user_sp, s.as_bytes());
+    write_user_bytes(memory_set, *user_sp + s.len(), &[0]);
+    *user_sp
+}
+
+/// Entries of the auxiliary vector, AT_NULL included
+const AUXV_LEN: usize = 7;
+
+/// Most bytes `init_user_stack` takes for `args` and `envs`
+fn user_stack_needed(args: &[String], envs: &[String]) -> usize {
+    let word = core::mem::size_of::<usize>();
+    let strings: usize = args.iter().chain(envs).map(|s| s.len() + 1).sum();
+    let words = 1 + args.len() + 1 + envs.len() + 1 + 2 * AUXV_LEN;
+    // AT_RANDOM bytes, and sp rounded down to 16 bytes
+    strings + 16 + words * word + 15
+}
+
+/// Lay out the initial user stack below `user_sp` as the RISC-V psABI
+/// expects, returns the new sp:
+///
+/// ```text
+/// sp -> argc
+///       argv[0..argc], 0
+///       envp[..], 0
+///       auxv (type, value) pairs, AT_NULL
+///       16 random bytes, then the argument and environment strings
+/// ```
+fn init_user_stack(
+    memory_set: &MemorySet,
+    mut user_sp: usize,
+    args: &[String],
+    envs: &[String],
+    elf_info: &ElfInfo,
+    seed: usize,
+) -> usize {
+    let word = core::mem::size_of::<usize>();
+    let argv: Vec<usize> = args.iter().map(|arg| push_str(memory_set, &mut user_sp, arg)).collect();
+    let envp: Vec<usize> = envs.iter().map(|env| push_str(memory_set, &mut user_sp, env)).collect();
+    // seeds the stack protector and the like, not for cryptography
+    user_sp -= 16;
+    let random = user_sp;
+    let mut state = (get_time() ^ seed.rotate_left(32)) as u64 | 1;
+    for i in 0..2 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        write_user_bytes(memory_set, random + i * 8, &state.to_le_bytes());
+    }
+    let auxv: [(usize, usize); AUXV_LEN] = [
+        (AT_PHDR, elf_info.phdr),
+        (AT_PHENT, elf_info.phent),
+        (AT_PHNUM, elf_info.phnum),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_ENTRY, elf_info.entry),
+        (AT_RANDOM, random),
+        (AT_NULL, 0),
+    ];
+    let mut words: Vec<usize> = Vec::new();
+    words.push(args.len());
+    words.extend(argv.iter());
+    words.push(0);
+    words.extend(envp.iter());
+    words.push(0);
+    for (key, value) in auxv.iter() {
+        words.push(*key);
+        words.push(*value);
+    }
+    user_sp = (user_sp - words.len() * word) & !0xf;
+    for (i, value) in words.iter().enumerate() {
+        *translated_refmut(memory_set.token(), (user_sp + i * word) as *mut usize) = *value;
+    }
+    user_sp
+}
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum TaskStatus {
     Ready,
     Running,
+    /// waiting for a signal or timer, off the ready queue
+    Blocked,
     Zombie,
 }
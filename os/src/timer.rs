@@ -1,8 +1,132 @@
 //! RISC-V timer-related functionality
 use crate::board::CLOCK_FREQ;
+use crate::smp::hart_id;
+use crate::sync::SpinMutex;
+use alloc::collections::BinaryHeap;
+use core::cmp::Ordering;
+use lazy_static::*;
 // use riscv::register::time;
 
 const MSEC_PER_SEC: usize = 1000;
+const USEC_PER_SEC: usize = 1_000_000;
+/// times()等使用的clock_t单位，每秒100个
+pub const CLK_TCK: usize = 100;
+
+pub const ITIMER_REAL: usize = 0;
+pub const ITIMER_VIRTUAL: usize = 1;
+pub const ITIMER_PROF: usize = 2;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    /// 换算成时钟周期，非零的时间至少为一个周期；溢出时返回None
+    pub fn to_ticks(&self) -> Option<usize> {
+        let ticks = self
+            .sec
+            .checked_mul(CLOCK_FREQ)?
+            .checked_add(self.usec.checked_mul(CLOCK_FREQ / USEC_PER_SEC)?)?;
+        if ticks == 0 && (self.sec != 0 || self.usec != 0) {
+            Some(1)
+        } else {
+            Some(ticks)
+        }
+    }
+    pub fn from_ticks(ticks: usize) -> Self {
+        Self {
+            sec: ticks / CLOCK_FREQ,
+            usec: ticks % CLOCK_FREQ / (CLOCK_FREQ / USEC_PER_SEC),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ITimerVal {
+    pub interval: TimeVal,
+    pub value: TimeVal,
+}
+
+/// 进程的一个间隔定时器，单位为时钟周期，value为0表示未启用。
+/// ITIMER_REAL的value是到期的绝对时间，另外两个是剩余的CPU时间
+#[derive(Clone, Copy, Default)]
+pub struct ITimer {
+    pub value: usize,
+    pub interval: usize,
+}
+
+/// 定时器到期时要做的事
+enum TimerKind {
+    /// 进程的ITIMER_REAL
+    Real,
+    /// 唤醒阻塞等待超时的进程
+    Wakeup,
+}
+
+/// 等待到期的定时器
+struct TimerEntry {
+    expire: usize,
+    pid: usize,
+    kind: TimerKind,
+}
+
+impl PartialEq for TimerEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire == other.expire
+    }
+}
+impl Eq for TimerEntry {}
+impl PartialOrd for TimerEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for TimerEntry {
+    // 小顶堆，最早到期的在堆顶
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.expire.cmp(&self.expire)
+    }
+}
+
+lazy_static! {
+    static ref TIMERS: SpinMutex<BinaryHeap<TimerEntry>> = SpinMutex::new(BinaryHeap::new());
+}
+
+/// 登记进程`pid`在`expire`到期的实时定时器
+pub fn add_timer(expire: usize, pid: usize) {
+    TIMERS.lock().push(TimerEntry { expire, pid, kind: TimerKind::Real });
+}
+
+/// 在`expire`时唤醒阻塞中的进程`pid`，被唤醒的进程自行检查是否超时
+pub fn add_wakeup_timer(expire: usize, pid: usize) {
+    TIMERS.lock().push(TimerEntry { expire, pid, kind: TimerKind::Wakeup });
+}
+
+/// 取出所有已到期的定时器并通知对应进程。
+/// 被重设或取消的定时器留下的旧条目由`fire_real_timer`丢弃
+pub fn check_timer() {
+    let now = get_time();
+    loop {
+        let mut timers = TIMERS.lock();
+        let entry = match timers.peek() {
+            Some(entry) if entry.expire <= now => timers.pop().unwrap(),
+            _ => break,
+        };
+        drop(timers);
+        match entry.kind {
+            TimerKind::Real => crate::task::fire_real_timer(entry.pid, entry.expire),
+            TimerKind::Wakeup => {
+                if let Some(task) = crate::task::pid2task(entry.pid) {
+                    crate::task::wakeup_task(&task);
+                }
+            }
+        }
+    }
+}
 
 pub fn get_time() -> usize {
     read_time() as usize
@@ -13,6 +137,11 @@ pub fn get_time_ms() -> usize {
     read_time() as usize / (CLOCK_FREQ / MSEC_PER_SEC)
 }
 
+/// 把时钟周期换算成clock_t
+pub fn ticks_to_clock_t(ticks: usize) -> usize {
+    ticks / (CLOCK_FREQ / CLK_TCK)
+}
+
 /// set the next timer interrupt
 pub fn set_next_trigger() {
     set_timer();
@@ -22,11 +151,12 @@ pub fn read_time() -> u64 {
     unsafe { (MTIME_ADDR as *const u64).read_volatile() }
 }
 
-/// 配置时钟中断
+/// 配置当前 hart 的时钟中断
 pub fn set_timer() {
     const MTIME_ADDR: usize = 0x0200bff8;
     const MTIMECMP_ADDR: usize = 0x02004000;
     const INTERVAL: u64 = 1000000; // 大约1秒
+    let mtimecmp = MTIMECMP_ADDR + 8 * hart_id();
 
     unsafe {
         // 读取当前时间
@@ -34,6 +164,6 @@ pub fn set_timer() {
 
         // 设置下一次中断时间
         let next_time = mtime + INTERVAL;
-        (MTIMECMP_ADDR as *mut u64).write_volatile(next_time);
+        (mtimecmp as *mut u64).write_volatile(next_time);
     }
 }
\ No newline at end of file
@@ -17,6 +17,8 @@ pub struct TrapContext {
     pub kernel_sp: usize,
     /// Addr of trap_handler_s function
     pub trap_handler_s: usize,
+    /// hart the app was last returned to user on, reloaded into tp on trap
+    pub hart_id: usize,
 }
 
 impl TrapContext {
@@ -41,6 +43,7 @@ impl TrapContext {
             kernel_satp,  // addr of page table
             kernel_sp,    // kernel stack
             trap_handler_s, // addr of trap_handler_s function
+            hart_id: 0,     // filled in by trap_return_s
         };
         cx.set_sp(sp); // app's user stack pointer
         cx // return initial Trap Context of app
@@ -1,11 +1,13 @@
 mod context;
 
+use crate::backtrace::print_pc;
 use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
 use crate::mm::VirtAddr;
 use crate::syscall::syscall;
 use crate::task::{
-    check_signals_error_of_current, current_add_signal, current_trap_cx, current_user_token,
-    exit_current_and_run_next, handle_signals, suspend_current_and_run_next, SignalFlags,
+    charge_current_time, check_signals_error_of_current, current_add_signal, current_trap_cx,
+    current_user_token, dump_core_of_current, exit_current_and_run_next, handle_breakpoint, handle_signals,
+    suspend_current_and_run_next, SignalFlags,
 };
 use core::arch::{asm, global_asm};
 use riscv::register::{
@@ -17,7 +19,7 @@ use riscv::register::{
         Interrupt::{SupervisorSoft, SupervisorTimer},
         Trap,
     },
-    stval, stvec,
+    sepc, stval, stvec,
 };
 
 global_asm!(include_str!("trap.S"));
@@ -25,7 +27,7 @@ global_asm!(include_str!("trap.S"));
 /// initialize CSR `stvec` as the entry of `__alltraps`
 pub fn init() {
     set_kernel_trap_entry();
-    println!("init trap");
+    debug!("hart {} traps set up", crate::smp::hart_id());
 }
 
 fn set_kernel_trap_entry() {
@@ -38,13 +40,14 @@ fn set_kernel_trap_entry() {
     // }
     unsafe {
         stvec::write(trap_from_kernel as usize, TrapMode::Direct);
-        println!("stvec: {:?}", stvec::read());
     }
 }
 
 #[unsafe(no_mangle)]
 /// handle an interrupt, exception, or system call from user space
 pub fn trap_handler_s() -> ! {
+    // 从用户态进入，此前的时间记为用户时间
+    charge_current_time(true);
     let cx = current_trap_cx();
     let scause = scause::read(); // get trap cause
     let stval = stval::read(); // get extra value
@@ -61,7 +64,10 @@ pub fn trap_handler_s() -> ! {
             let mut cx = current_trap_cx();
             cx.sepc += 4;
             // get system call return value
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]);
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
             // cx is changed during sys_exec, so we have to call it again
             cx = current_trap_cx();
             cx.x[10] = result as usize;
@@ -73,7 +79,7 @@ pub fn trap_handler_s() -> ! {
             // );
             let fault_addr = VirtAddr(stval::read());
             if !crate::task::handle_cow(fault_addr) {
-                println!("[kernel] StorePageFault in application and not cow");
+                info!("StorePageFault at {:#x} in application and not cow", fault_addr.0);
                 current_add_signal(SignalFlags::SIGSEGV);
             }
             // else{
@@ -95,6 +101,10 @@ pub fn trap_handler_s() -> ! {
             */
             current_add_signal(SignalFlags::SIGSEGV);
         }
+        Trap::Exception(Exception::Breakpoint) => {
+            // sepc stays on the ebreak, the debugger moves it on
+            handle_breakpoint();
+        }
         Trap::Exception(Exception::IllegalInstruction) => {
             // println!("[kernel] IllegalInstruction in application, kernel killed it.");
             // println!(
@@ -104,7 +114,7 @@ pub fn trap_handler_s() -> ! {
             current_add_signal(SignalFlags::SIGILL);
         }
         Trap::Interrupt(SupervisorTimer) => {
-            println!("|s_timer_interrupt|");
+            trace!("s_timer_interrupt");
             set_next_trigger();
             suspend_current_and_run_next();
         }
@@ -117,6 +127,14 @@ pub fn trap_handler_s() -> ! {
                 asm!("csrw sip, {sip}", sip = in(reg) sip & !2);
                 // panic!("|s_soft_interrupt|");
             }
+            // 核间中断与时钟共用SSIP，先处理挂起的IPI
+            crate::smp::handle_ipi();
+            // 时钟中断转发来的，给采样分析器计一次
+            crate::task::sample_current(cx.sepc);
+            // 轮询串口，把^C/^Z转成前台进程组的信号
+            crate::fs::poll_console();
+            // 检查到期的ITIMER_REAL
+            crate::timer::check_timer();
             // 时间片轮转
             set_next_trigger();
             suspend_current_and_run_next();
@@ -131,7 +149,11 @@ pub fn trap_handler_s() -> ! {
     }
     handle_signals();
     if let Some((errno, msg)) = check_signals_error_of_current() {
-        println!("[kernel] {}", msg);
+        if dump_core_of_current() {
+            warn!("{} (core dumped)", msg);
+        } else {
+            warn!("{}", msg);
+        }
         exit_current_and_run_next(errno);
     }
     trap_return_s();
@@ -155,6 +177,10 @@ pub fn trap_return_s() -> ! {
     // println!("|s_trap_return|");
     let trap_cx_ptr = TRAP_CONTEXT;
     let user_satp = current_user_token();
+    // 下次陷入时由__alltraps_s恢复到tp
+    current_trap_cx().hart_id = crate::smp::hart_id();
+    // 返回用户态前结算内核时间
+    charge_current_time(false);
     // println!("|s_trap_return|");
     unsafe extern "C" {
         safe fn __alltraps_s();
@@ -179,6 +205,13 @@ pub fn trap_return_s() -> ! {
 pub fn trap_from_kernel() -> ! {
     println!("|kernel_trap|");
     // unsafe { core::arch::asm!("ecall"); }
+    println!(
+        "[kernel] {:?} in kernel, stval = {:#x}",
+        scause::read().cause(),
+        stval::read()
+    );
+    print_pc("[kernel] sepc", sepc::read());
+    // the panic report unwinds from here into the trapped code
     panic!("a trap from kernel!");
 }
 
@@ -113,6 +113,15 @@ impl Uart {
             self_data.read()
         }
     }
+
+    pub fn try_receive(&mut self) -> Option<u8> {
+        let self_data = self.data.load(Ordering::Relaxed);
+        if self.line_sts().contains(LineStsFlags::DATA_READY) {
+            unsafe { Some(self_data.read()) }
+        } else {
+            None
+        }
+    }
 }
 
 
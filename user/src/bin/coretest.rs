@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::format;
+use user_lib::{
+    close, delete, exit, fork, open, read, setrlimit, waitpid, OpenFlags, RLimit, RLIMIT_CORE,
+    RLIM_INFINITY, SIGSEGV,
+};
+
+/// Fork a child that writes through a null pointer, return its pid
+fn crash_child(dump: bool) -> usize {
+    let pid = fork();
+    if pid == 0 {
+        if !dump {
+            setrlimit(RLIMIT_CORE, &RLimit { cur: 0, max: RLIM_INFINITY });
+        }
+        unsafe {
+            core::ptr::null_mut::<u8>().write_volatile(0);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    assert_eq!(exit_code, -SIGSEGV);
+    pid as usize
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let pid = crash_child(true);
+    let path = format!("core.{}\0", pid);
+    let fd = open(path.as_str(), OpenFlags::RDONLY);
+    assert!(fd >= 0, "no core file");
+    let mut ehdr = [0u8; 64];
+    assert_eq!(read(fd as usize, &mut ehdr), 64);
+    close(fd as usize);
+    assert_eq!(&ehdr[..4], b"\x7fELF");
+    // ET_CORE for EM_RISCV
+    assert_eq!(u16::from_le_bytes([ehdr[16], ehdr[17]]), 4);
+    assert_eq!(u16::from_le_bytes([ehdr[18], ehdr[19]]), 243);
+    delete(path.as_str());
+
+    // RLIMIT_CORE of 0 leaves no file behind
+    let pid = crash_child(false);
+    let path = format!("core.{}\0", pid);
+    assert!(open(path.as_str(), OpenFlags::RDONLY) < 0);
+    println!("coretest passed!");
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::vec;
+use user_lib::{
+    syslog, write, SYSLOG_ACTION_CLEAR, SYSLOG_ACTION_CONSOLE_LEVEL, SYSLOG_ACTION_READ_ALL,
+    SYSLOG_ACTION_READ_CLEAR, SYSLOG_ACTION_SIZE_BUFFER,
+};
+
+/// dmesg [-c | -C | -n level]: print the kernel log; -c clears it after,
+/// -C only clears it, -n sets the level below which lines reach the console
+#[unsafe(no_mangle)]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    let action = match (argc, argv.get(1).copied()) {
+        (1, _) => SYSLOG_ACTION_READ_ALL,
+        (2, Some("-c")) => SYSLOG_ACTION_READ_CLEAR,
+        (2, Some("-C")) => return syslog(SYSLOG_ACTION_CLEAR, core::ptr::null_mut(), 0) as i32,
+        (3, Some("-n")) => {
+            let Ok(level) = argv[2].parse::<usize>() else {
+                println!("dmesg: bad level '{}'", argv[2]);
+                return -1;
+            };
+            let ret = syslog(SYSLOG_ACTION_CONSOLE_LEVEL, core::ptr::null_mut(), level);
+            if ret < 0 {
+                println!("dmesg: bad level '{}'", argv[2]);
+            }
+            return ret as i32;
+        }
+        _ => {
+            println!("usage: dmesg [-c | -C | -n level]");
+            return -1;
+        }
+    };
+    let size = syslog(SYSLOG_ACTION_SIZE_BUFFER, core::ptr::null_mut(), 0) as usize;
+    let mut buf = vec![0u8; size];
+    let len = syslog(action, buf.as_mut_ptr(), buf.len());
+    if len < 0 {
+        return len as i32;
+    }
+    write(1, &buf[..len as usize]);
+    0
+}
@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::slice;
+use user_lib::{
+    exec, exit, fork, getitimer, open, pipe, read, sigaltstack, waitpid, write, Errno, ITimerVal,
+    OpenFlags, SignalStack, ITIMER_REAL,
+};
+
+/// Below the program image, never mapped
+const UNMAPPED: usize = 0x1000;
+/// The trap context page, mapped for the kernel only
+const TRAP_CONTEXT: usize = usize::MAX - 2 * 4096 + 1;
+
+/// Bad pointers passed to syscalls fail them with EFAULT instead of
+/// bringing the kernel down
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let unmapped = unsafe { slice::from_raw_parts_mut(UNMAPPED as *mut u8, 16) };
+    let kernel = unsafe { slice::from_raw_parts(TRAP_CONTEXT as *const u8, 16) };
+    // readable but not writable
+    let text = unsafe { slice::from_raw_parts_mut(main as usize as *mut u8, 16) };
+
+    assert_eq!(write(1, unmapped), Errno::EFAULT.ret());
+    assert_eq!(write(1, kernel), Errno::EFAULT.ret());
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(write(fds[1], b"x"), 1);
+    assert_eq!(read(fds[0], unmapped), Errno::EFAULT.ret());
+    assert_eq!(read(fds[0], text), Errno::EFAULT.ret());
+    let text_fds = unsafe { slice::from_raw_parts_mut(main as usize as *mut usize, 2) };
+    assert_eq!(pipe(text_fds), Errno::EFAULT.ret());
+
+    let path = unsafe { core::str::from_utf8_unchecked(&unmapped[..1]) };
+    assert_eq!(open(path, OpenFlags::RDONLY), Errno::EFAULT.ret());
+    let args = [UNMAPPED as *const u8, core::ptr::null()];
+    assert_eq!(exec("hello_world\0", &args), Errno::EFAULT.ret());
+
+    let timer = unsafe { &mut *(UNMAPPED as *mut ITimerVal) };
+    assert_eq!(getitimer(ITIMER_REAL, timer), Errno::EFAULT.ret());
+    let stack = unsafe { &*(TRAP_CONTEXT as *const SignalStack) };
+    assert_eq!(sigaltstack(Some(stack), None), Errno::EFAULT.ret());
+
+    // the child is still there to be waited for after a bad status pointer
+    let pid = fork();
+    if pid == 0 {
+        exit(7);
+    }
+    let status = unsafe { &mut *(UNMAPPED as *mut i32) };
+    assert_eq!(waitpid(pid as usize, status), Errno::EFAULT.ret());
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+    println!("efaulttest passed!");
+    0
+}
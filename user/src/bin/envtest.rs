@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::vec;
+use user_lib::{
+    exec, fork, getauxval, getenv, setenv, unsetenv, waitpid, Errno, AT_ENTRY, AT_PAGESZ,
+    AT_RANDOM,
+};
+
+/// Run again by exec: the environment must have come along
+fn child() -> i32 {
+    match getenv("GREETING") {
+        Some(value) if value == "hello world" => {}
+        other => {
+            println!("GREETING = {:?} after exec", other);
+            return -1;
+        }
+    }
+    if getenv("REMOVED").is_some() {
+        println!("unset variable survived exec");
+        return -1;
+    }
+    0
+}
+
+#[unsafe(no_mangle)]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc == 2 && argv[1] == "child" {
+        return child();
+    }
+    assert_eq!(getauxval(AT_PAGESZ), Some(4096));
+    assert!(getauxval(AT_ENTRY).is_some_and(|entry| entry != 0));
+    assert!(getauxval(AT_RANDOM).is_some());
+    assert_eq!(setenv("GREETING", "hello"), 0);
+    assert_eq!(setenv("GREETING", "hello world"), 0);
+    assert_eq!(setenv("REMOVED", "yes"), 0);
+    unsetenv("REMOVED");
+    assert_eq!(setenv("BAD=NAME", "x"), -1);
+    // arguments that do not fit on the new stack fail exec, and we go on
+    // 12KiB, more than the default 8KiB stack
+    let mut big = vec![b'x'; 12 * 1024];
+    *big.last_mut().unwrap() = 0;
+    assert_eq!(
+        exec("envtest\0", &["envtest\0".as_ptr(), big.as_ptr(), core::ptr::null()]),
+        Errno::E2BIG.ret()
+    );
+    let pid = fork();
+    if pid == 0 {
+        // fork copies the environment along with everything else
+        assert_eq!(getenv("GREETING").as_deref(), Some("hello world"));
+        exec("envtest\0", &["envtest\0".as_ptr(), "child\0".as_ptr(), core::ptr::null()]);
+        panic!("exec failed");
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    if exit_code != 0 {
+        println!("envtest failed");
+        return -1;
+    }
+    println!("envtest passed!");
+    0
+}
@@ -4,14 +4,14 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{OpenFlags, close, open, read, write, mkdir, rmdir, delete, lseek, rename};
+use user_lib::{Errno, OpenFlags, close, open, read, write, mkdir, rmdir, delete, lseek, rename};
 
 #[unsafe(no_mangle)]
 pub fn main() -> i32 {
     // 1. 创建目录和文件
     let dir_name = "testdir\0";
     assert_eq!(mkdir(dir_name), 0);   // 创建新目录
-    assert_eq!(mkdir(dir_name), -1);  // 重复失败
+    assert_eq!(mkdir(dir_name), Errno::EEXIST.ret());  // 重复失败
 
     let file_path = "testdir/filex\0";
     let test_str = "Rust: hello, directories!";
@@ -63,30 +63,30 @@ pub fn main() -> i32 {
     let fd2 = open(moved_file, OpenFlags::CREATE | OpenFlags::WRONLY);
     assert!(fd2 > 0);
     close(fd2 as usize);
-    assert_eq!(rename(moved_file, moved_file), -1);
+    assert_eq!(rename(moved_file, moved_file), Errno::EEXIST.ret());
 
     // 6. 目录重命名（空目录）
     let newdir = "renameddir\0";
     assert_eq!(mkdir("emptydir\0"), 0);
     assert_eq!(rename("emptydir\0", newdir), 0);
     // 旧目录不能删了，因为已经换名字
-    assert_eq!(rmdir("emptydir\0"), -1);
+    assert_eq!(rmdir("emptydir\0"), Errno::ENOENT.ret());
     assert_eq!(rmdir(newdir), 0);
 
     // 7. 非空目录重命名（testdir2)
     let bigdir = "bigdir\0";
     assert_eq!(rename(dir2, bigdir), 0);
     // 文件移动后旧目录消失，新目录可删
-    assert_eq!(rmdir(dir2), -1);
+    assert_eq!(rmdir(dir2), Errno::ENOENT.ret());
     // 但新目录还不能删（有文件）
-    assert_eq!(rmdir(bigdir), -1);
+    assert_eq!(rmdir(bigdir), Errno::ENOTEMPTY.ret());
     // 删文件再删目录
     assert_eq!(delete("bigdir/filemoved\0"), 0);
     assert_eq!(rmdir(bigdir), 0);
 
     // 尾部清理
     assert_eq!(rmdir(dir_name), 0); // remove testdir
-    assert_eq!(rmdir(dir_name), -1);
+    assert_eq!(rmdir(dir_name), Errno::ENOENT.ret());
 
     println!("dir/seek/delete/rename_test passed!");
     0
@@ -13,7 +13,7 @@ fn main() -> i32 {
         loop {
             let mut exit_code: i32 = 0;
             let pid = wait(&mut exit_code);
-            if pid == -1 {
+            if pid < 0 {
                 yield_();
                 continue;
             }
@@ -0,0 +1,87 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::{close, exec, fork, open, waitpid, write, OpenFlags};
+
+const PROGRAM: &str = "linux_uname\0";
+const BASE: u64 = 0x10000;
+/// ELF header and one program header, the code follows
+const HEADERS: usize = 64 + 56;
+
+const SP: u32 = 2;
+const A0: u32 = 10;
+const A7: u32 = 17;
+const SYS_UNAME: i32 = 160;
+const SYS_EXIT_GROUP: i32 = 94;
+/// Our exit, reached only if the kernel takes the program for a native one
+const SYSCALL_EXIT: i32 = 93;
+const ECALL: u32 = 0x73;
+
+const fn addi(rd: u32, rs1: u32, imm: i32) -> u32 {
+    ((imm as u32 & 0xfff) << 20) | (rs1 << 15) | (rd << 7) | 0x13
+}
+
+/// A static RISC-V executable without our `.note.acore`, so a Linux one:
+/// exit_group(uname(sp - 512)). Under our numbering both calls fail with
+/// ENOSYS and it exits with -38.
+fn linux_elf() -> Vec<u8> {
+    let code = [
+        addi(A0, SP, -512),
+        addi(A7, 0, SYS_UNAME),
+        ECALL,
+        addi(A7, 0, SYS_EXIT_GROUP),
+        ECALL,
+        addi(A7, 0, SYSCALL_EXIT),
+        ECALL,
+    ];
+    let size = (HEADERS + code.len() * 4) as u64;
+    let mut elf = Vec::new();
+    elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    // ET_EXEC for EM_RISCV
+    elf.extend_from_slice(&2u16.to_le_bytes());
+    elf.extend_from_slice(&0xf3u16.to_le_bytes());
+    elf.extend_from_slice(&1u32.to_le_bytes());
+    elf.extend_from_slice(&(BASE + HEADERS as u64).to_le_bytes());
+    // program headers right after this one, no section headers
+    elf.extend_from_slice(&64u64.to_le_bytes());
+    elf.extend_from_slice(&0u64.to_le_bytes());
+    elf.extend_from_slice(&0u32.to_le_bytes());
+    for half in [64u16, 56, 1, 64, 0, 0] {
+        elf.extend_from_slice(&half.to_le_bytes());
+    }
+    // one PT_LOAD, readable and executable, mapping the whole file
+    elf.extend_from_slice(&1u32.to_le_bytes());
+    elf.extend_from_slice(&5u32.to_le_bytes());
+    for word in [0, BASE, BASE, size, size, 0x1000] {
+        elf.extend_from_slice(&word.to_le_bytes());
+    }
+    for inst in code {
+        elf.extend_from_slice(&inst.to_le_bytes());
+    }
+    elf
+}
+
+/// Programs without our ELF note get the Linux syscall numbering
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let fd = open(PROGRAM, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    write(fd as usize, &linux_elf());
+    close(fd as usize);
+    let pid = fork();
+    if pid == 0 {
+        exec(PROGRAM, &[PROGRAM.as_ptr(), core::ptr::null()]);
+        panic!("exec returned");
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0, "not run as a Linux program");
+    println!("linuxtest passed!");
+    0
+}
@@ -0,0 +1,63 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, getpid, perf_event_open, perf_event_read, rdcycle, rdinstret, PerfEventAttr,
+    PERF_COUNT_HW_CPU_CYCLES, PERF_COUNT_HW_INSTRUCTIONS, PERF_TYPE_HARDWARE,
+};
+
+const DEFAULT_ROUNDS: usize = 1000;
+
+fn open_counter(config: u64) -> usize {
+    let attr = PerfEventAttr {
+        type_: PERF_TYPE_HARDWARE,
+        size: core::mem::size_of::<PerfEventAttr>() as u32,
+        config,
+        ..Default::default()
+    };
+    let fd = perf_event_open(&attr, 0, -1, -1);
+    assert!(fd >= 0, "perf_event_open: {}", fd);
+    fd as usize
+}
+
+/// perfbench [rounds]: cycles and instructions per getpid, from our own
+/// perf events and from the raw counters of the hart
+#[unsafe(no_mangle)]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    let rounds = if argc > 1 {
+        argv[1].parse().unwrap_or(DEFAULT_ROUNDS).max(1)
+    } else {
+        DEFAULT_ROUNDS
+    };
+    let cycles = open_counter(PERF_COUNT_HW_CPU_CYCLES);
+    let instructions = open_counter(PERF_COUNT_HW_INSTRUCTIONS);
+
+    let (cycles_before, instret_before) = (perf_event_read(cycles), perf_event_read(instructions));
+    for _ in 0..rounds {
+        getpid();
+    }
+    let (cycles_after, instret_after) = (perf_event_read(cycles), perf_event_read(instructions));
+    println!(
+        "perf events: {} cycles, {} instructions per getpid",
+        (cycles_after - cycles_before) / rounds as u64,
+        (instret_after - instret_before) / rounds as u64
+    );
+
+    // the raw counters also count whatever else ran on this hart meanwhile
+    let (cycle_start, instret_start) = (rdcycle(), rdinstret());
+    for _ in 0..rounds {
+        getpid();
+    }
+    let (cycle_end, instret_end) = (rdcycle(), rdinstret());
+    println!(
+        "rdcycle/rdinstret: {} cycles, {} instructions per getpid",
+        (cycle_end - cycle_start) / rounds as u64,
+        (instret_end - instret_start) / rounds as u64
+    );
+    close(cycles);
+    close(instructions);
+    0
+}
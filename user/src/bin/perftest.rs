@@ -0,0 +1,78 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, getpid, perf_event_ioctl, perf_event_open, perf_event_read, rdcycle, rdinstret, read,
+    PerfEventAttr, PERF_ATTR_DISABLED, PERF_COUNT_HW_CPU_CYCLES, PERF_COUNT_HW_INSTRUCTIONS,
+    PERF_EVENT_IOC_DISABLE, PERF_EVENT_IOC_ENABLE, PERF_EVENT_IOC_RESET, PERF_TYPE_HARDWARE,
+};
+
+const ENOENT: isize = -2;
+const EINVAL: isize = -22;
+const ROUNDS: u64 = 100;
+
+fn attr(config: u64, flags: u64) -> PerfEventAttr {
+    PerfEventAttr {
+        type_: PERF_TYPE_HARDWARE,
+        size: core::mem::size_of::<PerfEventAttr>() as u32,
+        config,
+        flags,
+        ..Default::default()
+    }
+}
+
+fn syscalls() {
+    for _ in 0..ROUNDS {
+        getpid();
+    }
+}
+
+/// User mode reads the counters directly, and perf events count for this
+/// process only while enabled
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    // these trap with SIGILL unless the kernel opened the counters to us
+    let (cycle, instret) = (rdcycle(), rdinstret());
+    syscalls();
+    assert!(rdcycle() > cycle && rdinstret() > instret);
+
+    let cycles = perf_event_open(&attr(PERF_COUNT_HW_CPU_CYCLES, 0), 0, -1, -1);
+    let instructions = perf_event_open(&attr(PERF_COUNT_HW_INSTRUCTIONS, 0), 0, -1, -1);
+    assert!(cycles >= 0 && instructions >= 0);
+    let (cycles, instructions) = (cycles as usize, instructions as usize);
+    let before = perf_event_read(instructions);
+    syscalls();
+    let after = perf_event_read(instructions);
+    // every round runs the syscall path
+    assert!(after - before >= ROUNDS, "{} instructions", after - before);
+    assert!(perf_event_read(cycles) > 0);
+    let mut short = [0u8; 4];
+    assert_eq!(read(instructions, &mut short), 0);
+
+    assert_eq!(perf_event_ioctl(instructions, PERF_EVENT_IOC_DISABLE), 0);
+    let stopped = perf_event_read(instructions);
+    syscalls();
+    assert_eq!(perf_event_read(instructions), stopped);
+    assert_eq!(perf_event_ioctl(instructions, PERF_EVENT_IOC_RESET), 0);
+    assert_eq!(perf_event_read(instructions), 0);
+    assert_eq!(perf_event_ioctl(instructions, PERF_EVENT_IOC_ENABLE), 0);
+    syscalls();
+    assert!(perf_event_read(instructions) >= ROUNDS);
+
+    let disabled = perf_event_open(&attr(PERF_COUNT_HW_CPU_CYCLES, PERF_ATTR_DISABLED), 0, -1, -1);
+    assert!(disabled >= 0);
+    syscalls();
+    assert_eq!(perf_event_read(disabled as usize), 0);
+
+    assert_eq!(perf_event_open(&attr(99, 0), 0, -1, -1), ENOENT);
+    assert_eq!(perf_event_open(&attr(PERF_COUNT_HW_CPU_CYCLES, 0), 0, 0, -1), EINVAL);
+    assert_eq!(perf_event_open(&attr(PERF_COUNT_HW_CPU_CYCLES, 0), -2, -1, -1), EINVAL);
+    close(cycles);
+    close(instructions);
+    close(disabled as usize);
+    println!("perftest passed!");
+    0
+}
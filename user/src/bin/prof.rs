@@ -0,0 +1,183 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use user_lib::{
+    close, execvp, fork, open, profile, read, waitpid, waitpid_options, OpenFlags, ProfSample,
+    PROF_READ, PROF_START, WNOWAIT,
+};
+
+const SHT_SYMTAB: u32 = 2;
+const STT_FUNC: u8 = 2;
+
+/// A function of the profiled program
+struct Symbol {
+    start: usize,
+    end: usize,
+    name: String,
+}
+
+fn u16_at(data: &[u8], offset: usize) -> usize {
+    u16::from_le_bytes([data[offset], data[offset + 1]]) as usize
+}
+
+fn u32_at(data: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap()) as usize
+}
+
+fn u64_at(data: &[u8], offset: usize) -> usize {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap()) as usize
+}
+
+fn read_file(path: &str) -> Option<Vec<u8>> {
+    let fd = open(format!("{}\0", path).as_str(), OpenFlags::RDONLY);
+    if fd < 0 {
+        return None;
+    }
+    let mut data = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        data.extend_from_slice(&buf[..len as usize]);
+    }
+    close(fd as usize);
+    Some(data)
+}
+
+/// `a::b::c` out of a legacy Rust symbol `_ZN1a1b1c17h<hash>E`; other
+/// names are kept as they are
+fn demangle(name: &str) -> String {
+    let Some(mut rest) = name.strip_prefix("_ZN") else {
+        return String::from(name);
+    };
+    let mut parts = Vec::new();
+    while let Some(digits) = rest.find(|c: char| !c.is_ascii_digit()).filter(|&n| n > 0) {
+        let len: usize = rest[..digits].parse().unwrap();
+        let Some(part) = rest.get(digits..digits + len) else {
+            return String::from(name);
+        };
+        parts.push(part);
+        rest = &rest[digits + len..];
+    }
+    if parts.last().is_some_and(|part| part.len() == 17 && part.starts_with('h')) {
+        parts.pop();
+    }
+    parts.join("::")
+}
+
+/// Functions in the symbol table of the ELF at `path`, by address
+fn symbols(path: &str) -> Vec<Symbol> {
+    let Some(elf) = read_file(path) else {
+        return Vec::new();
+    };
+    if elf.len() < 64 || &elf[..4] != b"\x7fELF" {
+        return Vec::new();
+    }
+    let (shoff, shentsize, shnum) = (u64_at(&elf, 0x28), u16_at(&elf, 0x3a), u16_at(&elf, 0x3c));
+    let section = |i: usize| shoff + i * shentsize;
+    let mut symbols = Vec::new();
+    for i in 0..shnum {
+        let header = section(i);
+        if header + shentsize > elf.len() || u32_at(&elf, header + 4) as u32 != SHT_SYMTAB {
+            continue;
+        }
+        let (offset, size, entsize) =
+            (u64_at(&elf, header + 0x18), u64_at(&elf, header + 0x20), u64_at(&elf, header + 0x38));
+        let strtab = u64_at(&elf, section(u32_at(&elf, header + 0x28)) + 0x18);
+        for sym in (offset..offset + size).step_by(entsize) {
+            let (start, len) = (u64_at(&elf, sym + 8), u64_at(&elf, sym + 16));
+            if elf[sym + 4] & 0xf != STT_FUNC || start == 0 {
+                continue;
+            }
+            let name_start = strtab + u32_at(&elf, sym);
+            let name_len = elf[name_start..].iter().position(|&b| b == 0).unwrap_or(0);
+            let name = core::str::from_utf8(&elf[name_start..name_start + name_len]).unwrap_or("?");
+            symbols.push(Symbol {
+                start,
+                end: start + len,
+                name: demangle(name),
+            });
+        }
+    }
+    symbols.sort_by_key(|symbol| symbol.start);
+    symbols
+}
+
+/// Function the user `pc` is in
+fn lookup(symbols: &[Symbol], pc: usize) -> Option<&Symbol> {
+    let index = symbols.partition_point(|symbol| symbol.start <= pc);
+    symbols[..index].iter().rev().find(|symbol| pc < symbol.end)
+}
+
+/// Hits per function, user functions named from the program's symbol
+/// table and kernel pcs left as addresses for addr2line on the kernel ELF
+fn report(path: &str, samples: &[ProfSample]) {
+    let symbols = symbols(path);
+    let mut hits: BTreeMap<String, usize> = BTreeMap::new();
+    for sample in samples {
+        let place = if sample.kernel != 0 {
+            format!("[kernel] {:#x}", sample.pc)
+        } else {
+            match lookup(&symbols, sample.pc) {
+                Some(symbol) => symbol.name.clone(),
+                None => format!("{:#x}", sample.pc),
+            }
+        };
+        *hits.entry(place).or_insert(0) += sample.count;
+    }
+    let total: usize = hits.values().sum();
+    let mut hits: Vec<(String, usize)> = hits.into_iter().collect();
+    hits.sort_by(|a, b| b.1.cmp(&a.1));
+    println!("{} samples", total);
+    println!("  count      %  function");
+    for (place, count) in hits {
+        let tenths = count * 1000 / total;
+        println!("{:7} {:4}.{}  {}", count, tenths / 10, tenths % 10, place);
+    }
+}
+
+/// prof <app> [args...]: run an app, sampling where it is on every timer
+/// tick, and print the hits per function once it exits
+#[unsafe(no_mangle)]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 2 {
+        println!("usage: prof <app> [args...]");
+        return -1;
+    }
+    let args: Vec<String> = argv[1..].iter().map(|arg| format!("{}\0", arg)).collect();
+    let mut arg_ptrs: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+    arg_ptrs.push(core::ptr::null());
+    let pid = fork();
+    if pid == 0 {
+        profile(PROF_START, 0, &mut []);
+        execvp(args[0].as_str(), arg_ptrs.as_slice());
+        println!("Error when executing '{}'", argv[1]);
+        return -4;
+    }
+    // read the samples before reaping the child takes them away
+    let mut exit_code: i32 = 0;
+    waitpid_options(pid, &mut exit_code, WNOWAIT);
+    let total = profile(PROF_READ, pid as usize, &mut []);
+    let mut samples = vec![ProfSample::default(); total.max(0) as usize];
+    profile(PROF_READ, pid as usize, &mut samples);
+    waitpid(pid as usize, &mut exit_code);
+    if samples.is_empty() {
+        println!("no samples, {} exited too soon", argv[1]);
+    } else {
+        report(argv[1], &samples);
+    }
+    exit_code
+}
@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, get_time, profile, waitpid, waitpid_options, ProfSample, PROF_READ, PROF_START,
+    PROF_STOP, WNOWAIT,
+};
+
+const ESRCH: isize = -3;
+
+/// Burn user time for `ms` milliseconds, a tick comes every 100
+fn spin(ms: isize) {
+    let start = get_time();
+    while get_time() - start < ms {}
+}
+
+/// Hits in the samples of `pid`
+fn hits(pid: usize) -> usize {
+    let mut samples = [ProfSample::default(); 64];
+    let total = profile(PROF_READ, pid, &mut samples);
+    assert!(total >= 0 && total as usize <= samples.len());
+    samples[..total as usize].iter().map(|sample| sample.count).sum()
+}
+
+/// The profiler counts ticks while started, keeps them once stopped, and
+/// a child's samples stay readable until it is reaped
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    assert_eq!(profile(PROF_READ, 0, &mut []), 0);
+    assert_eq!(profile(PROF_START, 0, &mut []), 0);
+    spin(500);
+    assert_eq!(profile(PROF_STOP, 0, &mut []), 0);
+    let taken = hits(0);
+    assert!(taken > 0, "no samples");
+    spin(300);
+    assert_eq!(hits(0), taken);
+
+    let pid = fork();
+    if pid == 0 {
+        profile(PROF_START, 0, &mut []);
+        spin(500);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid_options(pid, &mut exit_code, WNOWAIT), pid);
+    assert!(hits(pid as usize) > 0, "no samples from the child");
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(profile(PROF_READ, pid as usize, &mut []), ESRCH);
+    println!("proftest passed!");
+    0
+}
@@ -0,0 +1,88 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{
+    exit, fork, getpid, kill, ptrace, waitpid, wifstopped, wstopsig, UserRegs, PTRACE_CONT,
+    PTRACE_GETREGS, PTRACE_PEEKDATA, PTRACE_POKEDATA, PTRACE_SETREGS, PTRACE_SINGLESTEP,
+    PTRACE_TRACEME, SIGTRAP, SIGUSR1,
+};
+
+const BEFORE: usize = 0x1234;
+const AFTER: usize = 0x5678;
+static VALUE: AtomicUsize = AtomicUsize::new(BEFORE);
+
+/// Wait for `pid` to stop and return the signal it stopped for
+fn wait_stop(pid: usize) -> i32 {
+    let mut status = 0;
+    assert_eq!(waitpid(pid, &mut status), pid as isize);
+    assert!(wifstopped(status), "child did not stop, status {:#x}", status);
+    wstopsig(status)
+}
+
+fn child() -> i32 {
+    assert_eq!(ptrace(PTRACE_TRACEME, 0, 0, 0), 0);
+    // the tracer swallows this one, it would kill us otherwise
+    kill(getpid() as usize, SIGUSR1);
+    if VALUE.load(Ordering::SeqCst) != AFTER {
+        return 1;
+    }
+    unsafe {
+        core::arch::asm!(".option push", ".option norvc", "ebreak", ".option pop");
+    }
+    let mut sum = 0;
+    for i in 0..4 {
+        sum += core::hint::black_box(i);
+    }
+    if sum == 6 { 0 } else { 2 }
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        exit(child());
+    }
+    let pid = pid as usize;
+
+    // stopped on the way to delivering SIGUSR1
+    assert_eq!(wait_stop(pid), SIGUSR1);
+    let addr = &VALUE as *const AtomicUsize as usize;
+    let mut word = 0usize;
+    assert_eq!(ptrace(PTRACE_PEEKDATA, pid, addr, &mut word as *mut usize as usize), 0);
+    assert_eq!(word, BEFORE);
+    assert_eq!(ptrace(PTRACE_POKEDATA, pid, addr, AFTER), 0);
+    // our copy is untouched
+    assert_eq!(VALUE.load(Ordering::SeqCst), BEFORE);
+    assert_eq!(ptrace(PTRACE_CONT, pid, 0, 0), 0);
+
+    // stopped at the ebreak; step over it
+    assert_eq!(wait_stop(pid), SIGTRAP);
+    let mut regs: UserRegs = [0; 32];
+    assert_eq!(ptrace(PTRACE_GETREGS, pid, 0, &mut regs as *mut UserRegs as usize), 0);
+    let mut inst = 0usize;
+    ptrace(PTRACE_PEEKDATA, pid, regs[0], &mut inst as *mut usize as usize);
+    assert_eq!(inst as u32, 0x0010_0073, "pc is not at the ebreak");
+    regs[0] += 4;
+    assert_eq!(ptrace(PTRACE_SETREGS, pid, 0, &regs as *const UserRegs as usize), 0);
+
+    // a few single steps, each one moves the pc
+    let mut pc = regs[0];
+    for _ in 0..5 {
+        assert_eq!(ptrace(PTRACE_SINGLESTEP, pid, 0, 0), 0);
+        assert_eq!(wait_stop(pid), SIGTRAP);
+        assert_eq!(ptrace(PTRACE_GETREGS, pid, 0, &mut regs as *mut UserRegs as usize), 0);
+        assert_ne!(regs[0], pc);
+        pc = regs[0];
+    }
+    assert_eq!(ptrace(PTRACE_CONT, pid, 0, 0), 0);
+
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    assert_eq!(exit_code, 0);
+    println!("ptracetest passed!");
+    0
+}
@@ -0,0 +1,105 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    dup, exec, exit, fork, getrlimit, setrlimit, setuid, spawn, wait, waitpid, Errno, RLimit,
+    RLIMIT_AS, RLIMIT_CORE, RLIMIT_CPU, RLIMIT_NOFILE, RLIMIT_NPROC, RLIMIT_STACK, RLIM_INFINITY,
+    SIGXCPU,
+};
+
+/// Run `f` in a child and return its exit code
+fn in_child(f: fn() -> i32) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        exit(f());
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    exit_code
+}
+
+fn limit_fds() -> i32 {
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &RLimit { cur: 8, max: 8 }), 0);
+    let mut last = 0;
+    loop {
+        let fd = dup(0);
+        if fd < 0 {
+            break;
+        }
+        last = fd;
+    }
+    assert_eq!(last, 7);
+    0
+}
+
+fn limit_children() -> i32 {
+    assert_eq!(setrlimit(RLIMIT_NPROC, &RLimit { cur: 2, max: 2 }), 0);
+    for _ in 0..2 {
+        if fork() == 0 {
+            exit(0);
+        }
+    }
+    // zombies count until they are reaped
+    assert_eq!(fork(), Errno::EAGAIN.ret());
+    assert_eq!(spawn("yield\0", &[core::ptr::null::<u8>()], None), Errno::EAGAIN.ret());
+    let mut exit_code = 0;
+    assert!(wait(&mut exit_code) > 0);
+    let pid = fork();
+    if pid == 0 {
+        exit(0);
+    }
+    assert!(pid > 0);
+    0
+}
+
+fn limit_address_space() -> i32 {
+    assert_eq!(setrlimit(RLIMIT_AS, &RLimit { cur: 4096, max: RLIM_INFINITY }), 0);
+    // the image is kept when the new one does not fit
+    assert_eq!(exec("yield\0", &[core::ptr::null::<u8>()]), Errno::ENOMEM.ret());
+    0
+}
+
+fn limit_cpu() -> i32 {
+    assert_eq!(setrlimit(RLIMIT_CPU, &RLimit { cur: 1, max: RLIM_INFINITY }), 0);
+    // SIGXCPU would leave a core file behind
+    setrlimit(RLIMIT_CORE, &RLimit { cur: 0, max: RLIM_INFINITY });
+    let mut x: usize = 0;
+    loop {
+        x = x.wrapping_add(1);
+        core::hint::black_box(x);
+    }
+}
+
+fn drop_privileges() -> i32 {
+    let mut limit = RLimit::default();
+    getrlimit(RLIMIT_NOFILE, &mut limit);
+    assert_eq!(setuid(1000), 0);
+    // hard limits only go down once we are not root
+    let lower = RLimit { cur: 16, max: 32 };
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &lower), 0);
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &limit), Errno::EPERM.ret());
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &RLimit { cur: 32, max: 32 }), 0);
+    assert_eq!(setrlimit(RLIMIT_NOFILE, &RLimit { cur: 64, max: 32 }), Errno::EINVAL.ret());
+    0
+}
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let mut stack = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_STACK, &mut stack), 0);
+    assert!(stack.cur <= stack.max);
+    assert_eq!(
+        setrlimit(RLIMIT_STACK, &RLimit { cur: stack.cur, max: RLIM_INFINITY }),
+        -1
+    );
+    assert_eq!(in_child(limit_fds), 0);
+    assert_eq!(in_child(limit_children), 0);
+    assert_eq!(in_child(limit_address_space), 0);
+    assert_eq!(in_child(drop_privileges), 0);
+    assert_eq!(in_child(limit_cpu), -SIGXCPU);
+    println!("rlimittest passed!");
+    0
+}
@@ -0,0 +1,72 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exec, execvp, fork, open, setenv, waitpid, write, Errno, OpenFlags};
+
+const SCRIPT: &str = "script_test.sh\0";
+const NOT_EXEC: &str = "script_test.txt\0";
+const BAD_ELF: &str = "script_test.elf\0";
+
+/// Started through the script: argv must be rewritten the #! way, with
+/// the script path as exec found it
+fn interpreter(argv: &[&str]) -> i32 {
+    if argv.len() == 4
+        && argv[..2] == ["scripttest", "interp"]
+        && argv[2].trim_start_matches('/') == "script_test.sh"
+        && argv[3] == "extra"
+    {
+        0
+    } else {
+        println!("interpreter got {:?}", argv);
+        -1
+    }
+}
+
+fn create(path: &str, content: &str) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    write(fd as usize, content.as_bytes());
+    close(fd as usize);
+}
+
+/// Fork a child that runs `f`, returns its exit code
+fn run(f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        panic!("exec returned");
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    exit_code
+}
+
+fn exec_script() {
+    exec(SCRIPT, &[SCRIPT.as_ptr(), "extra\0".as_ptr(), core::ptr::null()]);
+}
+
+fn execvp_script() {
+    // the first directory does not exist, the second has the script
+    setenv("PATH", "/nonexistent:/");
+    execvp(SCRIPT, &[SCRIPT.as_ptr(), "extra\0".as_ptr(), core::ptr::null()]);
+}
+
+#[unsafe(no_mangle)]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc >= 2 && argv[1] == "interp" {
+        return interpreter(argv);
+    }
+    create(SCRIPT, "#!scripttest interp\nnot read by the kernel\n");
+    create(NOT_EXEC, "neither ELF nor script\n");
+    // the ELF magic and nothing else
+    create(BAD_ELF, "\x7fELF truncated\n");
+    assert_eq!(run(exec_script), 0, "#! script did not run");
+    assert_eq!(run(execvp_script), 0, "execvp did not search PATH");
+    assert_eq!(exec(NOT_EXEC, &[NOT_EXEC.as_ptr(), core::ptr::null()]), Errno::ENOEXEC.ret());
+    assert_eq!(exec(BAD_ELF, &[BAD_ELF.as_ptr(), core::ptr::null()]), Errno::ENOEXEC.ret());
+    println!("scripttest passed!");
+    0
+}
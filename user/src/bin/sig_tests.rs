@@ -3,8 +3,21 @@
 
 extern crate user_lib;
 
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use user_lib::*;
 
+static CHILD_EXITED: AtomicBool = AtomicBool::new(false);
+static INNER_HANDLED: AtomicBool = AtomicBool::new(false);
+static OUTER_SAW_INNER: AtomicBool = AtomicBool::new(false);
+static ON_ALT_STACK: AtomicBool = AtomicBool::new(false);
+static QUEUED_COUNT: AtomicUsize = AtomicUsize::new(0);
+static QUEUED_SUM: AtomicUsize = AtomicUsize::new(0);
+static ALARMS: AtomicUsize = AtomicUsize::new(0);
+static VTALARMED: AtomicBool = AtomicBool::new(false);
+
+const ALT_STACK_SIZE: usize = 8192;
+static mut ALT_STACK: [u8; ALT_STACK_SIZE] = [0; ALT_STACK_SIZE];
+
 fn func() {
     println!("func triggered");
     sigreturn();
@@ -14,7 +27,7 @@ fn user_sig_test_failsignum() {
     let mut new = SignalAction::default();
     let mut old = SignalAction::default();
     new.handler = func as usize;
-    if sigaction(50, Some(&new), Some(&mut old)) >= 0 {
+    if sigaction(SIGRTMAX + 1, Some(&new), Some(&mut old)) >= 0 {
         panic!("Wrong sigaction but successed!");
     }
 }
@@ -73,8 +86,8 @@ fn user_sig_test_restore() {
 }
 
 fn kernel_sig_test_ignore() {
-    sigprocmask(SignalFlags::SIGSTOP.bits() as u32);
-    if kill(getpid() as usize, SignalFlags::SIGSTOP.bits()) < 0 {
+    sigprocmask(SignalFlags::SIGSTOP.bits());
+    if kill(getpid() as usize, SignalFlags::SIGSTOP.bits() as i32) < 0 {
         println!("kill faild\n");
         exit(-1);
     }
@@ -94,6 +107,256 @@ fn kernel_sig_test_stop_cont() {
     }
 }
 
+fn on_sigchld() {
+    CHILD_EXITED.store(true, Ordering::Release);
+    sigreturn();
+}
+
+fn kernel_sig_test_sig_ign() {
+    let mut new = SignalAction::default();
+    let mut old = SignalAction::default();
+    new.handler = SIG_IGN;
+    if sigaction(SIGUSR1, Some(&new), Some(&mut old)) < 0 {
+        panic!("Sigaction failed!");
+    }
+    if kill(getpid() as usize, SIGUSR1) < 0 {
+        println!("Kill failed!");
+        exit(-1);
+    }
+    // still alive
+}
+
+fn kernel_sig_test_default_terminate() {
+    let pid = fork();
+    if pid == 0 {
+        loop {}
+    } else {
+        kill(pid as usize, SIGTERM);
+        let mut exit_code = 0;
+        waitpid(pid as usize, &mut exit_code);
+        if exit_code != -SIGTERM {
+            println!("Child exited with {} instead of {}", exit_code, -SIGTERM);
+            exit(-1);
+        }
+    }
+}
+
+fn kernel_sig_test_sigchld() {
+    let mut new = SignalAction::default();
+    let mut old = SignalAction::default();
+    new.handler = on_sigchld as usize;
+    if sigaction(SIGCHLD, Some(&new), Some(&mut old)) < 0 {
+        panic!("Sigaction failed!");
+    }
+    // keep SIGCHLD pending until sigsuspend opens the mask
+    sigprocmask(SignalFlags::SIGCHLD.bits());
+    let pid = fork();
+    if pid == 0 {
+        exit(0);
+    }
+    while !CHILD_EXITED.load(Ordering::Acquire) {
+        sigsuspend(0);
+    }
+    sigprocmask(0);
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+}
+
+fn inner_handler() {
+    INNER_HANDLED.store(true, Ordering::Release);
+}
+
+fn outer_handler() {
+    // SIGUSR2 is delivered on top of this handler's frame
+    kill(getpid() as usize, SIGUSR2);
+    OUTER_SAW_INNER.store(INNER_HANDLED.load(Ordering::Acquire), Ordering::Release);
+}
+
+fn user_sig_test_nested() {
+    let mut new = SignalAction::default();
+    let mut old = SignalAction::default();
+    new.handler = inner_handler as usize;
+    if sigaction(SIGUSR2, Some(&new), Some(&mut old)) < 0 {
+        panic!("Sigaction failed!");
+    }
+    new.handler = outer_handler as usize;
+    if sigaction(SIGUSR1, Some(&new), Some(&mut old)) < 0 {
+        panic!("Sigaction failed!");
+    }
+    // handlers return normally through the kernel trampoline
+    kill(getpid() as usize, SIGUSR1);
+    if !OUTER_SAW_INNER.load(Ordering::Acquire) {
+        println!("Nested handler did not run!");
+        exit(-1);
+    }
+}
+
+fn alt_stack_handler() {
+    let sp: usize;
+    unsafe {
+        core::arch::asm!("mv {}, sp", out(reg) sp);
+    }
+    let base = core::ptr::addr_of!(ALT_STACK) as usize;
+    ON_ALT_STACK.store((base..base + ALT_STACK_SIZE).contains(&sp), Ordering::Release);
+}
+
+fn user_sig_test_altstack() {
+    let stack = SignalStack {
+        sp: core::ptr::addr_of_mut!(ALT_STACK) as usize,
+        flags: 0,
+        size: ALT_STACK_SIZE,
+    };
+    if sigaltstack(Some(&stack), None) < 0 {
+        panic!("Sigaltstack failed!");
+    }
+    let mut new = SignalAction::default();
+    let mut old = SignalAction::default();
+    new.handler = alt_stack_handler as usize;
+    new.flags = SA_ONSTACK;
+    if sigaction(SIGUSR1, Some(&new), Some(&mut old)) < 0 {
+        panic!("Sigaction failed!");
+    }
+    kill(getpid() as usize, SIGUSR1);
+    if !ON_ALT_STACK.load(Ordering::Acquire) {
+        println!("Handler did not run on the alternate stack!");
+        exit(-1);
+    }
+}
+
+fn queued_handler(_signum: i32, info: &SignalInfo, _frame: usize) {
+    if info.code == SI_QUEUE && info.pid as isize == getpid() {
+        QUEUED_COUNT.fetch_add(1, Ordering::AcqRel);
+        QUEUED_SUM.fetch_add(info.value, Ordering::AcqRel);
+    }
+}
+
+fn user_sig_test_sigqueue() {
+    let mut new = SignalAction::default();
+    let mut old = SignalAction::default();
+    new.handler = queued_handler as usize;
+    new.flags = SA_SIGINFO;
+    if sigaction(SIGRTMIN, Some(&new), Some(&mut old)) < 0 {
+        panic!("Sigaction failed!");
+    }
+    // real-time signals queue up while blocked instead of merging
+    sigprocmask(SignalFlags::SIGRT0.bits());
+    for value in 1..=3 {
+        if sigqueue(getpid() as usize, SIGRTMIN, value) < 0 {
+            println!("Sigqueue failed!");
+            exit(-1);
+        }
+    }
+    sigprocmask(0);
+    if QUEUED_COUNT.load(Ordering::Acquire) != 3 || QUEUED_SUM.load(Ordering::Acquire) != 6 {
+        println!("Queued signals lost!");
+        exit(-1);
+    }
+    // at most SIGQUEUE_MAX (64) are queued, then sending fails with EAGAIN
+    sigprocmask(SignalFlags::SIGRT0.bits());
+    let mut queued = 0;
+    while queued <= 64 && sigqueue(getpid() as usize, SIGRTMIN, 0) == 0 {
+        queued += 1;
+    }
+    if queued != 64
+        || sigqueue(getpid() as usize, SIGRTMIN, 0) != Errno::EAGAIN.ret()
+        || kill(getpid() as usize, SIGRTMIN) != Errno::EAGAIN.ret()
+    {
+        println!("Full signal queue not reported!");
+        exit(-1);
+    }
+    sigprocmask(0);
+}
+
+fn alarm_handler() {
+    ALARMS.fetch_add(1, Ordering::AcqRel);
+}
+
+fn vtalarm_handler() {
+    VTALARMED.store(true, Ordering::Release);
+}
+
+fn user_sig_test_itimer() {
+    let mut new = SignalAction::default();
+    new.handler = alarm_handler as usize;
+    if sigaction(SIGALRM, Some(&new), None) < 0 {
+        panic!("Sigaction failed!");
+    }
+    new.handler = vtalarm_handler as usize;
+    if sigaction(SIGVTALRM, Some(&new), None) < 0 {
+        panic!("Sigaction failed!");
+    }
+    // a periodic real timer keeps firing until disarmed
+    let periodic = ITimerVal {
+        interval: TimeVal::from_ms(50),
+        value: TimeVal::from_ms(50),
+    };
+    setitimer(ITIMER_REAL, &periodic, None);
+    while ALARMS.load(Ordering::Acquire) < 2 {
+        pause();
+    }
+    setitimer(ITIMER_REAL, &ITimerVal::default(), None);
+    let mut curr = ITimerVal::default();
+    getitimer(ITIMER_REAL, &mut curr);
+    if curr.value.sec != 0 || curr.value.usec != 0 {
+        println!("Real timer misbehaved!");
+        exit(-1);
+    }
+    // the virtual timer only runs while we burn user time
+    setitimer(
+        ITIMER_VIRTUAL,
+        &ITimerVal {
+            interval: TimeVal::default(),
+            value: TimeVal::from_ms(20),
+        },
+        None,
+    );
+    let start = get_time();
+    while !VTALARMED.load(Ordering::Acquire) && get_time() - start < 3000 {}
+    if !VTALARMED.load(Ordering::Acquire) {
+        println!("Virtual timer never fired!");
+        exit(-1);
+    }
+    // alarm reports what was left of the previous one
+    alarm(100);
+    if alarm(0) == 0 {
+        println!("Alarm lost!");
+        exit(-1);
+    }
+}
+
+fn user_sig_test_sigtimedwait() {
+    let mut new = SignalAction::default();
+    new.handler = func as usize;
+    if sigaction(SIGUSR1, Some(&new), None) < 0 {
+        panic!("Sigaction failed!");
+    }
+    // a blocked signal is taken by sigwaitinfo instead of the handler
+    sigprocmask(SignalFlags::SIGUSR1.bits());
+    let parent = getpid() as usize;
+    let pid = fork();
+    if pid == 0 {
+        sigqueue(parent, SIGUSR1, 42);
+        exit(0);
+    }
+    let mut info = SignalInfo::default();
+    if sigwaitinfo(SignalFlags::SIGUSR1.bits(), Some(&mut info)) != SIGUSR1 as isize
+        || info.code != SI_QUEUE
+        || info.pid as isize != pid
+        || info.value != 42
+    {
+        println!("Sigwaitinfo returned the wrong signal!");
+        exit(-1);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    // nothing else is coming, so the wait times out
+    let timeout = TimeVal::from_ms(50);
+    if sigtimedwait(SignalFlags::SIGUSR1.bits(), None, Some(&timeout)) != Errno::EAGAIN.ret() {
+        println!("Sigtimedwait did not time out!");
+        exit(-1);
+    }
+}
+
 fn kernel_sig_test_failignorekill() {
     let mut new = SignalAction::default();
     let mut old = SignalAction::default();
@@ -162,7 +425,7 @@ fn run(f: fn()) -> bool {
 
 #[unsafe(no_mangle)]
 pub fn main() -> i32 {
-    let tests: [(fn(), &str); 8] = [
+    let tests: [(fn(), &str); 16] = [
         (user_sig_test_failsignum, "user_sig_test_failsignum"),
         (user_sig_test_kill, "user_sig_test_kill"),
         (
@@ -170,8 +433,19 @@ pub fn main() -> i32 {
             "user_sig_test_multiprocsignals",
         ),
         (user_sig_test_restore, "user_sig_test_restore"),
+        (user_sig_test_nested, "user_sig_test_nested"),
+        (user_sig_test_altstack, "user_sig_test_altstack"),
+        (user_sig_test_sigqueue, "user_sig_test_sigqueue"),
+        (user_sig_test_itimer, "user_sig_test_itimer"),
+        (user_sig_test_sigtimedwait, "user_sig_test_sigtimedwait"),
         (kernel_sig_test_ignore, "kernel_sig_test_ignore"),
         (kernel_sig_test_stop_cont, "kernel_sig_test_stop_cont"),
+        (kernel_sig_test_sig_ign, "kernel_sig_test_sig_ign"),
+        (
+            kernel_sig_test_default_terminate,
+            "kernel_sig_test_default_terminate",
+        ),
+        (kernel_sig_test_sigchld, "kernel_sig_test_sigchld"),
         (
             kernel_sig_test_failignorekill,
             "kernel_sig_test_failignorekill",
@@ -0,0 +1,36 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::{execvp, fork, strace, waitpid};
+
+/// strace <app> [args...]: run an app, logging every syscall it and its
+/// children make
+#[unsafe(no_mangle)]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 2 {
+        println!("usage: strace <app> [args...]");
+        return -1;
+    }
+    let args: Vec<String> = argv[1..].iter().map(|arg| alloc::format!("{}\0", arg)).collect();
+    let mut arg_ptrs: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+    arg_ptrs.push(core::ptr::null());
+    let pid = fork();
+    if pid == 0 {
+        strace(true);
+        execvp(args[0].as_str(), arg_ptrs.as_slice());
+        strace(false);
+        println!("Error when executing '{}'", argv[1]);
+        return -4;
+    }
+    let mut exit_code: i32 = 0;
+    waitpid(pid as usize, &mut exit_code);
+    println!("[{}] +++ exited with {} +++", pid, exit_code);
+    exit_code
+}
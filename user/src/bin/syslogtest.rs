@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::vec;
+use user_lib::{
+    exit, fork, setuid, syslog, waitpid, SYSLOG_ACTION_CLEAR, SYSLOG_ACTION_CONSOLE_LEVEL,
+    SYSLOG_ACTION_READ_ALL, SYSLOG_ACTION_READ_CLEAR, SYSLOG_ACTION_SIZE_BUFFER,
+};
+
+const EPERM: isize = -1;
+const EINVAL: isize = -22;
+
+/// The kernel log keeps the exit of a child, and clearing it drops it
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    let null = core::ptr::null_mut();
+    assert_eq!(syslog(100, null, 0), EINVAL);
+    assert_eq!(syslog(SYSLOG_ACTION_READ_ALL, null, 16), EINVAL);
+    assert_eq!(syslog(SYSLOG_ACTION_CONSOLE_LEVEL, null, 9), EINVAL);
+    let size = syslog(SYSLOG_ACTION_SIZE_BUFFER, null, 0);
+    assert!(size > 0);
+    let mut buf = vec![0u8; size as usize];
+
+    assert_eq!(syslog(SYSLOG_ACTION_CLEAR, null, 0), 0);
+    let pid = fork();
+    if pid == 0 {
+        exit(7);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    let len = syslog(SYSLOG_ACTION_READ_CLEAR, buf.as_mut_ptr(), buf.len());
+    assert!(len > 0);
+    let log = core::str::from_utf8(&buf[..len as usize]).unwrap();
+    let line = format!("pid {} exited with code 7", pid);
+    assert!(log.contains(line.as_str()), "no '{}' in the log", line);
+
+    assert_eq!(syslog(SYSLOG_ACTION_READ_ALL, buf.as_mut_ptr(), buf.len()), 0);
+
+    // others may only read
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(setuid(1000), 0);
+        assert_eq!(syslog(SYSLOG_ACTION_CLEAR, null, 0), EPERM);
+        assert_eq!(syslog(SYSLOG_ACTION_CONSOLE_LEVEL, null, 1), EPERM);
+        assert_eq!(syslog(SYSLOG_ACTION_READ_CLEAR, buf.as_mut_ptr(), buf.len()), EPERM);
+        assert_eq!(syslog(SYSLOG_ACTION_SIZE_BUFFER, null, 0), size);
+        assert!(syslog(SYSLOG_ACTION_READ_ALL, buf.as_mut_ptr(), buf.len()) >= 0);
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("syslogtest passed!");
+    0
+}
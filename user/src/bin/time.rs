@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::{exec, fork, get_time, getrusage, waitpid, RUsage, TimeVal, RUSAGE_CHILDREN};
+
+fn ms(tv: &TimeVal) -> usize {
+    tv.sec * 1000 + tv.usec / 1000
+}
+
+/// time <app> [args...]: run an app and report how long it took
+#[unsafe(no_mangle)]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 2 {
+        println!("usage: time <app> [args...]");
+        return -1;
+    }
+    let args: Vec<String> = argv[1..].iter().map(|arg| alloc::format!("{}\0", arg)).collect();
+    let mut arg_ptrs: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+    arg_ptrs.push(core::ptr::null());
+    let start = get_time();
+    let pid = fork();
+    if pid == 0 {
+        if exec(args[0].as_str(), arg_ptrs.as_slice()) < 0 {
+            println!("Error when executing '{}'", argv[1]);
+            return -4;
+        }
+        unreachable!();
+    }
+    let mut exit_code: i32 = 0;
+    waitpid(pid as usize, &mut exit_code);
+    let real = get_time() - start;
+    let mut usage = RUsage::default();
+    getrusage(RUSAGE_CHILDREN, &mut usage);
+    println!(
+        "real {}ms, user {}ms, sys {}ms",
+        real,
+        ms(&usage.utime),
+        ms(&usage.stime)
+    );
+    println!(
+        "{} minor faults, {} voluntary and {} involuntary context switches",
+        usage.minflt, usage.nvcsw, usage.nivcsw
+    );
+    exit_code
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, getegid, geteuid, getgid, getpid, getuid, kill, setgid, setuid, sleep, waitpid,
+    Errno, SIGKILL, SIGUSR1,
+};
+
+const USER: u32 = 1000;
+const OTHER: u32 = 1001;
+
+#[unsafe(no_mangle)]
+pub fn main() -> i32 {
+    // everything starts out as root
+    assert_eq!((getuid(), geteuid(), getgid(), getegid()), (0, 0, 0, 0));
+    let root_pid = getpid() as usize;
+
+    // a victim owned by another user
+    let victim = fork();
+    if victim == 0 {
+        assert_eq!(setuid(OTHER), 0);
+        loop {
+            sleep(10);
+        }
+    }
+
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(setgid(USER), 0);
+        assert_eq!(setuid(USER), 0);
+        assert_eq!((getuid(), geteuid(), getgid(), getegid()), (USER, USER, USER, USER));
+        // root rights are gone for good
+        assert_eq!(setuid(0), Errno::EPERM.ret());
+        assert_eq!(setgid(0), Errno::EPERM.ret());
+        // only our own user's processes may be signalled
+        assert_eq!(kill(root_pid, SIGUSR1), Errno::EPERM.ret());
+        assert_eq!(kill(victim as usize, SIGKILL), Errno::EPERM.ret());
+        assert_eq!(kill(root_pid, 0), Errno::EPERM.ret());
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid as usize, &mut exit_code);
+    // root may signal anyone
+    assert_eq!(kill(victim as usize, 0), 0);
+    assert_eq!(kill(victim as usize, SIGKILL), 0);
+    let mut victim_code = 0;
+    waitpid(victim as usize, &mut victim_code);
+    assert_eq!(kill(victim as usize, 0), Errno::ESRCH.ret());
+    if exit_code != 0 {
+        println!("uidtest failed");
+        return -1;
+    }
+    println!("uidtest passed!");
+    0
+}
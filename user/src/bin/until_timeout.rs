@@ -4,13 +4,26 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{exec, fork, get_time, kill, waitpid, waitpid_nb, SignalFlags};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use user_lib::{
+    exec, fork, get_time, kill, setitimer, sigaction, waitpid, ITimerVal, SignalAction, TimeVal,
+    ITIMER_REAL, SIGALRM, SIGINT,
+};
+
+static CHILD: AtomicUsize = AtomicUsize::new(0);
+static TIMED_OUT: AtomicBool = AtomicBool::new(false);
+
+/// SIGALRM handler: the child ran out of time
+fn on_alarm() {
+    TIMED_OUT.store(true, Ordering::SeqCst);
+    kill(CHILD.load(Ordering::SeqCst), SIGINT);
+}
 
 #[unsafe(no_mangle)]
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
     assert_eq!(argc, 3, "argc must be 3!");
     let timeout_ms = argv[2]
-        .parse::<isize>()
+        .parse::<usize>()
         .expect("Error when parsing timeout!");
     let pid = fork() as usize;
     if pid == 0 {
@@ -19,27 +32,28 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
             return -4;
         }
     } else {
+        CHILD.store(pid, Ordering::SeqCst);
+        let mut action = SignalAction::default();
+        action.handler = on_alarm as usize;
+        sigaction(SIGALRM, Some(&action), None);
         let start_time = get_time();
-        let mut child_exited = false;
+        let timer = ITimerVal {
+            interval: TimeVal::default(),
+            value: TimeVal::from_ms(timeout_ms),
+        };
+        setitimer(ITIMER_REAL, &timer, None);
         let mut exit_code: i32 = 0;
-        loop {
-            if get_time() - start_time > timeout_ms {
-                break;
-            }
-            if waitpid_nb(pid, &mut exit_code) as usize == pid {
-                child_exited = true;
-                println!(
-                    "child exited in {}ms, exit_code = {}",
-                    get_time() - start_time,
-                    exit_code,
-                );
-            }
-        }
-        if !child_exited {
+        assert_eq!(waitpid(pid, &mut exit_code) as usize, pid);
+        if TIMED_OUT.load(Ordering::SeqCst) {
             println!("child has run for {}ms, kill it!", timeout_ms);
-            kill(pid, SignalFlags::SIGINT.bits());
-            assert_eq!(waitpid(pid, &mut exit_code) as usize, pid);
             println!("exit code of the child is {}", exit_code);
+        } else {
+            setitimer(ITIMER_REAL, &ITimerVal::default(), None);
+            println!(
+                "child exited in {}ms, exit_code = {}",
+                get_time() - start_time,
+                exit_code,
+            );
         }
     }
     0
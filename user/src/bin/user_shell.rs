@@ -13,10 +13,15 @@ const DL: u8 = 0x7fu8;
 const BS: u8 = 0x08u8;
 const LINE_START: &str = ">> ";
 
+use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
 use user_lib::console::getchar;
-use user_lib::{close, dup, exec, fork, open, pipe, waitpid, OpenFlags};
+use user_lib::{
+    close, getpid, killpg, open, pipe, read, setpgid, sigaction, sigreturn, spawn, tcsetpgrp,
+    try_open, waitpid_options, wifstopped, Errno, OpenFlags, SignalAction, SpawnAction, SpawnAttr,
+    SIGCONT, SIGINT, SIGTSTP, SPAWN_SETPGROUP, WNOHANG, WUNTRACED,
+};
 
 #[derive(Debug)]
 struct ProcessArguments {
@@ -74,9 +79,315 @@ impl ProcessArguments {
     }
 }
 
+/// A pipeline started by the shell, all in one process group
+struct Job {
+    id: usize,
+    pgid: usize,
+    /// members that have not exited yet
+    pids: Vec<usize>,
+    command: String,
+    stopped: bool,
+}
+
+impl Job {
+    /// Wait until every member exited or one of them stopped
+    fn wait_foreground(&mut self) {
+        while !self.pids.is_empty() {
+            let mut status: i32 = 0;
+            let pid = waitpid_options(-(self.pgid as isize), &mut status, WUNTRACED);
+            if pid < 0 {
+                self.pids.clear();
+                break;
+            }
+            if wifstopped(status) {
+                self.stopped = true;
+                break;
+            }
+            self.pids.retain(|&p| p != pid as usize);
+        }
+    }
+
+    /// Collect members that exited or stopped in the background
+    fn poll(&mut self) {
+        loop {
+            let mut status: i32 = 0;
+            let pid = waitpid_options(-(self.pgid as isize), &mut status, WUNTRACED | WNOHANG);
+            if pid == Errno::ECHILD.ret() {
+                self.pids.clear();
+            }
+            if pid < 0 {
+                break;
+            }
+            if wifstopped(status) {
+                self.stopped = true;
+            } else {
+                self.pids.retain(|&p| p != pid as usize);
+            }
+        }
+    }
+
+    fn state(&self) -> &'static str {
+        if self.stopped { "Stopped" } else { "Running" }
+    }
+}
+
+/// The shell has to survive ^C and ^Z typed at its own prompt
+fn ignore_signal() {
+    sigreturn();
+}
+
+/// Give the console to `pgid`
+fn set_foreground(pgid: usize) {
+    tcsetpgrp(0, pgid);
+}
+
+/// Spawn a process group running `line`, None if nothing was started
+fn launch(line: &str) -> Option<Job> {
+    let splited: Vec<_> = line.split('|').collect();
+    let process_arguments_list: Vec<_> = splited
+        .iter()
+        .map(|&cmd| ProcessArguments::new(cmd))
+        .collect();
+    let mut valid = true;
+    for (i, process_args) in process_arguments_list.iter().enumerate() {
+        if process_args.args_copy.is_empty() {
+            valid = false;
+        } else if i == 0 {
+            if !process_args.output.is_empty() {
+                valid = false;
+            }
+        } else if i == process_arguments_list.len() - 1 {
+            if !process_args.input.is_empty() {
+                valid = false;
+            }
+        } else if !process_args.output.is_empty() || !process_args.input.is_empty() {
+            valid = false;
+        }
+    }
+    if process_arguments_list.len() == 1 {
+        valid = !process_arguments_list[0].args_copy.is_empty();
+    }
+    if !valid {
+        println!("Invalid command: Inputs/Outputs cannot be correctly binded!");
+        return None;
+    }
+    // create pipes
+    let mut pipes_fd: Vec<[usize; 2]> = Vec::new();
+    if !process_arguments_list.is_empty() {
+        for _ in 0..process_arguments_list.len() - 1 {
+            let mut pipe_fd = [0usize; 2];
+            pipe(&mut pipe_fd);
+            pipes_fd.push(pipe_fd);
+        }
+    }
+    let mut pgid = 0;
+    let mut children: Vec<_> = Vec::new();
+    for (i, process_argument) in process_arguments_list.iter().enumerate() {
+        let input = &process_argument.input;
+        let output = &process_argument.output;
+        let args_copy = &process_argument.args_copy;
+        let args_addr = &process_argument.args_addr;
+        // the child gets our fd table rearranged by these actions
+        let mut actions: Vec<SpawnAction> = Vec::new();
+        let mut opened: Vec<usize> = Vec::new();
+        // redirect input
+        if !input.is_empty() {
+            let input_fd = match try_open(input.as_str(), OpenFlags::RDONLY) {
+                Ok(fd) => fd,
+                Err(errno) => {
+                    println!("Error when opening file {}: {}", input, errno);
+                    continue;
+                }
+            };
+            actions.push(SpawnAction::dup2(input_fd, 0));
+            actions.push(SpawnAction::close(input_fd));
+            opened.push(input_fd);
+        }
+        // redirect output
+        if !output.is_empty() {
+            let output_fd = match try_open(output.as_str(), OpenFlags::CREATE | OpenFlags::WRONLY) {
+                Ok(fd) => fd,
+                Err(errno) => {
+                    println!("Error when opening file {}: {}", output, errno);
+                    for fd in opened {
+                        close(fd);
+                    }
+                    continue;
+                }
+            };
+            actions.push(SpawnAction::dup2(output_fd, 1));
+            actions.push(SpawnAction::close(output_fd));
+            opened.push(output_fd);
+        }
+        // receive input from the previous process
+        if i > 0 {
+            actions.push(SpawnAction::dup2(pipes_fd[i - 1][0], 0));
+        }
+        // send output to the next process
+        if i < process_arguments_list.len() - 1 {
+            actions.push(SpawnAction::dup2(pipes_fd[i][1], 1));
+        }
+        // the child must not keep any pipe end open by accident
+        for pipe_fd in pipes_fd.iter() {
+            actions.push(SpawnAction::close(pipe_fd[0]));
+            actions.push(SpawnAction::close(pipe_fd[1]));
+        }
+        // the first process leads the job's group, the rest join it
+        let mut attr = SpawnAttr::new(actions.as_slice());
+        attr.flags = SPAWN_SETPGROUP;
+        attr.pgroup = pgid;
+        let pid = spawn(args_copy[0].as_str(), args_addr.as_slice(), Some(&attr));
+        for fd in opened {
+            close(fd);
+        }
+        if let Err(errno) = Errno::check(pid) {
+            println!("Error when executing: {}", errno);
+            continue;
+        }
+        if pgid == 0 {
+            pgid = pid as usize;
+        }
+        children.push(pid as usize);
+    }
+    for pipe_fd in pipes_fd.iter() {
+        close(pipe_fd[0]);
+        close(pipe_fd[1]);
+    }
+    if children.is_empty() {
+        return None;
+    }
+    Some(Job {
+        id: 0,
+        pgid,
+        pids: children,
+        command: String::from(line),
+        stopped: false,
+    })
+}
+
+/// Job named by `arg` ("%n" or "n"), or the most recent one
+fn find_job(jobs: &[Job], arg: Option<&str>) -> Option<usize> {
+    match arg {
+        None => jobs.len().checked_sub(1),
+        Some(arg) => {
+            let id: usize = arg.trim_start_matches('%').parse().ok()?;
+            jobs.iter().position(|job| job.id == id)
+        }
+    }
+}
+
+/// Run `job` in the foreground, keeping it in `jobs` if it stops
+fn foreground(jobs: &mut Vec<Job>, mut job: Job, shell_pgid: usize) {
+    set_foreground(job.pgid);
+    job.wait_foreground();
+    set_foreground(shell_pgid);
+    if job.stopped {
+        if job.id == 0 {
+            job.id = jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        }
+        println!("[{}] Stopped\t{}", job.id, job.command);
+        jobs.push(job);
+    }
+}
+
+/// Handle `jobs`, `fg` and `bg`, returns false for anything else
+fn builtin(jobs: &mut Vec<Job>, line: &str, shell_pgid: usize) -> bool {
+    let mut words = line.split(' ').filter(|word| !word.is_empty());
+    let name = words.next().unwrap_or("");
+    let arg = words.next();
+    match name {
+        "jobs" => {
+            for job in jobs.iter() {
+                println!("[{}] {}\t{}", job.id, job.state(), job.command);
+            }
+        }
+        "fg" | "bg" => {
+            let Some(idx) = find_job(jobs, arg) else {
+                println!("{}: no such job", name);
+                return true;
+            };
+            if name == "fg" {
+                let mut job = jobs.remove(idx);
+                println!("{}", job.command);
+                if job.stopped {
+                    job.stopped = false;
+                    killpg(job.pgid, SIGCONT);
+                }
+                foreground(jobs, job, shell_pgid);
+            } else {
+                let job = &mut jobs[idx];
+                if job.stopped {
+                    job.stopped = false;
+                    killpg(job.pgid, SIGCONT);
+                }
+                println!("[{}] {} &", job.id, job.command);
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Report background jobs that finished since the last prompt
+fn reap_jobs(jobs: &mut Vec<Job>) {
+    for job in jobs.iter_mut() {
+        job.poll();
+        if job.pids.is_empty() {
+            println!("[{}] Done\t{}", job.id, job.command);
+        }
+    }
+    jobs.retain(|job| !job.pids.is_empty());
+}
+
+/// Run the commands in the file at `path`, one pipeline per line; this
+/// is what a `#!user_shell` script executes
+fn run_script(path: &str) -> i32 {
+    let fd = open(format!("{}\0", path).as_str(), OpenFlags::RDONLY);
+    if fd < 0 {
+        println!("user_shell: cannot open {}", path);
+        return -1;
+    }
+    let mut content: Vec<u8> = Vec::new();
+    let mut buf = [0u8; 256];
+    loop {
+        let len = read(fd as usize, &mut buf);
+        if len <= 0 {
+            break;
+        }
+        content.extend_from_slice(&buf[..len as usize]);
+    }
+    close(fd as usize);
+    let Ok(text) = core::str::from_utf8(&content) else {
+        println!("user_shell: {} is not text", path);
+        return -1;
+    };
+    for line in text.lines() {
+        let line = line.trim();
+        // the #! line is a comment too
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(mut job) = launch(line) {
+            job.wait_foreground();
+        }
+    }
+    0
+}
+
 #[unsafe(no_mangle)]
-pub fn main() -> i32 {
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc >= 2 {
+        return run_script(argv[1]);
+    }
     println!("Rust user shell");
+    setpgid(0, 0);
+    let shell_pgid = getpid() as usize;
+    set_foreground(shell_pgid);
+    let mut ignore = SignalAction::default();
+    ignore.handler = ignore_signal as usize;
+    sigaction(SIGINT, Some(&ignore), Some(&mut SignalAction::default()));
+    sigaction(SIGTSTP, Some(&ignore), Some(&mut SignalAction::default()));
+    let mut jobs: Vec<Job> = Vec::new();
     let mut line: String = String::new();
     print!("{}", LINE_START);
     loop {
@@ -84,117 +395,24 @@ pub fn main() -> i32 {
         match c {
             LF | CR => {
                 println!("");
-                if !line.is_empty() {
-                    let splited: Vec<_> = line.as_str().split('|').collect();
-                    let process_arguments_list: Vec<_> = splited
-                        .iter()
-                        .map(|&cmd| ProcessArguments::new(cmd))
-                        .collect();
-                    let mut valid = true;
-                    for (i, process_args) in process_arguments_list.iter().enumerate() {
-                        if i == 0 {
-                            if !process_args.output.is_empty() {
-                                valid = false;
-                            }
-                        } else if i == process_arguments_list.len() - 1 {
-                            if !process_args.input.is_empty() {
-                                valid = false;
-                            }
-                        } else if !process_args.output.is_empty() || !process_args.input.is_empty()
-                        {
-                            valid = false;
-                        }
-                    }
-                    if process_arguments_list.len() == 1 {
-                        valid = true;
-                    }
-                    if !valid {
-                        println!("Invalid command: Inputs/Outputs cannot be correctly binded!");
-                    } else {
-                        // create pipes
-                        let mut pipes_fd: Vec<[usize; 2]> = Vec::new();
-                        if !process_arguments_list.is_empty() {
-                            for _ in 0..process_arguments_list.len() - 1 {
-                                let mut pipe_fd = [0usize; 2];
-                                pipe(&mut pipe_fd);
-                                pipes_fd.push(pipe_fd);
-                            }
-                        }
-                        let mut children: Vec<_> = Vec::new();
-                        for (i, process_argument) in process_arguments_list.iter().enumerate() {
-                            let pid = fork();
-                            if pid == 0 {
-                                let input = &process_argument.input;
-                                let output = &process_argument.output;
-                                let args_copy = &process_argument.args_copy;
-                                let args_addr = &process_argument.args_addr;
-                                // redirect input
-                                if !input.is_empty() {
-                                    let input_fd = open(input.as_str(), OpenFlags::RDONLY);
-                                    if input_fd == -1 {
-                                        println!("Error when opening file {}", input);
-                                        return -4;
-                                    }
-                                    let input_fd = input_fd as usize;
-                                    close(0);
-                                    assert_eq!(dup(input_fd), 0);
-                                    close(input_fd);
-                                }
-                                // redirect output
-                                if !output.is_empty() {
-                                    let output_fd = open(
-                                        output.as_str(),
-                                        OpenFlags::CREATE | OpenFlags::WRONLY,
-                                    );
-                                    if output_fd == -1 {
-                                        println!("Error when opening file {}", output);
-                                        return -4;
-                                    }
-                                    let output_fd = output_fd as usize;
-                                    close(1);
-                                    assert_eq!(dup(output_fd), 1);
-                                    close(output_fd);
-                                }
-                                // receive input from the previous process
-                                if i > 0 {
-                                    close(0);
-                                    let read_end = pipes_fd.get(i - 1).unwrap()[0];
-                                    assert_eq!(dup(read_end), 0);
-                                }
-                                // send output to the next process
-                                if i < process_arguments_list.len() - 1 {
-                                    close(1);
-                                    let write_end = pipes_fd.get(i).unwrap()[1];
-                                    assert_eq!(dup(write_end), 1);
-                                }
-                                // close all pipe ends inherited from the parent process
-                                for pipe_fd in pipes_fd.iter() {
-                                    close(pipe_fd[0]);
-                                    close(pipe_fd[1]);
-                                }
-                                // execute new application
-                                if exec(args_copy[0].as_str(), args_addr.as_slice()) == -1 {
-                                    println!("Error when executing!");
-                                    return -4;
-                                }
-                                unreachable!();
-                            } else {
-                                children.push(pid);
-                            }
-                        }
-                        for pipe_fd in pipes_fd.iter() {
-                            close(pipe_fd[0]);
-                            close(pipe_fd[1]);
-                        }
-                        let mut exit_code: i32 = 0;
-                        for pid in children.into_iter() {
-                            let exit_pid = waitpid(pid as usize, &mut exit_code);
-                            assert_eq!(pid, exit_pid);
-                            //println!("Shell: Process {} exited with code {}", pid, exit_code);
+                let command = line.trim();
+                if !command.is_empty() && !builtin(&mut jobs, command, shell_pgid) {
+                    let (command, background) = match command.strip_suffix('&') {
+                        Some(command) => (command.trim(), true),
+                        None => (command, false),
+                    };
+                    if let Some(mut job) = launch(command) {
+                        if background {
+                            job.id = jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+                            println!("[{}] {}", job.id, job.pgid);
+                            jobs.push(job);
+                        } else {
+                            foreground(&mut jobs, job, shell_pgid);
                         }
                     }
-                    line.clear();
                 }
+                line.clear();
+                reap_jobs(&mut jobs);
                 print!("{}", LINE_START);
             }
             BS | DL => {
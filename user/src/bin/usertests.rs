@@ -12,6 +12,18 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("filetest_simple\0", "\0", "\0", "\0", 0),
     ("cat\0", "filea\0", "\0", "\0", 0),
     ("cmdline_args\0", "1\0", "2\0", "3\0", 0),
+    ("envtest\0", "\0", "\0", "\0", 0),
+    ("scripttest\0", "\0", "\0", "\0", 0),
+    ("uidtest\0", "\0", "\0", "\0", 0),
+    ("rlimittest\0", "\0", "\0", "\0", 0),
+    ("ptracetest\0", "\0", "\0", "\0", 0),
+    ("coretest\0", "\0", "\0", "\0", 0),
+    ("efaulttest\0", "\0", "\0", "\0", 0),
+    ("linuxtest\0", "\0", "\0", "\0", 0),
+    ("syslogtest\0", "\0", "\0", "\0", 0),
+    ("proftest\0", "\0", "\0", "\0", 0),
+    ("perftest\0", "\0", "\0", "\0", 0),
+    ("strace\0", "hello_world\0", "\0", "\0", 0),
     ("exit\0", "\0", "\0", "\0", 0),
     ("fantastic_text\0", "\0", "\0", "\0", 0),
     ("forktest_simple\0", "\0", "\0", "\0", 0),
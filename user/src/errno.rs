@@ -0,0 +1,110 @@
+//! Error numbers the kernel fails syscalls with
+//!
+//! A failing syscall returns the negated number; `Errno::check` turns such
+//! a return value into a `Result`.
+use core::fmt;
+
+#[repr(isize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Errno {
+    EPERM = 1,
+    ENOENT = 2,
+    ESRCH = 3,
+    EINTR = 4,
+    E2BIG = 7,
+    ENOEXEC = 8,
+    EBADF = 9,
+    ECHILD = 10,
+    EAGAIN = 11,
+    ENOMEM = 12,
+    EFAULT = 14,
+    EEXIST = 17,
+    ENOTDIR = 20,
+    EISDIR = 21,
+    EINVAL = 22,
+    EMFILE = 24,
+    ENOTTY = 25,
+    ESPIPE = 29,
+    ENOSYS = 38,
+    ENOTEMPTY = 39,
+}
+
+/// Result of a syscall: its non-negative return value or why it failed
+pub type SysResult<T = usize> = Result<T, Errno>;
+
+const ALL: [Errno; 20] = [
+    Errno::EPERM,
+    Errno::ENOENT,
+    Errno::ESRCH,
+    Errno::EINTR,
+    Errno::E2BIG,
+    Errno::ENOEXEC,
+    Errno::EBADF,
+    Errno::ECHILD,
+    Errno::EAGAIN,
+    Errno::ENOMEM,
+    Errno::EFAULT,
+    Errno::EEXIST,
+    Errno::ENOTDIR,
+    Errno::EISDIR,
+    Errno::EINVAL,
+    Errno::EMFILE,
+    Errno::ENOTTY,
+    Errno::ESPIPE,
+    Errno::ENOSYS,
+    Errno::ENOTEMPTY,
+];
+
+impl Errno {
+    /// The value a syscall failing with this error returns
+    pub fn ret(self) -> isize {
+        -(self as isize)
+    }
+
+    /// The error a syscall return value `ret` stands for, None if it did
+    /// not fail or the number is unknown to us
+    pub fn from_ret(ret: isize) -> Option<Self> {
+        ALL.iter().copied().find(|errno| errno.ret() == ret)
+    }
+
+    /// Split a syscall return value into success and failure
+    pub fn check(ret: isize) -> SysResult {
+        if ret >= 0 {
+            Ok(ret as usize)
+        } else {
+            // an unknown error is still an error
+            Err(Self::from_ret(ret).unwrap_or(Errno::EINVAL))
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Errno::EPERM => "Operation not permitted",
+            Errno::ENOENT => "No such file or directory",
+            Errno::ESRCH => "No such process",
+            Errno::EINTR => "Interrupted system call",
+            Errno::E2BIG => "Argument list too long",
+            Errno::ENOEXEC => "Exec format error",
+            Errno::EBADF => "Bad file descriptor",
+            Errno::ECHILD => "No child processes",
+            Errno::EAGAIN => "Try again",
+            Errno::ENOMEM => "Out of memory",
+            Errno::EFAULT => "Bad address",
+            Errno::EEXIST => "File exists",
+            Errno::ENOTDIR => "Not a directory",
+            Errno::EISDIR => "Is a directory",
+            Errno::EINVAL => "Invalid argument",
+            Errno::EMFILE => "Too many open files",
+            Errno::ENOTTY => "Not a typewriter",
+            Errno::ESPIPE => "Illegal seek",
+            Errno::ENOSYS => "Function not implemented",
+            Errno::ENOTEMPTY => "Directory not empty",
+        }
+    }
+}
+
+impl fmt::Display for Errno {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
@@ -5,6 +5,7 @@
 
 #[macro_use]
 pub mod console;
+pub mod errno;
 mod lang_items;
 mod syscall;
 
@@ -12,11 +13,15 @@ extern crate alloc;
 #[macro_use]
 extern crate bitflags;
 
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
 use core::ptr::addr_of_mut;
 use syscall::*;
 
+pub use errno::{Errno, SysResult};
+
 const USER_HEAP_SIZE: usize = 32768;
 
 static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
@@ -29,31 +34,134 @@ pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
 }
 
-#[unsafe(no_mangle)]
-#[unsafe(link_section = ".text.entry")]
-pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
+/// ELF note the kernel looks for to give us its own syscall numbering;
+/// programs without it are taken for Linux ones
+#[repr(C)]
+struct AcoreNote {
+    namesz: u32,
+    descsz: u32,
+    kind: u32,
+    name: [u8; 8],
+    desc: u32,
+}
+
+#[used]
+#[unsafe(link_section = ".note.acore")]
+static ACORE_NOTE: AcoreNote = AcoreNote {
+    namesz: 6,
+    descsz: 4,
+    kind: 1,
+    name: *b"ACore\0\0\0",
+    desc: 1,
+};
+
+// The kernel leaves argc, argv, envp and auxv on the stack; a0 is free.
+core::arch::global_asm!(
+    ".section .text.entry",
+    ".globl _start",
+    "_start:",
+    "    mv a0, sp",
+    "    tail {start}",
+    start = sym start_main,
+);
+
+/// Read argc, argv and envp off the initial stack at `sp`, then run main
+extern "C" fn start_main(sp: *const usize) -> ! {
     unsafe {
         use core::ptr::addr_of_mut;
         let heap_ptr = addr_of_mut!(HEAP_SPACE) as *mut u8;
         HEAP.lock().init(heap_ptr as usize, USER_HEAP_SIZE);
     }
+    let argc = unsafe { sp.read_volatile() };
+    let argv = unsafe { sp.add(1) };
+    let envp = unsafe { argv.add(argc + 1) };
     let mut v: Vec<&'static str> = Vec::new();
     for i in 0..argc {
-        let str_start =
-            unsafe { ((argv + i * core::mem::size_of::<usize>()) as *const usize).read_volatile() };
-        let len = (0usize..)
-            .find(|i| unsafe { ((str_start + *i) as *const u8).read_volatile() == 0 })
-            .unwrap();
-        v.push(
-            core::str::from_utf8(unsafe {
-                core::slice::from_raw_parts(str_start as *const u8, len)
-            })
-            .unwrap(),
-        );
+        v.push(unsafe { c_str(argv.add(i).read_volatile()) });
+    }
+    let mut i = 0;
+    loop {
+        let env = unsafe { envp.add(i).read_volatile() };
+        if env == 0 {
+            break;
+        }
+        environ().push(format!("{}\0", unsafe { c_str(env) }));
+        i += 1;
+    }
+    unsafe {
+        AUXV = envp.add(i + 1) as usize;
     }
     exit(main(argc, v.as_slice()));
 }
 
+/// The NUL-terminated string at `start`
+unsafe fn c_str(start: usize) -> &'static str {
+    let len = (0usize..)
+        .find(|i| unsafe { ((start + *i) as *const u8).read_volatile() == 0 })
+        .unwrap();
+    core::str::from_utf8(unsafe { core::slice::from_raw_parts(start as *const u8, len) }).unwrap()
+}
+
+/// Start of the auxiliary vector on the initial stack
+static mut AUXV: usize = 0;
+
+pub const AT_PHDR: usize = 3;
+pub const AT_PHENT: usize = 4;
+pub const AT_PHNUM: usize = 5;
+pub const AT_PAGESZ: usize = 6;
+pub const AT_ENTRY: usize = 9;
+pub const AT_RANDOM: usize = 25;
+
+/// Value of auxiliary vector entry `key` the kernel passed at startup
+pub fn getauxval(key: usize) -> Option<usize> {
+    let mut entry = unsafe { AUXV } as *const usize;
+    loop {
+        let (k, v) = unsafe { (entry.read_volatile(), entry.add(1).read_volatile()) };
+        match k {
+            0 => return None,
+            k if k == key => return Some(v),
+            _ => entry = unsafe { entry.add(2) },
+        }
+    }
+}
+
+/// Our environment as "NAME=value\0" strings, passed on by exec and spawn
+static mut ENVIRON: Vec<String> = Vec::new();
+
+fn environ() -> &'static mut Vec<String> {
+    unsafe { &mut *addr_of_mut!(ENVIRON) }
+}
+
+/// Value of environment variable `name`
+pub fn getenv(name: &str) -> Option<String> {
+    environ().iter().find_map(|env| {
+        let (key, value) = env.trim_end_matches('\0').split_once('=')?;
+        (key == name).then(|| String::from(value))
+    })
+}
+
+/// Set environment variable `name` to `value`, replacing any old value
+pub fn setenv(name: &str, value: &str) -> isize {
+    if name.is_empty() || name.contains('=') || name.contains('\0') || value.contains('\0') {
+        return Errno::EINVAL.ret();
+    }
+    unsetenv(name);
+    environ().push(format!("{}={}\0", name, value));
+    0
+}
+
+pub fn unsetenv(name: &str) {
+    environ().retain(|env| env.split_once('=').map(|(key, _)| key) != Some(name));
+}
+
+/// Null-terminated pointer array to our environment strings, valid until
+/// the environment next changes
+fn envp() -> Vec<*const u8> {
+    let mut envp: Vec<*const u8> = environ().iter().map(|env| env.as_ptr()).collect();
+    envp.push(core::ptr::null());
+    envp
+}
+
 #[linkage = "weak"]
 #[unsafe(no_mangle)]
 fn main(_argc: usize, _argv: &[&str]) -> i32 {
@@ -103,6 +211,35 @@ pub fn mkdir(path: &str) -> isize {
 pub fn rmdir(path: &str) -> isize {
     sys_rmdir(path)
 }
+
+// The calls above, with failures as an `Errno`
+pub fn try_open(path: &str, flags: OpenFlags) -> SysResult {
+    Errno::check(open(path, flags))
+}
+pub fn try_close(fd: usize) -> SysResult<()> {
+    Errno::check(close(fd)).map(|_| ())
+}
+pub fn try_read(fd: usize, buf: &mut [u8]) -> SysResult {
+    Errno::check(read(fd, buf))
+}
+pub fn try_write(fd: usize, buf: &[u8]) -> SysResult {
+    Errno::check(write(fd, buf))
+}
+pub fn try_lseek(fd: usize, offset: isize, whence: usize) -> SysResult {
+    Errno::check(lseek(fd, offset, whence))
+}
+pub fn try_delete(path: &str) -> SysResult<()> {
+    Errno::check(delete(path)).map(|_| ())
+}
+pub fn try_rename(old_path: &str, new_path: &str) -> SysResult<()> {
+    Errno::check(rename(old_path, new_path)).map(|_| ())
+}
+pub fn try_mkdir(path: &str) -> SysResult<()> {
+    Errno::check(mkdir(path)).map(|_| ())
+}
+pub fn try_rmdir(path: &str) -> SysResult<()> {
+    Errno::check(rmdir(path)).map(|_| ())
+}
 pub fn exit(exit_code: i32) -> ! {
     sys_exit(exit_code);
 }
@@ -112,22 +249,333 @@ pub fn yield_() -> isize {
 pub fn get_time() -> isize {
     sys_get_time()
 }
+
+/// Units per second of the clock_t values from `times`
+pub const CLK_TCK: usize = 100;
+
+/// CPU times in clock_t units
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    /// reaped children, and what they reaped
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+
+/// Resource usage, laid out like `struct rusage`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RUsage {
+    pub utime: TimeVal,
+    pub stime: TimeVal,
+    pub maxrss: usize,
+    pub ixrss: usize,
+    pub idrss: usize,
+    pub isrss: usize,
+    pub minflt: usize,
+    pub majflt: usize,
+    pub nswap: usize,
+    pub inblock: usize,
+    pub oublock: usize,
+    pub msgsnd: usize,
+    pub msgrcv: usize,
+    pub nsignals: usize,
+    pub nvcsw: usize,
+    pub nivcsw: usize,
+}
+
+/// Fill in `tms` and return the clock_t ticks since boot
+pub fn times(tms: &mut Tms) -> isize {
+    sys_times(tms)
+}
+pub fn getrusage(who: isize, usage: &mut RUsage) -> isize {
+    sys_getrusage(who, usage)
+}
+
+/// CPU time in seconds, SIGXCPU at the soft limit and SIGKILL at the hard one
+pub const RLIMIT_CPU: usize = 0;
+/// Stack size in bytes of images exec'd from now on
+pub const RLIMIT_STACK: usize = 3;
+/// Largest core file in bytes, 0 turns core dumps off
+pub const RLIMIT_CORE: usize = 4;
+/// Live children
+pub const RLIMIT_NPROC: usize = 6;
+/// Open file descriptors
+pub const RLIMIT_NOFILE: usize = 7;
+/// Address space in bytes of images exec'd from now on
+pub const RLIMIT_AS: usize = 9;
+pub const RLIM_INFINITY: usize = usize::MAX;
+
+/// Soft limit `cur`, which may be raised up to the hard limit `max`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RLimit {
+    pub cur: usize,
+    pub max: usize,
+}
+
+pub fn getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    sys_getrlimit(resource, rlim)
+}
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    sys_setrlimit(resource, rlim)
+}
+
+/// Wall-clock timer, raises SIGALRM
+pub const ITIMER_REAL: usize = 0;
+/// User CPU time timer, raises SIGVTALRM
+pub const ITIMER_VIRTUAL: usize = 1;
+/// User and kernel CPU time timer, raises SIGPROF
+pub const ITIMER_PROF: usize = 2;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            sec: ms / 1000,
+            usec: ms % 1000 * 1000,
+        }
+    }
+}
+
+/// Interval timer setting: fire after `value`, then every `interval`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ITimerVal {
+    pub interval: TimeVal,
+    pub value: TimeVal,
+}
+
+pub fn getitimer(which: usize, curr_value: &mut ITimerVal) -> isize {
+    sys_getitimer(which, curr_value)
+}
+pub fn setitimer(which: usize, new_value: &ITimerVal, old_value: Option<&mut ITimerVal>) -> isize {
+    sys_setitimer(
+        which,
+        new_value,
+        old_value.map_or(core::ptr::null_mut(), |v| v),
+    )
+}
+pub const PTRACE_TRACEME: usize = 0;
+pub const PTRACE_PEEKTEXT: usize = 1;
+pub const PTRACE_PEEKDATA: usize = 2;
+pub const PTRACE_POKETEXT: usize = 4;
+pub const PTRACE_POKEDATA: usize = 5;
+pub const PTRACE_CONT: usize = 7;
+pub const PTRACE_KILL: usize = 8;
+pub const PTRACE_SINGLESTEP: usize = 9;
+pub const PTRACE_GETREGS: usize = 12;
+pub const PTRACE_SETREGS: usize = 13;
+pub const PTRACE_ATTACH: usize = 16;
+pub const PTRACE_DETACH: usize = 17;
+
+/// Registers of a traced child: the pc, then x1..x31
+pub type UserRegs = [usize; 32];
+
+/// Trace a child as Linux ptrace does: PEEK requests store the word at
+/// `data`, GETREGS and SETREGS take a `*mut UserRegs` in `data`, and CONT,
+/// SINGLESTEP and DETACH deliver signal `data` unless it is 0. A traced
+/// child stops whenever a signal is about to reach it; waitpid reports
+/// those stops without WUNTRACED.
+pub fn ptrace(request: usize, pid: usize, addr: usize, data: usize) -> isize {
+    sys_ptrace(request, pid, addr, data)
+}
+
+pub const SYSLOG_ACTION_READ: usize = 2;
+pub const SYSLOG_ACTION_READ_ALL: usize = 3;
+pub const SYSLOG_ACTION_READ_CLEAR: usize = 4;
+pub const SYSLOG_ACTION_CLEAR: usize = 5;
+pub const SYSLOG_ACTION_CONSOLE_OFF: usize = 6;
+pub const SYSLOG_ACTION_CONSOLE_ON: usize = 7;
+pub const SYSLOG_ACTION_CONSOLE_LEVEL: usize = 8;
+pub const SYSLOG_ACTION_SIZE_UNREAD: usize = 9;
+pub const SYSLOG_ACTION_SIZE_BUFFER: usize = 10;
+
+/// Read or control the kernel log as Linux klogctl does: READ and
+/// READ_ALL copy up to `len` bytes of log lines to `buf`, CONSOLE_LEVEL
+/// takes the level (1 to 8) in `len`. READ does not wait for new lines.
+pub fn syslog(action: usize, buf: *mut u8, len: usize) -> isize {
+    sys_syslog(action, buf, len)
+}
+
+/// Deliver SIGALRM in `secs` seconds, 0 cancels. Returns the seconds left
+/// on the previous alarm.
+pub fn alarm(secs: usize) -> usize {
+    let new_value = ITimerVal {
+        interval: TimeVal::default(),
+        value: TimeVal { sec: secs, usec: 0 },
+    };
+    let mut old_value = ITimerVal::default();
+    setitimer(ITIMER_REAL, &new_value, Some(&mut old_value));
+    // round a partial second up so a pending alarm never reads as none
+    old_value.value.sec + (old_value.value.usec != 0) as usize
+}
 pub fn getpid() -> isize {
     sys_getpid()
 }
+pub fn getuid() -> u32 {
+    sys_getuid() as u32
+}
+pub fn geteuid() -> u32 {
+    sys_geteuid() as u32
+}
+pub fn getgid() -> u32 {
+    sys_getgid() as u32
+}
+pub fn getegid() -> u32 {
+    sys_getegid() as u32
+}
+/// As root, become `uid` for good; otherwise only drop back to the real uid
+pub fn setuid(uid: u32) -> isize {
+    sys_setuid(uid)
+}
+pub fn setgid(gid: u32) -> isize {
+    sys_setgid(gid)
+}
+/// Log every syscall this process and the children it creates from now
+/// on make to the console, or stop doing so
+pub fn strace(enable: bool) -> isize {
+    sys_strace(enable as usize)
+}
+pub const PROF_START: usize = 0;
+pub const PROF_STOP: usize = 1;
+pub const PROF_READ: usize = 2;
+
+/// Hits on one pc in a sampling profile
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProfSample {
+    pub pc: usize,
+    pub count: usize,
+    /// 1 for a kernel pc, 0 for a user one
+    pub kernel: usize,
+}
+
+/// Sample where `pid` (0 for us, or a child) is on every timer tick:
+/// PROF_START clears the samples and starts, PROF_STOP stops, PROF_READ
+/// fills `samples` and returns how many there are in all. Samples survive
+/// exec; a child's stay readable until it is reaped, see WNOWAIT.
+pub fn profile(cmd: usize, pid: usize, samples: &mut [ProfSample]) -> isize {
+    sys_profile(cmd, pid, samples.as_mut_ptr() as *mut u8, samples.len())
+}
 pub fn fork() -> isize {
     sys_fork()
 }
+/// Run `path` with `args` in place of this program, passing on our
+/// environment
 pub fn exec(path: &str, args: &[*const u8]) -> isize {
-    sys_exec(path, args)
+    sys_exec(path, args, envp().as_slice())
+}
+/// Directories `execvp` searches when PATH is not set
+pub const DEFAULT_PATH: &str = "/";
+
+/// Like `exec`, but a `file` without a '/' is looked up in each directory
+/// of the colon-separated PATH in turn. Only returns if all of them fail,
+/// with the error of the last one.
+pub fn execvp(file: &str, args: &[*const u8]) -> isize {
+    let name = file.trim_end_matches('\0');
+    if name.contains('/') {
+        return exec(file, args);
+    }
+    let path = getenv("PATH").unwrap_or_else(|| String::from(DEFAULT_PATH));
+    let mut ret = Errno::ENOENT.ret();
+    for dir in path.split(':') {
+        let candidate = if dir.is_empty() || dir.ends_with('/') {
+            format!("{}{}\0", dir, name)
+        } else {
+            format!("{}/{}\0", dir, name)
+        };
+        ret = exec(candidate.as_str(), args);
+    }
+    ret
+}
+/// Like `exec`, but with environment `envs` ("NAME=value\0" strings)
+pub fn execve(path: &str, args: &[*const u8], envs: &[*const u8]) -> isize {
+    sys_exec(path, args, envs)
+}
+
+/// `SpawnAttr::flags`: put the child in process group `pgroup`, 0 for a
+/// new group led by the child
+pub const SPAWN_SETPGROUP: u32 = 2;
+/// `SpawnAttr::flags`: start the child with signal mask `sigmask`
+pub const SPAWN_SETSIGMASK: u32 = 8;
+/// `SpawnAction::op` values
+pub const SPAWN_DUP2: u32 = 1;
+pub const SPAWN_CLOSE: u32 = 2;
+
+/// File action applied in the child before it starts
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnAction {
+    pub op: u32,
+    pub fd: usize,
+    pub newfd: usize,
+}
+
+impl SpawnAction {
+    pub fn dup2(fd: usize, newfd: usize) -> Self {
+        Self { op: SPAWN_DUP2, fd, newfd }
+    }
+    pub fn close(fd: usize) -> Self {
+        Self { op: SPAWN_CLOSE, fd, newfd: 0 }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnAttr {
+    pub flags: u32,
+    pub pgroup: usize,
+    pub sigmask: u64,
+    pub actions: *const SpawnAction,
+    pub actions_len: usize,
+}
+
+impl SpawnAttr {
+    pub fn new(actions: &[SpawnAction]) -> Self {
+        Self {
+            flags: 0,
+            pgroup: 0,
+            sigmask: 0,
+            actions: actions.as_ptr(),
+            actions_len: actions.len(),
+        }
+    }
+}
+
+/// Start `path` with `args` in a new child process without forking,
+/// passing on our environment. Returns the child's pid, or a negated
+/// `Errno`.
+pub fn spawn(path: &str, args: &[*const u8], attr: Option<&SpawnAttr>) -> isize {
+    spawnve(path, args, envp().as_slice(), attr)
+}
+/// Like `spawn`, with the environment `envs` instead of ours
+pub fn spawnve(
+    path: &str,
+    args: &[*const u8],
+    envs: &[*const u8],
+    attr: Option<&SpawnAttr>,
+) -> isize {
+    sys_spawn(path, args, envs, attr.map_or(core::ptr::null(), |attr| attr))
 }
 pub fn wait(exit_code: &mut i32) -> isize {
     loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
+        match sys_waitpid(-1, exit_code as *mut _, 0) {
+            ret if ret == Errno::EAGAIN.ret() => {
                 yield_();
             }
-            // -1 or a real pid
+            // -ECHILD or a real pid
             exit_pid => return exit_pid,
         }
     }
@@ -135,18 +583,133 @@ pub fn wait(exit_code: &mut i32) -> isize {
 
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
     loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => {
+        match sys_waitpid(pid as isize, exit_code as *mut _, 0) {
+            ret if ret == Errno::EAGAIN.ret() => {
                 yield_();
             }
-            // -1 or a real pid
+            // -ECHILD or a real pid
             exit_pid => return exit_pid,
         }
     }
 }
 
 pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> isize {
-    sys_waitpid(pid as isize, exit_code as *mut _)
+    sys_waitpid(pid as isize, exit_code as *mut _, 0)
+}
+
+/// Return -EAGAIN at once instead of waiting
+pub const WNOHANG: usize = 1;
+/// Also report children stopped by a signal
+pub const WUNTRACED: usize = 2;
+/// Also report stopped children resumed by SIGCONT
+pub const WCONTINUED: usize = 8;
+/// Report an exited child but leave it to be waited for again
+pub const WNOWAIT: usize = 0x0100_0000;
+
+/// waitpid taking Linux-style `pid` (-1 any child, 0 our process group,
+/// < -1 the group -pid) and options
+pub fn waitpid_options(pid: isize, exit_code: &mut i32, options: usize) -> isize {
+    loop {
+        match sys_waitpid(pid, exit_code as *mut _, options & !WNOHANG) {
+            ret if ret == Errno::EAGAIN.ret() && options & WNOHANG == 0 => {
+                yield_();
+            }
+            // -ECHILD, -EAGAIN or a real pid
+            exit_pid => return exit_pid,
+        }
+    }
+}
+
+pub fn wifstopped(status: i32) -> bool {
+    status & 0xff == 0x7f && (status >> 8) & 0xff != 0
+}
+pub fn wstopsig(status: i32) -> i32 {
+    (status >> 8) & 0xff
+}
+pub fn wifcontinued(status: i32) -> bool {
+    status == 0xffff
+}
+
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+pub fn setsid() -> isize {
+    sys_setsid()
+}
+
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+
+/// Foreground process group of the console behind `fd`
+pub fn tcgetpgrp(fd: usize) -> isize {
+    let mut pgid: u32 = 0;
+    match sys_ioctl(fd, TIOCGPGRP, &mut pgid as *mut u32 as usize) {
+        0 => pgid as isize,
+        err => err,
+    }
+}
+pub fn tcsetpgrp(fd: usize, pgid: usize) -> isize {
+    let pgid = pgid as u32;
+    sys_ioctl(fd, TIOCSPGRP, &pgid as *const u32 as usize)
+}
+
+pub const PERF_TYPE_HARDWARE: u32 = 0;
+pub const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+pub const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+/// `PerfEventAttr::flags`: open the event disabled
+pub const PERF_ATTR_DISABLED: u64 = 1;
+
+pub const PERF_EVENT_IOC_ENABLE: usize = 0x2400;
+pub const PERF_EVENT_IOC_DISABLE: usize = 0x2401;
+pub const PERF_EVENT_IOC_RESET: usize = 0x2403;
+
+/// The leading fields of Linux's `struct perf_event_attr`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PerfEventAttr {
+    pub type_: u32,
+    pub size: u32,
+    pub config: u64,
+    pub sample_period: u64,
+    pub sample_type: u64,
+    pub read_format: u64,
+    pub flags: u64,
+}
+
+/// Count hardware cycles or instructions of this process, in user and
+/// kernel mode and wherever it runs. `pid` must be 0, `cpu` and `group_fd`
+/// -1. Returns an fd; `perf_event_read` gives the count.
+pub fn perf_event_open(attr: &PerfEventAttr, pid: isize, cpu: isize, group_fd: isize) -> isize {
+    sys_perf_event_open(attr as *const _ as *const u8, pid, cpu, group_fd, 0)
+}
+/// PERF_EVENT_IOC_ENABLE, DISABLE or RESET on the event `fd`
+pub fn perf_event_ioctl(fd: usize, request: usize) -> isize {
+    sys_ioctl(fd, request, 0)
+}
+pub fn perf_event_read(fd: usize) -> u64 {
+    let mut count = [0u8; 8];
+    read(fd, &mut count);
+    u64::from_ne_bytes(count)
+}
+
+/// Cycles of this hart, readable in user mode without a syscall
+pub fn rdcycle() -> u64 {
+    let cycle: u64;
+    unsafe {
+        core::arch::asm!("rdcycle {}", out(reg) cycle);
+    }
+    cycle
+}
+/// Instructions retired on this hart
+pub fn rdinstret() -> u64 {
+    let instret: u64;
+    unsafe {
+        core::arch::asm!("rdinstret {}", out(reg) instret);
+    }
+    instret
 }
 
 pub fn sleep(period_ms: usize) {
@@ -161,7 +724,10 @@ pub fn sleep(period_ms: usize) {
 #[derive(Debug, Clone, Copy)]
 pub struct SignalAction {
     pub handler: usize,
+    /// signals blocked while the handler runs
     pub mask: SignalFlags,
+    /// SA_* flags
+    pub flags: u32,
 }
 
 impl Default for SignalAction {
@@ -169,10 +735,65 @@ impl Default for SignalAction {
         Self {
             handler: 0,
             mask: SignalFlags::empty(),
+            flags: 0,
         }
     }
 }
 
+/// Handler is `fn(signum: i32, info: &SignalInfo, frame: usize)`
+pub const SA_SIGINFO: u32 = 0x4;
+/// Run the handler on the alternate signal stack
+pub const SA_ONSTACK: u32 = 0x0800_0000;
+/// Do not block the signal while its own handler runs
+pub const SA_NODEFER: u32 = 0x4000_0000;
+/// Restore the default disposition once the handler is entered
+pub const SA_RESETHAND: u32 = 0x8000_0000;
+
+/// Sent by kill
+pub const SI_USER: i32 = 0;
+/// Raised by the kernel itself
+pub const SI_KERNEL: i32 = 0x80;
+/// Sent by sigqueue
+pub const SI_QUEUE: i32 = -1;
+/// SIGCHLD codes: child exited, stopped, continued
+pub const CLD_EXITED: i32 = 1;
+pub const CLD_STOPPED: i32 = 5;
+pub const CLD_CONTINUED: i32 = 6;
+
+/// Details of a delivered signal, passed to SA_SIGINFO handlers
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SignalInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    _pad: i32,
+    /// sending process
+    pub pid: i32,
+    pub uid: u32,
+    /// sigqueue value, or child status for SIGCHLD
+    pub value: usize,
+}
+
+/// Alternate signal stack, as passed to `sigaltstack`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SignalStack {
+    pub sp: usize,
+    pub flags: u32,
+    pub size: usize,
+}
+
+/// Alternate signal stack is in use
+pub const SS_ONSTACK: u32 = 1;
+/// No alternate signal stack
+pub const SS_DISABLE: u32 = 2;
+
+/// `SignalAction::handler` value for the default disposition
+pub const SIG_DFL: usize = 0;
+/// `SignalAction::handler` value for ignoring the signal
+pub const SIG_IGN: usize = 1;
+
 pub const SIGDEF: i32 = 0; // Default signal handling
 pub const SIGHUP: i32 = 1;
 pub const SIGINT: i32 = 2;
@@ -205,9 +826,13 @@ pub const SIGWINCH: i32 = 28;
 pub const SIGIO: i32 = 29;
 pub const SIGPWR: i32 = 30;
 pub const SIGSYS: i32 = 31;
+/// First queued real-time signal
+pub const SIGRTMIN: i32 = 32;
+/// Last queued real-time signal
+pub const SIGRTMAX: i32 = 63;
 
 bitflags! {
-    pub struct SignalFlags: i32 {
+    pub struct SignalFlags: u64 {
         const SIGDEF = 1; // Default signal handling
         const SIGHUP = 1 << 1;
         const SIGINT = 1 << 2;
@@ -240,11 +865,49 @@ bitflags! {
         const SIGIO = 1 << 29;
         const SIGPWR = 1 << 30;
         const SIGSYS = 1 << 31;
+        // real-time signals, SIGRTMIN + n
+        const SIGRT0 = 1 << 32;
+        const SIGRT1 = 1 << 33;
+        const SIGRT2 = 1 << 34;
+        const SIGRT3 = 1 << 35;
+        const SIGRT4 = 1 << 36;
+        const SIGRT5 = 1 << 37;
+        const SIGRT6 = 1 << 38;
+        const SIGRT7 = 1 << 39;
+        const SIGRT8 = 1 << 40;
+        const SIGRT9 = 1 << 41;
+        const SIGRT10 = 1 << 42;
+        const SIGRT11 = 1 << 43;
+        const SIGRT12 = 1 << 44;
+        const SIGRT13 = 1 << 45;
+        const SIGRT14 = 1 << 46;
+        const SIGRT15 = 1 << 47;
+        const SIGRT16 = 1 << 48;
+        const SIGRT17 = 1 << 49;
+        const SIGRT18 = 1 << 50;
+        const SIGRT19 = 1 << 51;
+        const SIGRT20 = 1 << 52;
+        const SIGRT21 = 1 << 53;
+        const SIGRT22 = 1 << 54;
+        const SIGRT23 = 1 << 55;
+        const SIGRT24 = 1 << 56;
+        const SIGRT25 = 1 << 57;
+        const SIGRT26 = 1 << 58;
+        const SIGRT27 = 1 << 59;
+        const SIGRT28 = 1 << 60;
+        const SIGRT29 = 1 << 61;
+        const SIGRT30 = 1 << 62;
+        const SIGRT31 = 1 << 63;
     }
 }
 
 pub fn kill(pid: usize, signum: i32) -> isize {
-    sys_kill(pid, signum)
+    sys_kill(pid as isize, signum)
+}
+
+/// Signal every process in group `pgid`, 0 for our own group
+pub fn killpg(pgid: usize, signum: i32) -> isize {
+    sys_kill(-(pgid as isize), signum)
 }
 
 pub fn sigaction(
@@ -259,10 +922,52 @@ pub fn sigaction(
     )
 }
 
-pub fn sigprocmask(mask: u32) -> isize {
+pub fn sigaltstack(ss: Option<&SignalStack>, old_ss: Option<&mut SignalStack>) -> isize {
+    sys_sigaltstack(
+        ss.map_or(core::ptr::null(), |s| s),
+        old_ss.map_or(core::ptr::null_mut(), |s| s),
+    )
+}
+
+pub fn sigprocmask(mask: u64) -> isize {
     sys_sigprocmask(mask)
 }
 
+/// Atomically install `mask` and sleep until a signal handler has run;
+/// the previous mask is restored afterwards. Always returns -EINTR.
+pub fn sigsuspend(mask: u64) -> isize {
+    sys_sigsuspend(mask)
+}
+
+/// Sleep until a signal handler has run. Always returns -EINTR.
+pub fn pause() -> isize {
+    // block everything first so nothing slips in before we sleep
+    let mask = sigprocmask(u64::MAX) as u64;
+    let ret = sigsuspend(mask);
+    sigprocmask(mask);
+    ret
+}
+
+/// Dequeue one of the signals in `set` without running its handler,
+/// waiting up to `timeout`, or forever if None. Returns the signal number,
+/// -EAGAIN on timeout or -EINTR if another signal arrived first.
+pub fn sigtimedwait(set: u64, info: Option<&mut SignalInfo>, timeout: Option<&TimeVal>) -> isize {
+    sys_sigtimedwait(
+        set,
+        info.map_or(core::ptr::null_mut(), |i| i),
+        timeout.map_or(core::ptr::null(), |t| t),
+    )
+}
+
+pub fn sigwaitinfo(set: u64, info: Option<&mut SignalInfo>) -> isize {
+    sigtimedwait(set, info, None)
+}
+
+/// Queue `signum` with `value` on `pid`; real-time signals queue every call
+pub fn sigqueue(pid: usize, signum: i32, value: usize) -> isize {
+    sys_sigqueue(pid, signum, value)
+}
+
 pub fn sigreturn() -> isize {
     sys_sigreturn()
 }
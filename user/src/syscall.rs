@@ -1,8 +1,9 @@
 use core::arch::asm;
 
-use crate::SignalAction;
+use crate::{ITimerVal, RLimit, RUsage, SignalAction, SignalInfo, SignalStack, SpawnAttr, TimeVal, Tms};
 
 const SYSCALL_DUP: usize = 24;
+const SYSCALL_IOCTL: usize = 29;
 const SYSCALL_OPEN: usize = 56;
 const SYSCALL_CLOSE: usize = 57;
 const SYSCALL_DELETE: usize = 58;
@@ -14,19 +15,45 @@ const SYSCALL_RENAME: usize = 82;
 const SYSCALL_MKDIR: usize = 83;
 const SYSCALL_RMDIR: usize = 84;
 const SYSCALL_EXIT: usize = 93;
+const SYSCALL_GETITIMER: usize = 102;
+const SYSCALL_SETITIMER: usize = 103;
+const SYSCALL_SYSLOG: usize = 116;
+const SYSCALL_PTRACE: usize = 117;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGALTSTACK: usize = 132;
+const SYSCALL_SIGSUSPEND: usize = 133;
 const SYSCALL_SIGACTION: usize = 134;
 const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGTIMEDWAIT: usize = 137;
+const SYSCALL_SIGQUEUE: usize = 138;
 const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_TIMES: usize = 153;
+const SYSCALL_SETGID: usize = 144;
+const SYSCALL_SETUID: usize = 146;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_SETSID: usize = 157;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETRUSAGE: usize = 165;
 const SYSCALL_GET_TIME: usize = 169;
 const SYSCALL_GETPID: usize = 172;
+const SYSCALL_GETUID: usize = 174;
+const SYSCALL_GETEUID: usize = 175;
+const SYSCALL_GETGID: usize = 176;
+const SYSCALL_GETEGID: usize = 177;
 const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
+const SYSCALL_PERF_EVENT_OPEN: usize = 241;
 const SYSCALL_WAITPID: usize = 260;
+const SYSCALL_SPAWN: usize = 400;
+const SYSCALL_STRACE: usize = 401;
+const SYSCALL_PROFILE: usize = 402;
 
 
-fn syscall(id: usize, args: [usize; 3]) -> isize {
+/// Arguments go in a0-a5 and the syscall id in a7, as on Linux
+fn syscall(id: usize, args: [usize; 6]) -> isize {
     let mut ret: isize;
     unsafe {
         asm!(
@@ -34,6 +61,9 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
             inlateout("x10") args[0] => ret,
             in("x11") args[1],
             in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
             in("x17") id
         );
     }
@@ -41,89 +71,204 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
 }
 
 pub fn sys_dup(fd: usize) -> isize {
-    syscall(SYSCALL_DUP, [fd, 0, 0])
+    syscall(SYSCALL_DUP, [fd, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    syscall(SYSCALL_IOCTL, [fd, request, arg, 0, 0, 0])
 }
 
 pub fn sys_open(path: &str, flags: u32) -> isize {
-    syscall(SYSCALL_OPEN, [path.as_ptr() as usize, flags as usize, 0])
+    syscall(SYSCALL_OPEN, [path.as_ptr() as usize, flags as usize, 0, 0, 0, 0])
 }
 
 pub fn sys_close(fd: usize) -> isize {
-    syscall(SYSCALL_CLOSE, [fd, 0, 0])
+    syscall(SYSCALL_CLOSE, [fd, 0, 0, 0, 0, 0])
 }
 
 pub fn sys_delete(path: &str) -> isize {
-    syscall(SYSCALL_DELETE, [path.as_ptr() as usize, 0, 0])
+    syscall(SYSCALL_DELETE, [path.as_ptr() as usize, 0, 0, 0, 0, 0])
 }
 pub fn sys_pipe(pipe: &mut [usize]) -> isize {
-    syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
+    syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0, 0, 0, 0])
 }
 
 
 pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
-    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence])
+    syscall(SYSCALL_LSEEK, [fd, offset as usize, whence, 0, 0, 0])
 }
 
 pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
     syscall(
         SYSCALL_READ,
-        [fd, buffer.as_mut_ptr() as usize, buffer.len()],
+        [fd, buffer.as_mut_ptr() as usize, buffer.len(), 0, 0, 0],
     )
 }
 
 pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
-    syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
+    syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len(), 0, 0, 0])
 }
 
 pub fn sys_rename(old_path: &str, new_path: &str) -> isize {
     syscall(
         SYSCALL_RENAME,
-        [old_path.as_ptr() as usize, new_path.as_ptr() as usize, 0],
+        [old_path.as_ptr() as usize, new_path.as_ptr() as usize, 0, 0, 0, 0],
     )
 }
 
 pub fn sys_mkdir(path: &str) -> isize {
-    syscall(SYSCALL_MKDIR, [path.as_ptr() as usize, 0, 0])
+    syscall(SYSCALL_MKDIR, [path.as_ptr() as usize, 0, 0, 0, 0, 0])
 }
 
 pub fn sys_rmdir(path: &str) -> isize {
-    syscall(SYSCALL_RMDIR, [path.as_ptr() as usize, 0, 0])
+    syscall(SYSCALL_RMDIR, [path.as_ptr() as usize, 0, 0, 0, 0, 0])
 }
 
 pub fn sys_exit(exit_code: i32) -> ! {
-    syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0]);
+    syscall(SYSCALL_EXIT, [exit_code as usize, 0, 0, 0, 0, 0]);
     panic!("sys_exit never returns!");
 }
 
 pub fn sys_yield() -> isize {
-    syscall(SYSCALL_YIELD, [0, 0, 0])
+    syscall(SYSCALL_YIELD, [0, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_kill(pid: isize, signal: i32) -> isize {
+    syscall(SYSCALL_KILL, [pid as usize, signal as usize, 0, 0, 0, 0])
+}
+
+pub fn sys_getitimer(which: usize, curr_value: *mut ITimerVal) -> isize {
+    syscall(SYSCALL_GETITIMER, [which, curr_value as usize, 0, 0, 0, 0])
+}
+
+pub fn sys_setitimer(which: usize, new_value: *const ITimerVal, old_value: *mut ITimerVal) -> isize {
+    syscall(
+        SYSCALL_SETITIMER,
+        [which, new_value as usize, old_value as usize, 0, 0, 0],
+    )
+}
+
+pub fn sys_syslog(action: usize, buf: *mut u8, len: usize) -> isize {
+    syscall(SYSCALL_SYSLOG, [action, buf as usize, len, 0, 0, 0])
+}
+
+pub fn sys_ptrace(request: usize, pid: usize, addr: usize, data: usize) -> isize {
+    syscall(SYSCALL_PTRACE, [request, pid, addr, data, 0, 0])
+}
+
+pub fn sys_times(tms: *mut Tms) -> isize {
+    syscall(SYSCALL_TIMES, [tms as usize, 0, 0, 0, 0, 0])
 }
 
-pub fn sys_kill(pid: usize, signal: i32) -> isize {
-    syscall(SYSCALL_KILL, [pid, signal as usize, 0])
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> isize {
+    syscall(SYSCALL_GETRLIMIT, [resource, rlim as usize, 0, 0, 0, 0])
+}
+
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> isize {
+    syscall(SYSCALL_SETRLIMIT, [resource, rlim as usize, 0, 0, 0, 0])
+}
+
+pub fn sys_getrusage(who: isize, usage: *mut RUsage) -> isize {
+    syscall(SYSCALL_GETRUSAGE, [who as usize, usage as usize, 0, 0, 0, 0])
 }
 
 pub fn sys_get_time() -> isize {
-    syscall(SYSCALL_GET_TIME, [0, 0, 0])
+    syscall(SYSCALL_GET_TIME, [0, 0, 0, 0, 0, 0])
 }
 
 pub fn sys_getpid() -> isize {
-    syscall(SYSCALL_GETPID, [0, 0, 0])
+    syscall(SYSCALL_GETPID, [0, 0, 0, 0, 0, 0])
 }
 
 pub fn sys_fork() -> isize {
-    syscall(SYSCALL_FORK, [0, 0, 0])
+    syscall(SYSCALL_FORK, [0, 0, 0, 0, 0, 0])
 }
 
-pub fn sys_exec(path: &str, args: &[*const u8]) -> isize {
+pub fn sys_perf_event_open(
+    attr: *const u8,
+    pid: isize,
+    cpu: isize,
+    group_fd: isize,
+    flags: usize,
+) -> isize {
+    syscall(
+        SYSCALL_PERF_EVENT_OPEN,
+        [attr as usize, pid as usize, cpu as usize, group_fd as usize, flags, 0],
+    )
+}
+
+pub fn sys_exec(path: &str, args: &[*const u8], envp: &[*const u8]) -> isize {
     syscall(
         SYSCALL_EXEC,
-        [path.as_ptr() as usize, args.as_ptr() as usize, 0],
+        [path.as_ptr() as usize, args.as_ptr() as usize, envp.as_ptr() as usize, 0, 0, 0],
     )
 }
 
-pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
-    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, 0])
+pub fn sys_spawn(
+    path: &str,
+    args: &[*const u8],
+    envp: &[*const u8],
+    attr: *const SpawnAttr,
+) -> isize {
+    syscall(
+        SYSCALL_SPAWN,
+        [
+            path.as_ptr() as usize,
+            args.as_ptr() as usize,
+            envp.as_ptr() as usize,
+            attr as usize,
+            0,
+            0,
+        ],
+    )
+}
+
+pub fn sys_strace(enable: usize) -> isize {
+    syscall(SYSCALL_STRACE, [enable, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_profile(cmd: usize, pid: usize, buf: *mut u8, len: usize) -> isize {
+    syscall(SYSCALL_PROFILE, [cmd, pid, buf as usize, len, 0, 0])
+}
+
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32, options: usize) -> isize {
+    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, options, 0, 0, 0])
+}
+
+pub fn sys_getuid() -> isize {
+    syscall(SYSCALL_GETUID, [0, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_geteuid() -> isize {
+    syscall(SYSCALL_GETEUID, [0, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_getgid() -> isize {
+    syscall(SYSCALL_GETGID, [0, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_getegid() -> isize {
+    syscall(SYSCALL_GETEGID, [0, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_setuid(uid: u32) -> isize {
+    syscall(SYSCALL_SETUID, [uid as usize, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_setgid(gid: u32) -> isize {
+    syscall(SYSCALL_SETGID, [gid as usize, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_SETPGID, [pid, pgid, 0, 0, 0, 0])
+}
+
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall(SYSCALL_GETPGID, [pid, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_setsid() -> isize {
+    syscall(SYSCALL_SETSID, [0, 0, 0, 0, 0, 0])
 }
 
 pub fn sys_sigaction(
@@ -133,14 +278,33 @@ pub fn sys_sigaction(
 ) -> isize {
     syscall(
         SYSCALL_SIGACTION,
-        [signum as usize, action as usize, old_action as usize],
+        [signum as usize, action as usize, old_action as usize, 0, 0, 0],
+    )
+}
+
+pub fn sys_sigaltstack(ss: *const SignalStack, old_ss: *mut SignalStack) -> isize {
+    syscall(SYSCALL_SIGALTSTACK, [ss as usize, old_ss as usize, 0, 0, 0, 0])
+}
+
+pub fn sys_sigprocmask(mask: u64) -> isize {
+    syscall(SYSCALL_SIGPROCMASK, [mask as usize, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_sigsuspend(mask: u64) -> isize {
+    syscall(SYSCALL_SIGSUSPEND, [mask as usize, 0, 0, 0, 0, 0])
+}
+
+pub fn sys_sigtimedwait(set: u64, info: *mut SignalInfo, timeout: *const TimeVal) -> isize {
+    syscall(
+        SYSCALL_SIGTIMEDWAIT,
+        [set as usize, info as usize, timeout as usize, 0, 0, 0],
     )
 }
 
-pub fn sys_sigprocmask(mask: u32) -> isize {
-    syscall(SYSCALL_SIGPROCMASK, [mask as usize, 0, 0])
+pub fn sys_sigqueue(pid: usize, signum: i32, value: usize) -> isize {
+    syscall(SYSCALL_SIGQUEUE, [pid, signum as usize, value, 0, 0, 0])
 }
 
 pub fn sys_sigreturn() -> isize {
-    syscall(SYSCALL_SIGRETURN, [0, 0, 0])
+    syscall(SYSCALL_SIGRETURN, [0, 0, 0, 0, 0, 0])
 }